@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use anyhow::Result;
+use docopt::Docopt;
+
+use trk_io::{cluster::QuickBundles, Reader, Writer};
+
+static USAGE: &str = "
+Cluster the streamlines of a TrackVis (.trk) file with QuickBundles. Save the centroids and,
+optionally, the streamlines of each cluster in their own file.
+
+Usage:
+  trk_cluster <input> <centroids> [options]
+  trk_cluster (-h | --help)
+  trk_cluster (-v | --version)
+
+Options:
+  -t --threshold=<mm>  Maximum distance between a streamline and a centroid. [default: 10]
+  -n --nb-points=<n>   Number of points of the centroids. [default: 12]
+  -o --output=<dir>    Save the streamlines of cluster i in <dir>/cluster_i.trk.
+  -h --help            Show this screen.
+  -v --version         Show version.
+";
+
+fn main() -> Result<()> {
+    let version = String::from(env!("CARGO_PKG_VERSION"));
+    let args = Docopt::new(USAGE)
+        .and_then(|dopt| dopt.version(Some(version)).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let threshold = args.get_str("--threshold").parse::<f32>()?;
+    let nb_points = args.get_str("--nb-points").parse::<usize>()?;
+
+    let mut reader = Reader::new(args.get_str("<input>"))?;
+    let tractogram = reader.tractogram()?;
+    let clusters =
+        QuickBundles::new(threshold).nb_points(nb_points).cluster(&tractogram.streamlines)?;
+
+    // The centroids don't have any scalars or properties
+    let mut header = reader.header.clone();
+    header.clear_scalars_and_properties();
+    let mut writer = Writer::new(args.get_str("<centroids>"), Some(&header))?;
+    for centroid in &clusters.centroids {
+        writer.write(centroid)?;
+    }
+    writer.finish()?;
+
+    let output = args.get_str("--output");
+    if !output.is_empty() {
+        for c in 0..clusters.len() {
+            let path = Path::new(output).join(format!("cluster_{}.trk", c));
+            let mut writer = Writer::new(path, Some(&reader.header))?;
+            for i in clusters.members(c) {
+                writer.write(tractogram.item(i))?;
+            }
+            writer.finish()?;
+        }
+    }
+
+    println!("{} streamlines in {} clusters", tractogram.streamlines.len(), clusters.len());
+    Ok(())
+}
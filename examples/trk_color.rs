@@ -2,7 +2,7 @@ use anyhow::Result;
 use docopt::Docopt;
 use trk_io::{Header, Point, Reader, Writer};
 
-static USAGE: &'static str = "
+static USAGE: &str = "
 Color a TrackVis (.trk) file.
 
 This will add 3 scalars (color_x, color_y, color_z) per point. Please note that coloring by 'local'
@@ -38,31 +38,35 @@ fn main() -> Result<()> {
         let r = args.get_str("<r>").parse::<u32>()?;
         let g = args.get_str("<g>").parse::<u32>()?;
         let b = args.get_str("<b>").parse::<u32>()?;
-        uniform(reader, header, args.get_str("<output>"), r, g, b);
+        uniform(reader, header, args.get_str("<output>"), r, g, b)?;
     } else if args.get_bool("local") {
-        local(reader, header, args.get_str("<output>"));
+        local(reader, header, args.get_str("<output>"))?;
     }
 
     Ok(())
 }
 
-fn uniform(reader: Reader, header: Header, write_to: &str, r: u32, g: u32, b: u32) {
+fn uniform(reader: Reader, header: Header, write_to: &str, r: u32, g: u32, b: u32) -> Result<()> {
     let (r, g, b) = (r as f32, g as f32, b as f32);
-    let mut writer = Writer::new(write_to, Some(&header)).unwrap();
-    for (streamline, mut scalars, properties) in reader.into_iter() {
+    let mut writer = Writer::new(write_to, Some(&header))?;
+    for item in reader.into_iter() {
+        let (streamline, mut scalars, properties) = item?;
         for _ in 0..streamline.len() {
             scalars.push(r);
             scalars.push(g);
             scalars.push(b);
         }
 
-        writer.write((streamline, scalars, properties));
+        writer.write((streamline, scalars, properties))?;
     }
+    writer.finish()?;
+    Ok(())
 }
 
-fn local(reader: Reader, header: Header, write_to: &str) {
-    let mut writer = Writer::new(write_to, Some(&header)).unwrap();
-    for (streamline, mut scalars, properties) in reader.into_iter() {
+fn local(reader: Reader, header: Header, write_to: &str) -> Result<()> {
+    let mut writer = Writer::new(write_to, Some(&header))?;
+    for item in reader.into_iter() {
+        let (streamline, mut scalars, properties) = item?;
         let mut add = |p1: &Point, p2: &Point| {
             let x = p2.x - p1.x;
             let y = p2.y - p1.y;
@@ -83,6 +87,8 @@ fn local(reader: Reader, header: Header, write_to: &str) {
         // Manage last point
         add(&streamline[streamline.len() - 2], &streamline[streamline.len() - 1]);
 
-        writer.write((streamline, scalars, properties));
+        writer.write((streamline, scalars, properties))?;
     }
+    writer.finish()?;
+    Ok(())
 }
@@ -0,0 +1,47 @@
+use anyhow::Result;
+use docopt::Docopt;
+
+use trk_io::{compress, Reader, Writer};
+
+static USAGE: &str = "
+Compress a TrackVis (.trk) file by removing the points that are not needed to keep the shape of
+the streamlines. Streamlines are read and written one at a time, so any file size can be
+compressed.
+
+Usage:
+  trk_compress <input> <output> [options]
+  trk_compress (-h | --help)
+  trk_compress (-v | --version)
+
+Options:
+  --tol-error=<mm>    Maximum distance between a removed point and the compressed streamline,
+                      in mm. [default: 0.01]
+  --max-segment=<mm>  Maximum length of a segment of the compressed streamline, in mm.
+                      [default: 10]
+  -h --help           Show this screen.
+  -v --version        Show version.
+";
+
+fn main() -> Result<()> {
+    let version = String::from(env!("CARGO_PKG_VERSION"));
+    let args = Docopt::new(USAGE)
+        .and_then(|dopt| dopt.version(Some(version)).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let tol_error = args.get_str("--tol-error").parse::<f32>()?;
+    let max_segment_length = args.get_str("--max-segment").parse::<f32>()?;
+
+    let reader = Reader::new(args.get_str("<input>"))?;
+    let mut writer = Writer::new(args.get_str("<output>"), Some(&reader.header))?;
+
+    let mut nb_points_read = 0;
+    for item in reader {
+        let item = item?;
+        nb_points_read += item.0.len();
+        writer.write(compress::compress_item(item, tol_error, max_segment_length))?;
+    }
+
+    let stats = writer.finish()?;
+    println!("Kept {} points out of {}", stats.nb_points, nb_points_read);
+    Ok(())
+}
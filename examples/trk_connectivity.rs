@@ -0,0 +1,74 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use anyhow::Result;
+use docopt::Docopt;
+
+use trk_io::{
+    connectivity::{Atlas, ConnectivityWeight},
+    Header,
+};
+
+static USAGE: &str = "
+Build the connectivity matrix of a TrackVis (.trk) file, using the labels of a NIfTI parcellation
+on the same grid at both endpoints of each streamline. The matrix is saved as CSV, with the labels
+on the first row and column.
+
+Usage:
+  trk_connectivity <input> <atlas> <output> [options]
+  trk_connectivity (-h | --help)
+  trk_connectivity (-v | --version)
+
+Options:
+  --weight=<w>          count, length or property. [default: count]
+  --property=<name>     Property to average when using '--weight property'.
+  --assignments=<path>  Save the endpoint labels of each streamline in this CSV file.
+  -h --help             Show this screen.
+  -v --version          Show version.
+";
+
+fn main() -> Result<()> {
+    let version = String::from(env!("CARGO_PKG_VERSION"));
+    let args = Docopt::new(USAGE)
+        .and_then(|dopt| dopt.version(Some(version)).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let input = args.get_str("<input>");
+    let weight = match args.get_str("--weight") {
+        "count" => ConnectivityWeight::Count,
+        "length" => ConnectivityWeight::MeanLength,
+        "property" => ConnectivityWeight::mean_property(
+            &Header::from_trk(input)?,
+            args.get_str("--property"),
+        )?,
+        w => anyhow::bail!("Unknown weight {:?}", w),
+    };
+    let assignments_path = args.get_str("--assignments");
+
+    let atlas = Atlas::from_nifti(args.get_str("<atlas>"))?;
+    let connectivity = atlas.connectivity_trk(input, weight, !assignments_path.is_empty())?;
+
+    let mut f = BufWriter::new(File::create(args.get_str("<output>"))?);
+    for label in &connectivity.labels {
+        write!(f, ",{}", label)?;
+    }
+    writeln!(f)?;
+    for (label, row) in connectivity.labels.iter().zip(connectivity.matrix.rows()) {
+        write!(f, "{}", label)?;
+        for value in row {
+            write!(f, ",{}", value)?;
+        }
+        writeln!(f)?;
+    }
+
+    if let Some(assignments) = connectivity.assignments {
+        let mut f = BufWriter::new(File::create(assignments_path)?);
+        for pair in assignments {
+            let (a, b) = pair.unwrap_or((0, 0));
+            writeln!(f, "{},{}", a, b)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use anyhow::{Context, Result};
+use docopt::Docopt;
+
+use trk_io::{metrics, Reader, Writer};
+
+static USAGE: &str = "
+Keep only the streamlines of a TrackVis (.trk) file with an acceptable length and number of
+points. Streamlines are read and written one at a time, so any file size can be filtered.
+
+Usage:
+  trk_filter <input> <output> [options]
+  trk_filter (-h | --help)
+  trk_filter (-v | --version)
+
+Options:
+  --min-length=<mm>   Minimum length of a streamline, in mm. [default: 0]
+  --max-length=<mm>   Maximum length of a streamline, in mm. [default: inf]
+  --min-points=<n>    Minimum number of points of a streamline. [default: 0]
+  --max-points=<n>    Maximum number of points of a streamline.
+  -h --help           Show this screen.
+  -v --version        Show version.
+";
+
+fn main() -> Result<()> {
+    let version = String::from(env!("CARGO_PKG_VERSION"));
+    let args = Docopt::new(USAGE)
+        .and_then(|dopt| dopt.version(Some(version)).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let min_length = args.get_str("--min-length").parse::<f32>()?;
+    let max_length = args.get_str("--max-length").parse::<f32>()?;
+    let min_points = args.get_str("--min-points").parse::<usize>()?;
+    // No default, because docopt can't write usize::MAX
+    let max_points = match args.get_str("--max-points") {
+        "" => usize::MAX,
+        n => n.parse::<usize>().with_context(|| format!("Invalid --max-points {:?}", n))?,
+    };
+
+    let reader = Reader::new(args.get_str("<input>"))?;
+    let mut writer = Writer::new(args.get_str("<output>"), Some(&reader.header))?;
+
+    let mut nb_read = 0;
+    for item in reader {
+        let item = item?;
+        nb_read += 1;
+
+        let length = metrics::length(&item.0);
+        let nb_points = item.0.len();
+        if min_length <= length
+            && length <= max_length
+            && min_points <= nb_points
+            && nb_points <= max_points
+        {
+            writer.write(item)?;
+        }
+    }
+
+    let stats = writer.finish()?;
+    println!("Kept {} streamlines out of {}", stats.nb_streamlines, nb_read);
+    Ok(())
+}
@@ -5,7 +5,7 @@ use docopt::Docopt;
 
 use trk_io::CHeader;
 
-static USAGE: &'static str = "
+static USAGE: &str = "
 Print a TrackVis (.trk) header in an readable form
 
 Usage:
@@ -43,11 +43,11 @@ fn main() -> Result<()> {
     println!("voxel_size: {:?}", header.voxel_size);
     println!("origin: {:?}", header.origin);
     println!("n_scalars: {:?}", header.n_scalars);
-    for (i, scalar_name) in header.get_scalars_name().iter().enumerate() {
+    for (i, scalar_name) in header.get_scalars_name()?.iter().enumerate() {
         println!("  {}: {}", i, scalar_name);
     }
     println!("n_properties: {:?}", header.n_properties);
-    for (i, property_name) in header.get_properties_name().iter().enumerate() {
+    for (i, property_name) in header.get_properties_name()?.iter().enumerate() {
         println!("  {}: {}", i, property_name);
     }
     println!("vox_to_ras: {:?}", &header.vox_to_ras[0..4]);
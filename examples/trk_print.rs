@@ -1,9 +1,9 @@
 use anyhow::Result;
 use docopt::Docopt;
 
-use trk_io::{Point, Reader};
+use trk_io::{Point, Reader, TrkIndex};
 
-static USAGE: &'static str = "
+static USAGE: &str = "
 Print the first points of the first streamlines of a trk file.
 
 Usage:
@@ -17,6 +17,8 @@ Options:
   -u --upto=<n>        Print up to `n` points per streamline. Will take the first `n / 2` and the
                        last `n / 2` points. An odd number is ignored in favor of `n - 1`.
                        Print all points if unspecified.
+  -i --index           With `at`, use the sidecar index `<input>.idx` to find the streamline,
+                       creating it if needed.
   -h --help            Show this screen.
   -v --version         Show version.
 ";
@@ -32,48 +34,48 @@ fn main() -> Result<()> {
         println!("({:.*} {:.*} {:.*})", precision, p[0], precision, p[1], precision, p[2]);
     };
 
-    let upto = args.get_str("--upto").parse::<usize>().unwrap_or(std::usize::MAX);
+    let upto = args.get_str("--upto").parse::<usize>().unwrap_or(usize::MAX);
     let first_part = upto / 2;
 
-    let reader = Reader::new(args.get_str("<input>"))?.into_streamlines_iter();
+    let input = args.get_str("<input>");
+    let print_streamline = |streamline: &[Point]| {
+        let len = streamline.len();
+        if len > upto {
+            streamline[0..first_part].iter().for_each(&print);
+            println!("...");
+
+            let second_part = len - first_part;
+            streamline[second_part..].iter().for_each(&print);
+        } else {
+            streamline.iter().for_each(&print)
+        }
+    };
+
     if args.get_bool("first") {
         // nb - 1 because we don't want to print the last \n
         let nb = args.get_str("<nb>").parse::<usize>()? - 1;
 
+        let reader = Reader::new(input)?.into_streamlines_iter();
         for (i, streamline) in reader.into_iter().enumerate() {
-            let len = streamline.len();
-            if len > upto {
-                streamline[0..first_part].iter().for_each(&print);
-                println!("...");
-
-                let second_part = len - first_part;
-                streamline[second_part..].iter().for_each(&print);
-            } else {
-                streamline.iter().for_each(&print)
-            }
-
+            print_streamline(&streamline?);
             if i == nb {
                 break;
             }
-            println!("");
+            println!();
         }
     } else {
         let idx = args.get_str("<idx>").parse::<usize>()?;
-        for (i, streamline) in reader.into_iter().enumerate() {
-            if i == idx {
-                let len = streamline.len();
-                if len > upto {
-                    streamline[0..first_part].iter().for_each(&print);
-                    println!("...");
-
-                    let second_part = len - first_part;
-                    streamline[second_part..].iter().for_each(&print);
-                } else {
-                    streamline.iter().for_each(&print)
-                }
-                break;
-            }
+        let index = if args.get_bool("--index") {
+            TrkIndex::load_or_build(input)?
+        } else {
+            TrkIndex::build(input)?
+        };
+        if idx >= index.len() {
+            anyhow::bail!("There are only {} streamlines in {}", index.len(), input);
         }
+
+        let (streamline, _, _) = Reader::new(input)?.item_at(&index, idx)?;
+        print_streamline(&streamline);
     }
 
     Ok(())
@@ -0,0 +1,48 @@
+use anyhow::Result;
+use docopt::Docopt;
+
+use trk_io::roi::{Mask, RoiLogic, RoiMode, RoiSelection};
+
+static USAGE: &str = "
+Keep only the streamlines of a TrackVis (.trk) file that satisfy some regions of interest, defined
+by binary NIfTI masks on the same grid. Streamlines are read and written one at a time, so any file
+size can be filtered.
+
+Usage:
+  trk_roi <input> <output> [--include=<mask> | --exclude=<mask> | --endpoint=<mask> | --both-endpoints=<mask>]... [--any]
+  trk_roi (-h | --help)
+  trk_roi (-v | --version)
+
+Options:
+  --include=<mask>         Keep the streamlines touching this mask.
+  --exclude=<mask>         Keep the streamlines never touching this mask.
+  --endpoint=<mask>        Keep the streamlines with at least one endpoint in this mask.
+  --both-endpoints=<mask>  Keep the streamlines with both endpoints in this mask.
+  --any                    Keep the streamlines satisfying any mask, instead of all masks.
+  -h --help                Show this screen.
+  -v --version             Show version.
+";
+
+fn main() -> Result<()> {
+    let version = String::from(env!("CARGO_PKG_VERSION"));
+    let args = Docopt::new(USAGE)
+        .and_then(|dopt| dopt.version(Some(version)).parse())
+        .unwrap_or_else(|e| e.exit());
+
+    let logic = if args.get_bool("--any") { RoiLogic::Any } else { RoiLogic::All };
+    let mut selection = RoiSelection::new(logic);
+    for (option, mode) in [
+        ("--include", RoiMode::Include),
+        ("--exclude", RoiMode::Exclude),
+        ("--endpoint", RoiMode::AnyEndpoint),
+        ("--both-endpoints", RoiMode::BothEndpoints),
+    ] {
+        for path in args.get_vec(option) {
+            selection = selection.add(Mask::from_nifti(path)?, mode);
+        }
+    }
+
+    let stats = selection.filter_trk(args.get_str("<input>"), args.get_str("<output>"))?;
+    println!("Kept {} streamlines", stats.nb_streamlines);
+    Ok(())
+}
@@ -2,10 +2,12 @@ use anyhow::Result;
 use docopt::Docopt;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
-use trk_io::{Reader, Writer};
+use trk_io::format::{create, open, TractogramReader, TractogramWriter};
 
-static USAGE: &'static str = "
-Subsample a TrackVis (.trk) file
+static USAGE: &str = "
+Subsample a tractogram. The input and output formats can be any of .trk, .tck, .vtk, .vtp and
+.trx (with the trx feature), and they can be different. A .trk file can be compressed, as
+.trk.gz or .trk.zst (with the gzip and zstd features).
 
 Usage:
   trk_subsampler <input> <output> (--percent=<p> | --number=<n>) [--seed=<s>]
@@ -26,8 +28,8 @@ fn main() -> Result<()> {
         .and_then(|dopt| dopt.version(Some(version)).parse())
         .unwrap_or_else(|e| e.exit());
 
-    let reader = Reader::new(args.get_str("<input>"))?;
-    let mut writer = Writer::new(args.get_str("<output>"), Some(&reader.header))?;
+    let reader = open(args.get_str("<input>"))?;
+    let mut writer = create(args.get_str("<output>"), reader.header())?;
 
     let mut rng = match args.get_str("--seed").parse::<u8>() {
         Ok(seed) => SmallRng::from_seed([seed; 32]),
@@ -37,12 +39,13 @@ fn main() -> Result<()> {
     if let Ok(percent) = args.get_str("--percent").parse::<f32>() {
         let percent = percent / 100.0;
         for item in reader {
+            let item = item?;
             if rng.gen::<f32>() < percent {
-                writer.write(item);
+                writer.write_item(item)?;
             }
         }
     } else if let Ok(nb) = args.get_str("--number").parse::<usize>() {
-        let size = reader.header.nb_streamlines;
+        let size = reader.header().nb_streamlines;
         let number = size.min(nb);
         if number == 0 {
             panic!(
@@ -56,26 +59,35 @@ fn main() -> Result<()> {
                 nb
             );
 
-            reader.into_iter().for_each(|item| writer.write(item));
+            for item in reader {
+                writer.write_item(item?)?;
+            }
         } else {
-            sampling_write(&mut writer, reader, number, &mut rng);
+            sampling_write(writer.as_mut(), reader, number, &mut rng)?;
         }
     } else {
         panic!("--percent or --number can't be parsed to a positive number");
     }
 
+    writer.finish()?;
     Ok(())
 }
 
-fn sampling_write(writer: &mut Writer, reader: Reader, number: usize, rng: &mut SmallRng) {
+fn sampling_write(
+    writer: &mut dyn TractogramWriter,
+    reader: Box<dyn TractogramReader>,
+    number: usize,
+    rng: &mut SmallRng,
+) -> Result<()> {
     let mut sampled_indices =
-        rand::seq::index::sample(rng, reader.header.nb_streamlines, number).into_vec();
+        rand::seq::index::sample(rng, reader.header().nb_streamlines, number).into_vec();
     sampled_indices.sort();
 
     let mut reader_iter = reader.into_iter();
     let mut last = 0;
     for idx in sampled_indices {
-        writer.write(reader_iter.nth(idx - last).unwrap());
+        writer.write_item(reader_iter.nth(idx - last).unwrap()?)?;
         last = idx + 1;
     }
+    Ok(())
 }
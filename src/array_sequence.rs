@@ -1,11 +1,11 @@
 use std::{
     mem,
-    ops::{Index, Range},
+    ops::{Bound, Index, IndexMut, Range, RangeBounds},
     slice,
     vec::Vec,
 };
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct ArraySequence<T> {
     pub offsets: Vec<usize>,
     pub data: Vec<T>,
@@ -16,25 +16,32 @@ impl<'a, T> IntoIterator for &'a ArraySequence<T> {
     type IntoIter = ArraySequenceIterator<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        ArraySequenceIterator { arr: self, index: 0..self.len() }
+        ArraySequenceIterator { offsets: &self.offsets, data: &self.data, index: 0..self.len() }
     }
 }
 
 pub struct ArraySequenceIterator<'a, T: 'a> {
-    arr: &'a ArraySequence<T>,
+    offsets: &'a [usize],
+    data: &'a [T],
     index: Range<usize>,
 }
 
+impl<'a, T> ArraySequenceIterator<'a, T> {
+    fn get(&self, idx: usize) -> &'a [T] {
+        &self.data[self.offsets[idx]..self.offsets[idx + 1]]
+    }
+}
+
 impl<'a, T> Iterator for ArraySequenceIterator<'a, T> {
     type Item = &'a [T];
 
     fn next(&mut self) -> Option<Self::Item> {
         let idx = self.index.next()?;
-        Some(&self.arr[idx])
+        Some(self.get(idx))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, Some(self.arr.len()))
+        self.index.size_hint()
     }
 }
 
@@ -63,7 +70,7 @@ impl<'a, T> Iterator for ArraySequenceIteratorMut<'a, T> {
         let nb_elements = current_offset - self.last_offset;
         self.last_offset = current_offset;
 
-        let data = mem::replace(&mut self.data, &mut []);
+        let data = mem::take(&mut self.data);
         let (slice, remaining_data) = data.split_at_mut(nb_elements);
         self.data = remaining_data;
         Some(slice)
@@ -75,20 +82,27 @@ impl<'data, T> ExactSizeIterator for ArraySequenceIterator<'data, T> {}
 impl<'data, T> DoubleEndedIterator for ArraySequenceIterator<'data, T> {
     fn next_back(&mut self) -> Option<Self::Item> {
         let idx = self.index.next_back()?;
-        Some(&self.arr[idx])
+        Some(self.get(idx))
     }
 }
 
 impl<T> Index<usize> for ArraySequence<T> {
     type Output = [T];
 
-    fn index<'a>(&'a self, i: usize) -> &'a Self::Output {
+    fn index(&self, i: usize) -> &Self::Output {
         let start = unsafe { *self.offsets.get_unchecked(i) };
         let end = unsafe { *self.offsets.get_unchecked(i + 1) };
         &self.data[start..end]
     }
 }
 
+impl<T> IndexMut<usize> for ArraySequence<T> {
+    fn index_mut(&mut self, i: usize) -> &mut Self::Output {
+        let (start, end) = (self.offsets[i], self.offsets[i + 1]);
+        &mut self.data[start..end]
+    }
+}
+
 impl<T> Default for ArraySequence<T> {
     fn default() -> Self {
         ArraySequence::empty()
@@ -127,7 +141,7 @@ impl<T> ArraySequence<T> {
             );
         }
 
-        ArraySequence { offsets, data: data }
+        ArraySequence { offsets, data }
     }
 
     pub fn push(&mut self, val: T) {
@@ -158,7 +172,7 @@ impl<T> ArraySequence<T> {
         self.offsets.len() - 1
     }
 
-    /// Same as obj[i].len(), without building a slice
+    /// Same as `obj[i].len()`, without building a slice
     pub fn length_of_array(&self, i: usize) -> usize {
         let current = unsafe { *self.offsets.get_unchecked(i) };
         let next = unsafe { *self.offsets.get_unchecked(i + 1) };
@@ -179,11 +193,117 @@ impl<T> ArraySequence<T> {
         new
     }
 
-    pub fn iter(&self) -> ArraySequenceIterator<T> {
+    /// Keep only the arrays for which `f` returns `true`, in place.
+    ///
+    /// `f` is called exactly once per array, in order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[T]) -> bool,
+    {
+        let keep = self.iter().map(&mut f).collect::<Vec<_>>();
+
+        // Mark each element with the decision of its array
+        let offsets = &self.offsets;
+        let mut array = 0;
+        let mut idx = 0;
+        self.data.retain(|_| {
+            while offsets[array + 1] <= idx {
+                array += 1;
+            }
+            idx += 1;
+            keep[array]
+        });
+
+        let mut new_offsets = Vec::with_capacity(self.offsets.len());
+        new_offsets.push(0);
+        for (i, _) in keep.iter().enumerate().filter(|(_, &keep)| keep) {
+            new_offsets.push(new_offsets.last().unwrap() + self.length_of_array(i));
+        }
+        self.offsets = new_offsets;
+    }
+
+    /// Remove and return array `i`, shifting all following arrays.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn remove(&mut self, i: usize) -> Vec<T> {
+        assert!(i < self.len(), "Index {} is out of bounds ({})", i, self.len());
+        let removed = self.data.drain(self.offsets[i]..self.offsets[i + 1]).collect::<Vec<_>>();
+        self.offsets.remove(i + 1);
+        for offset in &mut self.offsets[i + 1..] {
+            *offset -= removed.len();
+        }
+        removed
+    }
+
+    /// Remove and return array `i`, replacing it by the last array.
+    ///
+    /// Contrary to `Vec::swap_remove`, this is not O(1) because the arrays don't have the same
+    /// length. All elements after array `i` may need to be moved.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> Vec<T> {
+        let last = self.len().checked_sub(1);
+        assert!(Some(i) <= last, "Index {} is out of bounds ({})", i, self.len());
+        let last = last.unwrap();
+        let last_array = self.data.drain(self.offsets[last]..).collect::<Vec<_>>();
+        self.offsets.pop();
+        if i == last {
+            return last_array;
+        }
+
+        let (start, end) = (self.offsets[i], self.offsets[i + 1]);
+        let new_len = last_array.len();
+        let removed = self.data.splice(start..end, last_array).collect::<Vec<_>>();
+        for offset in &mut self.offsets[i + 1..] {
+            *offset = *offset + new_len - removed.len();
+        }
+        removed
+    }
+
+    /// Keep only the first `len` arrays. Does nothing if there are less than `len` arrays.
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            self.data.truncate(self.offsets[len]);
+            self.offsets.truncate(len + 1);
+        }
+    }
+
+    /// Move all arrays of `other` at the end of `self`, leaving `other` empty.
+    pub fn append(&mut self, other: &mut ArraySequence<T>) {
+        let base = self.data.len();
+        self.offsets.extend(other.offsets[1..].iter().map(|offset| offset + base));
+        self.data.append(&mut other.data);
+        other.offsets.truncate(1);
+    }
+
+    /// Build a new `ArraySequence` holding all arrays of `sequences`, in order.
+    pub fn concat<I>(sequences: I) -> ArraySequence<T>
+    where
+        I: IntoIterator<Item = ArraySequence<T>>,
+    {
+        let mut new = ArraySequence::empty();
+        for mut sequence in sequences {
+            new.append(&mut sequence);
+        }
+        new
+    }
+
+    pub fn iter(&self) -> ArraySequenceIterator<'_, T> {
         self.into_iter()
     }
 
-    pub fn iter_mut(&mut self) -> ArraySequenceIteratorMut<T> {
+    /// Borrow the arrays in `range`, without any copy.
+    ///
+    /// `Index` can only return a reference, so it can't be used to build a view. Use
+    /// `arr.view(0..10)` instead of `arr[0..10]`.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn view<R: RangeBounds<usize>>(&self, range: R) -> ArraySequenceView<'_, T> {
+        let range = to_range(range, self.len());
+        ArraySequenceView { offsets: &self.offsets[range.start..range.end + 1], data: &self.data }
+    }
+
+    pub fn iter_mut(&mut self) -> ArraySequenceIteratorMut<'_, T> {
         self.into_iter()
     }
 }
@@ -200,4 +320,121 @@ impl<T: Clone> ArraySequence<T> {
         self.data.extend_from_slice(other);
         self.end_push();
     }
+
+    /// Insert `array` at index `i`, shifting all following arrays.
+    ///
+    /// Panics if `i > len()`.
+    pub fn insert(&mut self, i: usize, array: &[T]) {
+        assert!(i <= self.len(), "Index {} is out of bounds ({})", i, self.len());
+        let start = self.offsets[i];
+        self.data.splice(start..start, array.iter().cloned());
+        self.offsets.insert(i + 1, start + array.len());
+        for offset in &mut self.offsets[i + 2..] {
+            *offset += array.len();
+        }
+    }
+
+    /// Copy the arrays in `range` in a new `ArraySequence`.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> ArraySequence<T> {
+        self.view(range).to_array_sequence()
+    }
+}
+
+/// Borrowed range of arrays of an `ArraySequence`, as returned by `ArraySequence::view`.
+#[derive(Debug)]
+pub struct ArraySequenceView<'a, T> {
+    /// Offsets of the viewed arrays, plus the end of the last one. They are relative to the
+    /// complete `data` of the `ArraySequence`.
+    offsets: &'a [usize],
+    data: &'a [T],
+}
+
+// Derive would require `T: Clone`
+impl<'a, T> Clone for ArraySequenceView<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for ArraySequenceView<'a, T> {}
+
+impl<'a, T> IntoIterator for ArraySequenceView<'a, T> {
+    type Item = &'a [T];
+    type IntoIter = ArraySequenceIterator<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        ArraySequenceIterator { offsets: self.offsets, data: self.data, index: 0..self.len() }
+    }
+}
+
+impl<'a, T> Index<usize> for ArraySequenceView<'a, T> {
+    type Output = [T];
+
+    fn index(&self, i: usize) -> &Self::Output {
+        &self.data[self.offsets[i]..self.offsets[i + 1]]
+    }
+}
+
+impl<'a, T> ArraySequenceView<'a, T> {
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Same as `view[i].len()`, without building a slice
+    pub fn length_of_array(&self, i: usize) -> usize {
+        self.offsets[i + 1] - self.offsets[i]
+    }
+
+    /// All elements of the viewed arrays, contiguous.
+    pub fn data(&self) -> &'a [T] {
+        &self.data[self.offsets[0]..self.offsets[self.len()]]
+    }
+
+    /// Restrict the view to the arrays in `range`, relative to this view.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn view<R: RangeBounds<usize>>(&self, range: R) -> ArraySequenceView<'a, T> {
+        let range = to_range(range, self.len());
+        ArraySequenceView { offsets: &self.offsets[range.start..range.end + 1], data: self.data }
+    }
+
+    pub fn iter(&self) -> ArraySequenceIterator<'a, T> {
+        self.into_iter()
+    }
+}
+
+impl<'a, T: Clone> ArraySequenceView<'a, T> {
+    /// Copy the viewed arrays in a new `ArraySequence`.
+    pub fn to_array_sequence(&self) -> ArraySequence<T> {
+        let first = self.offsets[0];
+        let offsets = self.offsets.iter().map(|offset| offset - first).collect();
+        ArraySequence { offsets, data: self.data().to_vec() }
+    }
+}
+
+/// Convert any kind of range to a `Range`, checking that it fits in `len` arrays.
+fn to_range<R: RangeBounds<usize>>(range: R, len: usize) -> Range<usize> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start > end {
+        panic!("Range starts at {} but ends at {}", start, end);
+    }
+    if end > len {
+        panic!("Range end {} is out of bounds ({})", end, len);
+    }
+    start..end
 }
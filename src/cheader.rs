@@ -1,23 +1,22 @@
 use std::{
     fmt,
-    fs::File,
-    io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Seek, SeekFrom},
+    io::{Read, Seek, SeekFrom},
     str::from_utf8,
 };
 
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 use nalgebra::Vector4;
 
-#[cfg(feature = "nifti_images")]
-use crate::Affine;
 use crate::{
     orientation::{
         affine_to_axcodes, axcodes_to_orientations, inverse_orientations_affine,
         orientations_transform,
     },
-    Affine4, TrkEndianness,
+    reader::read_up_to,
+    Affine, Affine4, Error, Result, TrkEndianness,
 };
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Endianness {
     Little,
     Big,
@@ -73,30 +72,40 @@ impl CHeader {
         srow_z: [f32; 4],
     ) -> CHeader {
         #[rustfmt::skip]
-        let affine = Affine::new(
-            srow_x[0], srow_x[1], srow_x[2],
-            srow_y[0], srow_y[1], srow_y[2],
-            srow_z[0], srow_z[1], srow_z[2],
-        );
-        #[rustfmt::skip]
-        let vox_to_ras = [
+        let affine4 = Affine4::new(
             srow_x[0], srow_x[1], srow_x[2], srow_x[3],
             srow_y[0], srow_y[1], srow_y[2], srow_y[3],
             srow_z[0], srow_z[1], srow_z[2], srow_z[3],
             0.0, 0.0, 0.0, 1.0,
-        ];
+        );
+        CHeader::from_affine4(
+            [dim[1] as i16, dim[2] as i16, dim[3] as i16],
+            [pixdim[1], pixdim[2], pixdim[3]],
+            &affine4,
+        )
+    }
+
+    /// Build a header for a grid of shape `dim` and voxel size `voxel_size`, with `affine4` as
+    /// voxel to RAS+ mm transformation.
+    pub fn from_affine4(dim: [i16; 3], voxel_size: [f32; 3], affine4: &Affine4) -> CHeader {
+        let affine: Affine = affine4.fixed_view::<3, 3>(0, 0).into_owned();
+        let mut vox_to_ras = [0.0; 16];
+        for (i, v) in vox_to_ras.iter_mut().enumerate() {
+            *v = affine4[(i / 4, i % 4)];
+        }
         let vo = affine_to_axcodes(&affine).into_bytes();
         CHeader {
-            dim: [dim[1] as i16, dim[2] as i16, dim[3] as i16],
-            voxel_size: [pixdim[1], pixdim[2], pixdim[3]],
+            dim,
+            voxel_size,
             vox_to_ras,
             voxel_order: [vo[0], vo[1], vo[2], 0u8],
             ..CHeader::default()
         }
     }
 
-    pub fn seek_n_count_field(f: &mut BufWriter<File>) -> Result<()> {
-        let n_count_offset = (HEADER_SIZE - 12) as u64;
+    /// Seek to the `n_count` field of a header written at position `header_start`.
+    pub fn seek_n_count_field<W: Seek>(f: &mut W, header_start: u64) -> std::io::Result<()> {
+        let n_count_offset = header_start + (HEADER_SIZE - 12) as u64;
         f.seek(SeekFrom::Start(n_count_offset))?;
         Ok(())
     }
@@ -109,21 +118,19 @@ impl CHeader {
     }
 
     pub fn add_scalar(&mut self, name: &str) -> Result<()> {
-        if self.n_scalars > 10 {
-            Err(Error::new(ErrorKind::InvalidInput, "Trk header is already full of scalars (10)"))
-        } else if name.len() > 20 {
-            Err(Error::new(ErrorKind::InvalidInput, "New scalar name must be <= 20 characters."))
-        } else if !name.is_ascii() {
-            Err(Error::new(ErrorKind::InvalidInput, "New scalar name must be pure ascii."))
-        } else {
-            let pos = 20 * self.n_scalars as usize;
-            self.scalar_name[pos..pos + name.len()].clone_from_slice(name.as_bytes());
-            self.n_scalars += 1;
-            return Ok(());
-        }
+        self.add_scalar_array(name, 1)
     }
 
-    pub fn get_scalars_name(&self) -> Vec<String> {
+    /// Add a scalar of `nb_components` values per point, e.g. a RGB color.
+    ///
+    /// The name is saved once, with a repeat count (`name\0{number}`), as long as it has at most
+    /// 18 characters. Otherwise, it is saved once per component.
+    pub fn add_scalar_array(&mut self, name: &str, nb_components: usize) -> Result<()> {
+        let kind = ("scalar", "scalars");
+        add_name(&mut self.scalar_name, &mut self.n_scalars, name, nb_components, kind)
+    }
+
+    pub fn get_scalars_name(&self) -> Result<Vec<String>> {
         read_names(&self.scalar_name, self.n_scalars as usize)
     }
 
@@ -135,24 +142,16 @@ impl CHeader {
     }
 
     pub fn add_property(&mut self, name: &str) -> Result<()> {
-        if self.n_properties > 10 {
-            Err(Error::new(
-                ErrorKind::InvalidInput,
-                "Trk header is already full of properties (10)",
-            ))
-        } else if name.len() > 20 {
-            Err(Error::new(ErrorKind::InvalidInput, "New property name must be <= 20 characters."))
-        } else if !name.is_ascii() {
-            Err(Error::new(ErrorKind::InvalidInput, "New property name must be pure ascii."))
-        } else {
-            let pos = 20 * self.n_properties as usize;
-            self.property_name[pos..pos + name.len()].clone_from_slice(name.as_bytes());
-            self.n_properties += 1;
-            return Ok(());
-        }
+        self.add_property_array(name, 1)
+    }
+
+    /// Add a property of `nb_components` values per streamline, saved as in `add_scalar_array`.
+    pub fn add_property_array(&mut self, name: &str, nb_components: usize) -> Result<()> {
+        let kind = ("property", "properties");
+        add_name(&mut self.property_name, &mut self.n_properties, name, nb_components, kind)
     }
 
-    pub fn get_properties_name(&self) -> Vec<String> {
+    pub fn get_properties_name(&self) -> Result<Vec<String>> {
         read_names(&self.property_name, self.n_properties as usize)
     }
 
@@ -185,7 +184,7 @@ impl CHeader {
 
         let voxel_to_rasmm = Affine4::from_iterator(self.vox_to_ras.iter().cloned()).transpose();
 
-        let header_ornt = axcodes_to_orientations(from_utf8(&self.voxel_order).unwrap());
+        let header_ornt = axcodes_to_orientations(&String::from_utf8_lossy(&self.voxel_order));
         let affine_order = affine_to_axcodes(&voxel_to_rasmm.fixed_view::<3, 3>(0, 0).into_owned());
         let affine_ornt = axcodes_to_orientations(&affine_order);
         let orientations = orientations_transform(&header_ornt, &affine_ornt);
@@ -195,28 +194,41 @@ impl CHeader {
         voxel_to_rasmm * affine
     }
 
-    pub fn read(reader: &mut BufReader<File>) -> Result<(CHeader, Endianness)> {
+    /// Read a trk header, along with its byte order, from the start of `reader`.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<(CHeader, Endianness)> {
         reader.seek(SeekFrom::Start(0))?;
-        let endianness = test_endianness(reader)?;
+        CHeader::read_stream(reader)
+    }
+
+    /// Read a trk header, along with its byte order, without seeking. It is assumed that `reader`
+    /// is currently at the start of the trk header.
+    ///
+    /// The complete header is read before deciding its byte order, so `reader` will be positioned
+    /// on the first streamline when this function returns.
+    pub fn read_stream<R: Read>(reader: &mut R) -> Result<(CHeader, Endianness)> {
+        let mut bytes = [0u8; HEADER_SIZE];
+        let nb_read = read_up_to(reader, &mut bytes)?;
+
+        // Make sure that the file signature (magic number) is right before doing anything else
+        if nb_read < 6 || &bytes[..6] != b"TRACK\0" {
+            return Err(Error::BadMagic);
+        }
+        if nb_read < HEADER_SIZE {
+            return Err(Error::InvalidHeader("File is too small to hold a trk header".to_string()));
+        }
+
+        let endianness = test_endianness(&bytes);
         let header = match endianness {
-            Endianness::Little => CHeader::read_::<LittleEndian>(reader)?,
-            Endianness::Big => CHeader::read_::<BigEndian>(reader)?,
+            Endianness::Little => CHeader::read_::<LittleEndian>(&mut &bytes[..])?,
+            Endianness::Big => CHeader::read_::<BigEndian>(&mut &bytes[..])?,
         };
         Ok((header, endianness))
     }
 
-    fn read_<E: ByteOrder>(reader: &mut BufReader<File>) -> Result<CHeader> {
+    fn read_<E: ByteOrder>(reader: &mut &[u8]) -> Result<CHeader> {
         let mut header = CHeader::default();
 
-        // Make sure that the file signature (magic number) is right before doing anything else
         reader.read_exact(&mut header.id_string)?;
-        if &header.id_string != b"TRACK\0" {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Not a TrackVis file (wrong file signature)",
-            ));
-        }
-
         for i in &mut header.dim {
             *i = reader.read_i16::<E>()?;
         }
@@ -250,11 +262,22 @@ impl CHeader {
         header.version = reader.read_i32::<E>()?;
         header.hdr_size = reader.read_i32::<E>()?;
 
+        // A name can be repeated many times, but there are only 10 slots to store them
+        let counts = [
+            ("n_scalars", header.n_scalars, &header.scalar_name),
+            ("n_properties", header.n_properties, &header.property_name),
+        ];
+        for (field, nb, names_bytes) in counts {
+            if nb < 0 || nb_used_slots(names_bytes, nb as usize) > 10 {
+                return Err(Error::InvalidHeader(format!("{} is {}", field, nb)));
+            }
+        }
+
         Ok(header)
     }
 
     pub fn write<W: WriteBytesExt>(&self, writer: &mut W) -> Result<()> {
-        writer.write(&self.id_string)?;
+        writer.write_all(&self.id_string)?;
         for i in &self.dim {
             writer.write_i16::<TrkEndianness>(*i)?;
         }
@@ -265,19 +288,19 @@ impl CHeader {
             writer.write_f32::<TrkEndianness>(*f)?;
         }
         writer.write_i16::<TrkEndianness>(self.n_scalars)?;
-        writer.write(&self.scalar_name)?;
+        writer.write_all(&self.scalar_name)?;
         writer.write_i16::<TrkEndianness>(self.n_properties)?;
-        writer.write(&self.property_name)?;
+        writer.write_all(&self.property_name)?;
         for f in &self.vox_to_ras {
             writer.write_f32::<TrkEndianness>(*f)?;
         }
-        writer.write(&self.reserved)?;
-        writer.write(&self.voxel_order)?;
-        writer.write(&self.pad2)?;
+        writer.write_all(&self.reserved)?;
+        writer.write_all(&self.voxel_order)?;
+        writer.write_all(&self.pad2)?;
         for f in &self.image_orientation_patient {
             writer.write_f32::<TrkEndianness>(*f)?;
         }
-        writer.write(&self.pad1)?;
+        writer.write_all(&self.pad1)?;
         writer.write_u8(self.invert_x)?;
         writer.write_u8(self.invert_y)?;
         writer.write_u8(self.invert_z)?;
@@ -324,49 +347,129 @@ impl Default for CHeader {
     }
 }
 
-/// Returns the endianness used when saving the trk file read by `reader`
+/// Returns the endianness used when saving the trk header `bytes`
 ///
 /// We use `version` to discover the endianness because it's the biggest
 /// integer field with the most constrained possible values {1, 2}.
 /// Read in LittleEndian, version == 1 or 2.
 /// Read in BigEndian, version == 511 or 767
 /// Even with hundreds major updates, `version` should be safe.
-fn test_endianness(reader: &mut BufReader<File>) -> Result<Endianness> {
-    let version_offset = (HEADER_SIZE - 8) as u64;
-    reader.seek(SeekFrom::Start(version_offset))?;
-    let version = reader.read_i32::<LittleEndian>()?;
-    let endianness = if version <= 255 { Endianness::Little } else { Endianness::Big };
-    reader.seek(SeekFrom::Start(0))?;
-
-    Ok(endianness)
+fn test_endianness(bytes: &[u8; HEADER_SIZE]) -> Endianness {
+    let version = LittleEndian::read_i32(&bytes[HEADER_SIZE - 8..]);
+    if version <= 255 {
+        Endianness::Little
+    } else {
+        Endianness::Big
+    }
 }
 
-/// Returns the names from the [10][20] arrays of bytes.
+/// Write `name` in the first free slots of the [10][20] array of bytes `names_bytes`, with a repeat
+/// count when possible. `nb` is the number of names, that is, the number of values per row.
+fn add_name(
+    names_bytes: &mut [u8; 200],
+    nb: &mut i16,
+    name: &str,
+    nb_components: usize,
+    (kind, kinds): (&str, &'static str),
+) -> Result<()> {
+    if name.len() > 20 {
+        return Err(Error::InvalidName(format!("New {} name must be <= 20 characters.", kind)));
+    } else if !name.is_ascii() {
+        return Err(Error::InvalidName(format!("New {} name must be pure ascii.", kind)));
+    }
+
+    // The repeat count is written after the \0 ending the name, with as many digits as possible
+    let max_repeat = if !name.is_empty() && name.len() <= 18 {
+        10usize.pow((19 - name.len()).min(5) as u32) - 1
+    } else {
+        1
+    };
+    let nb_slots = nb_components.div_ceil(max_repeat);
+    let first_slot = nb_used_slots(names_bytes, *nb as usize);
+    if first_slot + nb_slots > 10 || *nb as usize + nb_components > i16::MAX as usize {
+        return Err(Error::TooManyNames { kind: kinds, max: 10 });
+    }
+
+    let mut remaining = nb_components;
+    for slot in names_bytes.chunks_mut(20).skip(first_slot).take(nb_slots) {
+        let count = remaining.min(max_repeat);
+        slot[..name.len()].clone_from_slice(name.as_bytes());
+        if count > 1 {
+            let digits = count.to_string();
+            slot[name.len() + 1..name.len() + 1 + digits.len()].clone_from_slice(digits.as_bytes());
+        }
+        remaining -= count;
+    }
+    *nb += nb_components as i16;
+    Ok(())
+}
+
+/// Number of slots used by the `nb` names of `names_bytes`, taking the repeat counts into account.
+fn nb_used_slots(names_bytes: &[u8], nb: usize) -> usize {
+    let mut nb_slots = 0;
+    let mut nb_names = 0;
+    for slot in names_bytes.chunks(20) {
+        if nb_names >= nb {
+            break;
+        }
+        match parse_slot(slot) {
+            Ok(Some((_, number))) => nb_names = nb_names.saturating_add(number),
+            Ok(None) => break,
+            Err(_) => nb_names += 1,
+        }
+        nb_slots += 1;
+    }
+    // Empty names still use a slot each
+    nb_slots + nb.saturating_sub(nb_names)
+}
+
+/// Returns the name stored in a 20 bytes slot and its repeat count, or `None` if the slot is empty.
 ///
 /// Normal case: name\0\0...
-/// Special case: name\0{number}\0\0...
-fn read_names(names_bytes: &[u8], nb: usize) -> Vec<String> {
+/// Special case: name\0{number}\0\0..., where `number` has one or more digits.
+fn parse_slot(slot: &[u8]) -> Result<Option<(&str, usize)>> {
+    if slot[0] == 0u8 {
+        return Ok(None);
+    }
+
+    let idx = slot.iter().position(|&e| e == 0u8).unwrap_or(slot.len());
+    let name = from_utf8(&slot[..idx])
+        .map_err(|_| Error::InvalidHeader("Scalar or property name is not utf-8".to_string()))?;
+    let digits = slot.get(idx + 1..).unwrap_or(&[]);
+    let digits = &digits[..digits.iter().position(|&e| e == 0u8).unwrap_or(digits.len())];
+    if digits.is_empty() {
+        return Ok(Some((name, 1)));
+    }
+
+    let number = from_utf8(digits).ok().and_then(|digits| digits.parse::<usize>().ok());
+    match number {
+        Some(number) if number > 0 && digits.iter().all(u8::is_ascii_digit) => {
+            Ok(Some((name, number)))
+        }
+        _ => Err(Error::InvalidHeader(format!("Invalid repeat count for name {:?}", name))),
+    }
+}
+
+/// Returns the names from the [10][20] arrays of bytes.
+fn read_names(names_bytes: &[u8], nb: usize) -> Result<Vec<String>> {
     let mut at = 0;
     let mut names = vec![String::from(""); nb];
-    for names_byte in names_bytes.chunks(20) {
-        if names_byte[0] == 0u8 {
+    for slot in names_bytes.chunks(20) {
+        let Some((name, number)) = parse_slot(slot)? else {
             break;
+        };
+        if number > nb - at {
+            return Err(Error::InvalidHeader(format!(
+                "Header declares {} scalars or properties but contains more names",
+                nb
+            )));
         }
-
-        let idx = names_byte.iter().position(|&e| e == 0u8).unwrap_or(20);
-        let name = from_utf8(&names_byte[..idx]).unwrap().to_string();
-        if idx < 19 && names_byte[idx + 1] != 0u8 {
-            let number = names_byte[idx + 1] - 48;
-            for _ in 0..number {
-                names[at] = name.clone();
-                at += 1;
-            }
-        } else {
-            names[at] = name;
+        for _ in 0..number {
+            names[at] = name.to_string();
             at += 1;
         }
     }
-    names
+    Ok(names)
 }
 
 #[cfg(test)]
@@ -385,14 +488,71 @@ mod tests {
         assert_eq!(&header.scalar_name[..], &gt[..]);
     }
 
+    #[test]
+    fn test_scalar_arrays() {
+        let mut header = CHeader::default();
+        header.add_scalar_array("colors", 3).unwrap();
+        header.add_scalar("fa").unwrap();
+        header.add_scalar_array("a_long_name_of_19_c", 2).unwrap();
+        header.add_scalar_array("tensor", 12).unwrap();
+        assert_eq!(header.n_scalars, 18);
+
+        let mut gt = [0u8; 200];
+        gt[..8].clone_from_slice(b"colors\x003");
+        gt[20..22].clone_from_slice(b"fa");
+        gt[40..59].clone_from_slice(b"a_long_name_of_19_c");
+        gt[60..79].clone_from_slice(b"a_long_name_of_19_c");
+        gt[80..89].clone_from_slice(b"tensor\x0012");
+        assert_eq!(&header.scalar_name[..], &gt[..]);
+        assert_eq!(nb_used_slots(&header.scalar_name, 18), 5);
+
+        let names = header.get_scalars_name().unwrap();
+        assert_eq!(names.len(), 18);
+        assert_eq!(names[..4], ["colors", "colors", "colors", "fa"]);
+        assert!(names[6..].iter().all(|name| name == "tensor"));
+
+        // 5 slots are left, and a name of 19 characters has no room for a repeat count
+        assert!(header.add_scalar_array("b_long_name_of_19_c", 6).is_err());
+        header.add_scalar_array("b_long_name_of_19_c", 4).unwrap();
+        header.add_scalar_array("rgba", 1000).unwrap();
+        assert_eq!(&header.scalar_name[180..189], b"rgba\x001000");
+        assert!(matches!(header.add_scalar("x"), Err(Error::TooManyNames { .. })));
+    }
+
     #[test]
     fn test_read_empty_names() {
         // N scalars/properties without a empty description should still return a vector of N
         // empty strings. It's not super practical, but that's the best we can do with such data.
-        let scalars = read_names(&vec![0; 80], 3);
+        let scalars = read_names(&[0; 80], 3).unwrap();
         assert_eq!(scalars, vec![String::from(""), String::from(""), String::from("")]);
     }
 
+    #[test]
+    fn test_read_invalid_names() {
+        let mut names = [0u8; 40];
+        names[..6].clone_from_slice(b"colors");
+        names[7] = b'3';
+        assert_eq!(read_names(&names, 3).unwrap(), vec!["colors", "colors", "colors"]);
+
+        // More names than declared
+        assert!(matches!(read_names(&names, 2), Err(Error::InvalidHeader(_))));
+
+        // Not a repeat count
+        names[7] = 0xff;
+        assert!(matches!(read_names(&names, 3), Err(Error::InvalidHeader(_))));
+        names[7] = b'0';
+        assert!(matches!(read_names(&names, 3), Err(Error::InvalidHeader(_))));
+
+        // Repeat counts can have many digits, as written by nibabel
+        names[7..9].clone_from_slice(b"12");
+        names[20..22].clone_from_slice(b"fa");
+        let read = read_names(&names, 13).unwrap();
+        assert!(read[..12].iter().all(|name| name == "colors"));
+        assert_eq!(read[12], "fa");
+        assert_eq!(nb_used_slots(&names, 13), 2);
+        assert!(matches!(read_names(&names, 12), Err(Error::InvalidHeader(_))));
+    }
+
     #[test]
     fn test_header_size() {
         assert_eq!(HEADER_SIZE, 1000);
@@ -0,0 +1,114 @@
+//! Streamline clustering with QuickBundles (Garyfallidis et al., 2012).
+//!
+//! All streamlines are resampled to the same number of points, then compared to the centroid of
+//! each cluster with the minimum average direct-flip (MDF) distance. A streamline joins the closest
+//! cluster if it's nearer than the threshold, otherwise it starts a new cluster. The result depends
+//! on the order of the streamlines.
+
+use crate::{
+    distance::direct_flip, resample::resample_streamline, ArraySequence, Error, Points, Result,
+    Streamlines,
+};
+
+/// Number of points used by the reference implementation.
+pub const DEFAULT_NB_POINTS: usize = 12;
+
+/// QuickBundles parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct QuickBundles {
+    threshold: f32,
+    nb_points: usize,
+}
+
+impl QuickBundles {
+    /// Streamlines nearer than `threshold` mm from a centroid join its cluster.
+    pub fn new(threshold: f32) -> QuickBundles {
+        QuickBundles { threshold, nb_points: DEFAULT_NB_POINTS }
+    }
+
+    /// Resample the streamlines to `nb_points` points before comparing them.
+    ///
+    /// Panics if `nb_points` is 0.
+    pub fn nb_points(mut self, nb_points: usize) -> Self {
+        assert!(nb_points > 0, "QuickBundles needs at least one point per streamline");
+        self.nb_points = nb_points;
+        self
+    }
+
+    /// Cluster `streamlines`, in order.
+    ///
+    /// Returns `Error::InvalidInput` if a streamline is empty, because it can't be resampled.
+    pub fn cluster(&self, streamlines: &Streamlines) -> Result<Clusters> {
+        let mut sums: Vec<Points> = vec![];
+        let mut centroids: Vec<Points> = vec![];
+        let mut sizes: Vec<usize> = vec![];
+        let mut assignments = Vec::with_capacity(streamlines.len());
+        for (i, streamline) in streamlines.into_iter().enumerate() {
+            if streamline.is_empty() {
+                return Err(Error::InvalidInput(format!("Streamline {} is empty", i)));
+            }
+            let streamline = resample_streamline(streamline, self.nb_points);
+
+            let mut best = None;
+            for (c, centroid) in centroids.iter().enumerate() {
+                let (distance, flipped) = direct_flip(&streamline, centroid);
+                if distance < self.threshold && best.is_none_or(|(d, _, _)| distance < d) {
+                    best = Some((distance, c, flipped));
+                }
+            }
+
+            match best {
+                Some((_, c, flipped)) => {
+                    let aligned: Points =
+                        if flipped { streamline.into_iter().rev().collect() } else { streamline };
+                    sizes[c] += 1;
+                    for ((s, centroid), p) in sums[c].iter_mut().zip(&mut centroids[c]).zip(aligned)
+                    {
+                        s.coords += p.coords;
+                        *centroid = *s / sizes[c] as f32;
+                    }
+                    assignments.push(c);
+                }
+                None => {
+                    assignments.push(centroids.len());
+                    sums.push(streamline.clone());
+                    centroids.push(streamline);
+                    sizes.push(1);
+                }
+            }
+        }
+
+        let mut arr = ArraySequence::with_capacity(centroids.len() * self.nb_points);
+        for centroid in centroids {
+            arr.extend(centroid);
+        }
+        Ok(Clusters { centroids: arr, sizes, assignments })
+    }
+}
+
+/// The result of a clustering.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Clusters {
+    /// Mean of all the (resampled and aligned) streamlines of each cluster.
+    pub centroids: Streamlines,
+    /// Number of streamlines in each cluster.
+    pub sizes: Vec<usize>,
+    /// Cluster index of each streamline, in order.
+    pub assignments: Vec<usize>,
+}
+
+impl Clusters {
+    /// Number of clusters.
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    /// Index of all streamlines in cluster `c`, in order.
+    pub fn members(&self, c: usize) -> Vec<usize> {
+        self.assignments.iter().enumerate().filter(|&(_, &a)| a == c).map(|(i, _)| i).collect()
+    }
+}
@@ -0,0 +1,121 @@
+//! Lossy compression of streamlines by linearization (Presseau et al., 2015).
+//!
+//! Points are removed as long as the removed points stay within `tol_error` mm of the compressed
+//! polyline and no segment becomes longer than `max_segment_length` mm. The first and last points
+//! are always kept. The per-point scalars of the kept points are kept as-is.
+
+use crate::{ArraySequence, Point, Points, Streamlines, Tractogram, TractogramItem};
+
+/// Default tolerance, in mm, of the reference implementation.
+pub const DEFAULT_TOL_ERROR: f32 = 0.01;
+
+/// Default maximum segment length, in mm, of the reference implementation.
+pub const DEFAULT_MAX_SEGMENT_LENGTH: f32 = 10.0;
+
+/// Indices of the points of `streamline` that must be kept.
+pub fn compress_indices(
+    streamline: &[Point],
+    tol_error: f32,
+    max_segment_length: f32,
+) -> Vec<usize> {
+    let nb_points = streamline.len();
+    if nb_points <= 2 {
+        return (0..nb_points).collect();
+    }
+
+    let mut kept = vec![0];
+    let mut start = 0;
+    for end in 2..nb_points {
+        let (a, b) = (&streamline[start], &streamline[end]);
+        let acceptable = (b - a).norm() <= max_segment_length
+            && streamline[start + 1..end].iter().all(|p| distance_to_segment(p, a, b) <= tol_error);
+        if !acceptable {
+            start = end - 1;
+            kept.push(start);
+        }
+    }
+    kept.push(nb_points - 1);
+    kept
+}
+
+/// Compressed copy of `streamline`.
+pub fn compress_streamline(
+    streamline: &[Point],
+    tol_error: f32,
+    max_segment_length: f32,
+) -> Points {
+    compress_indices(streamline, tol_error, max_segment_length)
+        .into_iter()
+        .map(|i| streamline[i])
+        .collect()
+}
+
+/// Compressed copy of all streamlines. Empty streamlines stay empty.
+pub fn compress_streamlines(
+    streamlines: &Streamlines,
+    tol_error: f32,
+    max_segment_length: f32,
+) -> Streamlines {
+    let mut compressed = ArraySequence::with_capacity(streamlines.data.len() / 2);
+    for streamline in streamlines {
+        compressed.data.extend(compress_streamline(streamline, tol_error, max_segment_length));
+        compressed.offsets.push(compressed.data.len());
+    }
+    compressed
+}
+
+/// Compressed copy of a tractogram. Only the scalars of the kept points are kept. The properties
+/// are copied. Empty streamlines stay empty.
+pub fn compress_tractogram(
+    tractogram: &Tractogram,
+    tol_error: f32,
+    max_segment_length: f32,
+) -> Tractogram {
+    let mut streamlines = ArraySequence::with_capacity(tractogram.streamlines.data.len() / 2);
+    let mut scalars = ArraySequence::with_capacity(tractogram.scalars.data.len() / 2);
+    for (streamline, streamline_scalars, _) in tractogram {
+        let kept = compress_indices(streamline, tol_error, max_segment_length);
+        // Always push the offsets, even for an empty streamline, so that the streamlines, scalars
+        // and properties stay aligned.
+        streamlines.data.extend(kept.iter().map(|&i| streamline[i]));
+        streamlines.offsets.push(streamlines.data.len());
+        if !tractogram.scalars.is_empty() {
+            scalars.data.extend(decimate_scalars(streamline.len(), streamline_scalars, &kept));
+            scalars.offsets.push(scalars.data.len());
+        }
+    }
+    Tractogram::new(streamlines, scalars, tractogram.properties.clone())
+}
+
+/// Compress a single item, e.g. between a `Reader` and a `Writer`.
+pub fn compress_item(
+    item: TractogramItem,
+    tol_error: f32,
+    max_segment_length: f32,
+) -> TractogramItem {
+    let (streamline, streamline_scalars, properties) = item;
+    let kept = compress_indices(&streamline, tol_error, max_segment_length);
+    let mut scalars = ArraySequence::empty();
+    scalars.extend(decimate_scalars(streamline.len(), &streamline_scalars.data, &kept));
+    (kept.iter().map(|&i| streamline[i]).collect(), scalars, properties)
+}
+
+/// Keep the scalars of the `kept` points. `scalars` holds all scalars of all points, in order.
+fn decimate_scalars(nb_points: usize, scalars: &[f32], kept: &[usize]) -> Vec<f32> {
+    if nb_points == 0 || scalars.is_empty() {
+        return vec![];
+    }
+    let nb_scalars = scalars.len() / nb_points;
+    kept.iter().flat_map(|&i| &scalars[i * nb_scalars..(i + 1) * nb_scalars]).cloned().collect()
+}
+
+/// Shortest distance between `p` and the segment `[a, b]`.
+fn distance_to_segment(p: &Point, a: &Point, b: &Point) -> f32 {
+    let ab = b - a;
+    let squared_length = ab.norm_squared();
+    if squared_length == 0.0 {
+        return (p - a).norm();
+    }
+    let t = ((p - a).dot(&ab) / squared_length).clamp(0.0, 1.0);
+    (p - (a + ab * t)).norm()
+}
@@ -0,0 +1,334 @@
+//! Transparent gzip (`gzip` feature) and zstd (`zstd` feature) compression of trk files.
+//!
+//! Compressed inputs are detected from their first bytes, so `Reader::new` reads `.trk.gz` and
+//! `.trk.zst` files as any other trk file. Compressed outputs are chosen from the extension given
+//! to `Writer::new`.
+//!
+//! The `n_count` field of the header can only be written once all streamlines are known, but a
+//! compressed stream can't be modified afterwards. The header is thus stored uncompressed, alone
+//! in the first gzip member or zstd frame, and the streamlines are compressed in a second one.
+//! Decoders read both as a single stream, and `n_count` can be patched in place.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+#[cfg(feature = "gzip")]
+use flate2::{read::MultiGzDecoder, write::GzEncoder, Crc};
+
+use crate::{cheader::HEADER_SIZE, error::path_error, CHeader, Result, TrkEndianness};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression of a trk file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Compression of a file starting with the bytes `magic`.
+    pub fn detect(magic: &[u8]) -> Compression {
+        if magic.starts_with(&GZIP_MAGIC) {
+            Compression::Gzip
+        } else if magic.starts_with(&ZSTD_MAGIC) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Compression of a new file, from its extension: `.gz` or `.zst`.
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Compression {
+        let extension = path.as_ref().extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Bytes written before the header, to hold it uncompressed.
+    fn header_prefix(self) -> Vec<u8> {
+        let mut prefix = vec![];
+        match self {
+            Compression::None => {}
+            Compression::Gzip => {
+                // Member header: deflate, no flags, no time, unknown OS
+                prefix.extend([0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255]);
+                // A single final stored block
+                let len = HEADER_SIZE as u16;
+                prefix.push(1);
+                prefix.extend(len.to_le_bytes());
+                prefix.extend((!len).to_le_bytes());
+            }
+            Compression::Zstd => {
+                // Single segment frame, with a 2 bytes content size and no checksum
+                prefix.extend(ZSTD_MAGIC);
+                prefix.push(0x60);
+                prefix.extend((HEADER_SIZE as u16 - 256).to_le_bytes());
+                // A single last raw block
+                let block_header = 1 | (HEADER_SIZE as u32) << 3;
+                prefix.extend(&block_header.to_le_bytes()[..3]);
+            }
+        }
+        prefix
+    }
+}
+
+#[cfg(any(not(feature = "gzip"), not(feature = "zstd")))]
+fn missing_feature(name: &str) -> crate::Error {
+    crate::Error::InvalidHeader(format!("{} compressed files require the `{}` feature", name, name))
+}
+
+/// A trk file opened by `Reader::new`, decompressed on the fly if needed.
+///
+/// Only uncompressed files can seek.
+pub struct TrkInput {
+    input: Input,
+}
+
+enum Input {
+    Plain(BufReader<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(Box<BufReader<MultiGzDecoder<BufReader<File>>>>),
+    #[cfg(feature = "zstd")]
+    Zstd(BufReader<zstd::Decoder<'static, BufReader<File>>>),
+}
+
+impl TrkInput {
+    /// Open `path`, detecting its compression from its first bytes.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<TrkInput> {
+        let f = File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let mut reader = BufReader::new(f);
+
+        // Peek at the magic number, without consuming it
+        let input = match Compression::detect(reader.fill_buf()?) {
+            Compression::None => Input::Plain(reader),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Input::Gzip(Box::new(BufReader::new(MultiGzDecoder::new(reader)))),
+            #[cfg(not(feature = "gzip"))]
+            Compression::Gzip => return Err(missing_feature("gzip")),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Input::Zstd(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(missing_feature("zstd")),
+        };
+        Ok(TrkInput { input })
+    }
+
+    pub fn compression(&self) -> Compression {
+        match self.input {
+            Input::Plain(_) => Compression::None,
+            #[cfg(feature = "gzip")]
+            Input::Gzip(_) => Compression::Gzip,
+            #[cfg(feature = "zstd")]
+            Input::Zstd(_) => Compression::Zstd,
+        }
+    }
+}
+
+impl Read for TrkInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.input {
+            Input::Plain(r) => r.read(buf),
+            #[cfg(feature = "gzip")]
+            Input::Gzip(r) => r.read(buf),
+            #[cfg(feature = "zstd")]
+            Input::Zstd(r) => r.read(buf),
+        }
+    }
+}
+
+impl Seek for TrkInput {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match &mut self.input {
+            Input::Plain(r) => r.seek(pos),
+            #[allow(unreachable_patterns)]
+            _ => Err(io::Error::new(ErrorKind::Unsupported, "Can't seek in a compressed trk file")),
+        }
+    }
+}
+
+/// A trk file created by `Writer::new`, compressed on the fly if its extension is `.gz` or
+/// `.zst`.
+pub struct TrkOutput {
+    output: Output,
+    compression: Compression,
+
+    /// Uncompressed header of a compressed file, as written until now.
+    header: Vec<u8>,
+    /// `true` once the compressed stream has been completed.
+    finished: bool,
+}
+
+enum Output {
+    Plain(BufWriter<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(GzEncoder<BufWriter<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl TrkOutput {
+    /// Create `path`, compressed as requested by its extension.
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<TrkOutput> {
+        let compression = Compression::from_extension(&path);
+        let create = || -> Result<BufWriter<File>> {
+            let f = File::create(path.as_ref())
+                .map_err(|e| path_error(e, "Failed to create", &path))?;
+            let mut writer = BufWriter::new(f);
+            writer.write_all(&compression.header_prefix())?;
+            Ok(writer)
+        };
+
+        // The encoders don't write anything before receiving data, so the uncompressed header is
+        // written directly in their inner writer.
+        let output = match compression {
+            Compression::None => Output::Plain(create()?),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                Output::Gzip(GzEncoder::new(create()?, flate2::Compression::default()))
+            }
+            #[cfg(not(feature = "gzip"))]
+            Compression::Gzip => return Err(missing_feature("gzip")),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Output::Zstd(zstd::Encoder::new(create()?, 0)?),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => return Err(missing_feature("zstd")),
+        };
+        Ok(TrkOutput { output, compression, header: vec![], finished: false })
+    }
+
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Call `fsync` on the file.
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        self.file().get_ref().sync_all()
+    }
+
+    /// Overwrite the `n_count` field of the header, then go back to the end of the data.
+    ///
+    /// The compressed stream is completed first, so nothing can be written afterwards.
+    pub(crate) fn patch_n_count(&mut self, n_count: i32) -> io::Result<()> {
+        if self.compression == Compression::None {
+            let file = self.file();
+            CHeader::seek_n_count_field(file, 0)?;
+            file.write_i32::<TrkEndianness>(n_count)?;
+            file.seek(SeekFrom::End(0))?;
+            return Ok(());
+        }
+        if self.header.len() < HEADER_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "The trk header is incomplete"));
+        }
+
+        self.finish_stream()?;
+        let n_count_offset = HEADER_SIZE - 12;
+        self.header[n_count_offset..n_count_offset + 4].copy_from_slice(&n_count.to_le_bytes());
+        let header_start = self.compression.header_prefix().len() as u64;
+        let crc = self.header_crc();
+        let file = self.file();
+        CHeader::seek_n_count_field(file, header_start)?;
+        file.write_i32::<TrkEndianness>(n_count)?;
+        if let Some(crc) = crc {
+            file.seek(SeekFrom::Start(header_start + HEADER_SIZE as u64))?;
+            file.write_u32::<LittleEndian>(crc)?;
+        }
+        file.seek(SeekFrom::End(0))?;
+        file.flush()
+    }
+
+    fn file(&mut self) -> &mut BufWriter<File> {
+        match &mut self.output {
+            Output::Plain(w) => w,
+            #[cfg(feature = "gzip")]
+            Output::Gzip(w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(w) => w.get_mut(),
+        }
+    }
+
+    /// CRC32 of the uncompressed header, for the gzip member trailer.
+    fn header_crc(&self) -> Option<u32> {
+        match self.compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut crc = Crc::new();
+                crc.update(&self.header);
+                Some(crc.sum())
+            }
+            _ => None,
+        }
+    }
+
+    /// Called when the header is complete, before compressing anything.
+    fn end_header(&mut self) -> io::Result<()> {
+        if let Some(crc) = self.header_crc() {
+            let file = self.file();
+            file.write_u32::<LittleEndian>(crc)?;
+            file.write_u32::<LittleEndian>(HEADER_SIZE as u32)?;
+        }
+        Ok(())
+    }
+
+    fn finish_stream(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        match &mut self.output {
+            Output::Plain(_) => Ok(()),
+            #[cfg(feature = "gzip")]
+            Output::Gzip(w) => w.try_finish(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(w) => w.do_finish(),
+        }
+    }
+}
+
+impl Write for TrkOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("The compressed trk file is finished"));
+        }
+        if self.compression != Compression::None && self.header.len() < HEADER_SIZE {
+            let n = buf.len().min(HEADER_SIZE - self.header.len());
+            self.file().write_all(&buf[..n])?;
+            self.header.extend_from_slice(&buf[..n]);
+            if self.header.len() == HEADER_SIZE {
+                self.end_header()?;
+            }
+            return Ok(n);
+        }
+
+        match &mut self.output {
+            Output::Plain(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            Output::Gzip(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Flushing an encoder ends a block, so it's only done when really needed.
+        if self.finished || self.header.len() < HEADER_SIZE {
+            return self.file().flush();
+        }
+        match &mut self.output {
+            Output::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Output::Gzip(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(w) => w.flush(),
+        }
+    }
+}
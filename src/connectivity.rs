@@ -0,0 +1,227 @@
+//! Connectivity matrices, built from the labels of a parcellation at both endpoints of each
+//! streamline.
+//!
+//! The streamlines must be in voxel space, as read by `Reader::to_voxel_space` or by a
+//! `VoxelSpaceReader`, and the parcellation must be on the same grid as the trk file. The label 0
+//! is the background. A streamline with an endpoint in the background, or outside of the grid, is
+//! not part of the matrix.
+
+use std::{collections::HashMap, path::Path};
+
+use ndarray::{Array2, Array3};
+
+use crate::{
+    metrics,
+    roi::{check_grid, read_volume},
+    tdi::voxel_of,
+    Error, Header, Point, Reader, Result, Spacing, Tractogram,
+};
+
+/// What is saved in each cell of a connectivity matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectivityWeight {
+    /// Number of streamlines connecting both labels.
+    Count,
+    /// Mean length, in mm, of the streamlines connecting both labels.
+    MeanLength,
+    /// Mean of a property, by index, of the streamlines connecting both labels.
+    MeanProperty(usize),
+}
+
+impl ConnectivityWeight {
+    /// Mean of the property called `name` in `header`.
+    pub fn mean_property(header: &Header, name: &str) -> Result<ConnectivityWeight> {
+        match header.properties_name.iter().position(|n| n == name) {
+            Some(i) => Ok(ConnectivityWeight::MeanProperty(i)),
+            None => Err(Error::InvalidHeader(format!("No property named {:?}", name))),
+        }
+    }
+
+    /// Returns an error if the property of `MeanProperty` is not one of the `nb_properties`.
+    fn check(self, nb_properties: usize) -> Result<()> {
+        match self {
+            ConnectivityWeight::MeanProperty(i) if i >= nb_properties => Err(Error::InvalidHeader(
+                format!("No property {}, the streamlines have {} properties", i, nb_properties),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A labeled parcellation, indexed in voxel order `[i, j, k]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Atlas {
+    data: Array3<i32>,
+    spacing: Spacing,
+    labels: Vec<i32>,
+}
+
+impl Atlas {
+    /// Build an atlas from its labels and its voxel size, in mm.
+    pub fn new(data: Array3<i32>, spacing: Spacing) -> Atlas {
+        let mut labels = data.iter().cloned().filter(|&label| label != 0).collect::<Vec<_>>();
+        labels.sort_unstable();
+        labels.dedup();
+        Atlas { data, spacing, labels }
+    }
+
+    /// Load a 3D NIfTI image. The values are rounded to the nearest integer label.
+    pub fn from_nifti<P: AsRef<Path>>(path: P) -> Result<Atlas> {
+        let (header, data) = read_volume(path, "An atlas")?;
+        let spacing = Spacing::from(header.raw_header().voxel_size);
+        Ok(Atlas::new(data.mapv(|v| v.round() as i32), spacing))
+    }
+
+    pub fn shape(&self) -> [usize; 3] {
+        let shape = self.data.shape();
+        [shape[0], shape[1], shape[2]]
+    }
+
+    /// All labels, except the background, sorted. They are the rows and columns of a
+    /// connectivity matrix.
+    pub fn labels(&self) -> &[i32] {
+        &self.labels
+    }
+
+    /// Label at `point`, in voxel space. 0 outside of the grid.
+    pub fn label(&self, point: &Point) -> i32 {
+        let voxel = voxel_of(&point.coords);
+        if voxel.iter().any(|&v| v < 0) {
+            return 0;
+        }
+        self.data.get(voxel.map(|v| v as usize)).cloned().unwrap_or(0)
+    }
+
+    /// Labels at the first and last points of `streamline`, in voxel space, or `None` if any of
+    /// them is the background.
+    pub fn endpoint_labels(&self, streamline: &[Point]) -> Option<(i32, i32)> {
+        let (first, last) = (self.label(streamline.first()?), self.label(streamline.last()?));
+        if first == 0 || last == 0 {
+            None
+        } else {
+            Some((first, last))
+        }
+    }
+
+    /// Build the connectivity matrix of `tractogram`, which must be in voxel space.
+    ///
+    /// If `with_assignments` is `true`, the label pair of each streamline is also returned.
+    /// Returns an error if `weight` is the mean of a property that any streamline doesn't have.
+    pub fn connectivity(
+        &self,
+        tractogram: &Tractogram,
+        weight: ConnectivityWeight,
+        with_assignments: bool,
+    ) -> Result<Connectivity> {
+        // There's no header to read the number of properties from, so check all streamlines
+        for (_, _, properties) in tractogram {
+            weight.check(properties.len())?;
+        }
+        let mut accumulator = Accumulator::new(self, weight, with_assignments);
+        for (streamline, _, properties) in tractogram {
+            accumulator.add(streamline, properties);
+        }
+        Ok(accumulator.finish())
+    }
+
+    /// Build the connectivity matrix of the trk file at `path`. Streamlines are read one at a
+    /// time, so any file size can be used.
+    ///
+    /// Returns an error if the atlas is not on the same grid as the trk file, or if `weight` is the
+    /// mean of a property that the trk file doesn't have.
+    pub fn connectivity_trk<P: AsRef<Path>>(
+        &self,
+        path: P,
+        weight: ConnectivityWeight,
+        with_assignments: bool,
+    ) -> Result<Connectivity> {
+        let reader = Reader::new(path)?;
+        check_grid(&reader.header, self.shape(), "Atlas")?;
+        weight.check(reader.header.properties_name.len())?;
+
+        let mut accumulator = Accumulator::new(self, weight, with_assignments);
+        for item in reader.to_voxel_space(self.spacing) {
+            let (streamline, _, properties) = item?;
+            accumulator.add(&streamline, &properties);
+        }
+        Ok(accumulator.finish())
+    }
+}
+
+/// A symmetric connectivity matrix.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Connectivity {
+    /// Label of each row and column of `matrix`.
+    pub labels: Vec<i32>,
+    /// Weight between each pair of labels. Cells without any streamline are 0.
+    pub matrix: Array2<f32>,
+    /// Endpoint labels of each streamline, in order, if requested. `None` for the streamlines that
+    /// are not part of the matrix.
+    pub assignments: Option<Vec<Option<(i32, i32)>>>,
+}
+
+struct Accumulator<'a> {
+    atlas: &'a Atlas,
+    weight: ConnectivityWeight,
+    index: HashMap<i32, usize>,
+    sums: Array2<f64>,
+    counts: Array2<usize>,
+    assignments: Option<Vec<Option<(i32, i32)>>>,
+}
+
+impl<'a> Accumulator<'a> {
+    fn new(atlas: &'a Atlas, weight: ConnectivityWeight, with_assignments: bool) -> Self {
+        let index = atlas.labels.iter().enumerate().map(|(i, &label)| (label, i)).collect();
+        let n = atlas.labels.len();
+        let assignments = if with_assignments { Some(vec![]) } else { None };
+        Accumulator {
+            atlas,
+            weight,
+            index,
+            sums: Array2::zeros((n, n)),
+            counts: Array2::zeros((n, n)),
+            assignments,
+        }
+    }
+
+    fn add(&mut self, streamline: &[Point], properties: &[f32]) {
+        let pair = self.atlas.endpoint_labels(streamline);
+        if let Some(assignments) = &mut self.assignments {
+            assignments.push(pair);
+        }
+        let (a, b) = match pair {
+            Some((a, b)) => (self.index[&a], self.index[&b]),
+            None => return,
+        };
+
+        let value = match self.weight {
+            ConnectivityWeight::Count => 1.0,
+            ConnectivityWeight::MeanLength => {
+                let spacing = self.atlas.spacing;
+                let mm = streamline.iter().map(|p| p.coords.component_mul(&spacing).into());
+                metrics::length(&mm.collect::<Vec<Point>>()) as f64
+            }
+            ConnectivityWeight::MeanProperty(i) => properties[i] as f64,
+        };
+        self.sums[(a, b)] += value;
+        self.counts[(a, b)] += 1;
+        if a != b {
+            self.sums[(b, a)] += value;
+            self.counts[(b, a)] += 1;
+        }
+    }
+
+    fn finish(self) -> Connectivity {
+        let matrix = match self.weight {
+            ConnectivityWeight::Count => self.sums.mapv(|sum| sum as f32),
+            _ => ndarray::Zip::from(&self.sums).and(&self.counts).map_collect(|&sum, &count| {
+                if count == 0 {
+                    0.0
+                } else {
+                    (sum / count as f64) as f32
+                }
+            }),
+        };
+        Connectivity { labels: self.atlas.labels.clone(), matrix, assignments: self.assignments }
+    }
+}
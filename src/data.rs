@@ -0,0 +1,276 @@
+//! Named data per point and per streamline, with any number of components.
+//!
+//! A `Tractogram` stores its scalars and properties as rows of values, one row per point or per
+//! streamline, and the `Header` names each column. A multi-component array, like a RGB color, is a
+//! group of consecutive columns with the same name. `DataPerPoint` and `DataPerStreamline` split
+//! these rows into named arrays, and the trk header saves each array as a single name with a
+//! repeat count (`colors\03`).
+
+use crate::{
+    header::{select_columns, split_names},
+    ArraySequence, Error, Header, Result, Tractogram,
+};
+
+/// A named array, with `nb_components` values per row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataArray {
+    pub name: String,
+    pub nb_components: usize,
+    /// All values, row by row.
+    pub data: Vec<f32>,
+}
+
+impl DataArray {
+    pub fn nb_rows(&self) -> usize {
+        self.data.len() / self.nb_components
+    }
+
+    /// Values of row `i`. Panics if `i` is out of bounds.
+    pub fn row(&self, i: usize) -> &[f32] {
+        &self.data[i * self.nb_components..(i + 1) * self.nb_components]
+    }
+
+    pub fn rows(&self) -> std::slice::ChunksExact<'_, f32> {
+        self.data.chunks_exact(self.nb_components)
+    }
+}
+
+macro_rules! data_arrays {
+    ($(#[$doc:meta])* $name:ident, $row:literal) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, Default, PartialEq)]
+        pub struct $name {
+            arrays: Vec<DataArray>,
+        }
+
+        impl $name {
+            pub fn new() -> $name {
+                $name::default()
+            }
+
+            #[doc = concat!("Split `data`, one row per ", $row, ", into named arrays.")]
+            ///
+            /// `names` has one name per column. Consecutive columns with the same name form a
+            /// single array. Returns an error if a name is used by non-consecutive columns.
+            pub fn from_columns(names: &[String], data: &[f32]) -> Result<$name> {
+                if names.is_empty() {
+                    if !data.is_empty() {
+                        return Err(Error::InvalidHeader(format!(
+                            "{} values per {} have no name",
+                            data.len(),
+                            $row
+                        )));
+                    }
+                    return Ok($name::new());
+                }
+                if data.len() % names.len() != 0 {
+                    return Err(Error::InvalidHeader(format!(
+                        "{} values can't be split in {} columns",
+                        data.len(),
+                        names.len()
+                    )));
+                }
+
+                let arrays = split_names(names)?
+                    .into_iter()
+                    .map(|(name, start, nb_components)| DataArray {
+                        name: name.to_string(),
+                        nb_components,
+                        data: select_columns(data, names.len(), start, nb_components).collect(),
+                    })
+                    .collect();
+                Ok($name { arrays })
+            }
+
+            /// Names of all columns, with one name per component, and all rows.
+            pub fn to_columns(&self) -> (Vec<String>, Vec<f32>) {
+                let names = self
+                    .arrays
+                    .iter()
+                    .flat_map(|array| std::iter::repeat_n(array.name.clone(), array.nb_components))
+                    .collect();
+                let mut data = Vec::with_capacity(self.nb_rows() * self.nb_components());
+                for i in 0..self.nb_rows() {
+                    for array in &self.arrays {
+                        data.extend_from_slice(array.row(i));
+                    }
+                }
+                (names, data)
+            }
+
+            /// Number of arrays.
+            pub fn len(&self) -> usize {
+                self.arrays.len()
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.arrays.is_empty()
+            }
+
+            #[doc = concat!("Number of rows, that is, of ", $row, "s. 0 if there's no array.")]
+            pub fn nb_rows(&self) -> usize {
+                self.arrays.first().map_or(0, DataArray::nb_rows)
+            }
+
+            /// Number of values per row, all arrays included.
+            pub fn nb_components(&self) -> usize {
+                self.arrays.iter().map(|array| array.nb_components).sum()
+            }
+
+            pub fn contains(&self, name: &str) -> bool {
+                self.get(name).is_some()
+            }
+
+            pub fn get(&self, name: &str) -> Option<&DataArray> {
+                self.arrays.iter().find(|array| array.name == name)
+            }
+
+            pub fn get_mut(&mut self, name: &str) -> Option<&mut DataArray> {
+                self.arrays.iter_mut().find(|array| array.name == name)
+            }
+
+            /// Add an array of `nb_components` values per row, or replace the array with the same
+            /// name, keeping its position. Returns the replaced array, if any.
+            ///
+            /// Panics if `nb_components` is 0, or if the number of rows is different from the
+            /// other arrays.
+            pub fn insert(
+                &mut self,
+                name: &str,
+                nb_components: usize,
+                data: Vec<f32>,
+            ) -> Option<DataArray> {
+                assert!(nb_components > 0, "An array needs at least one component");
+                assert!(
+                    data.len() % nb_components == 0,
+                    "{} values can't be split in rows of {} components",
+                    data.len(),
+                    nb_components
+                );
+                let array = DataArray { name: name.to_string(), nb_components, data };
+                let others = self.arrays.iter().filter(|other| other.name != name);
+                if let Some(other) = others.clone().next() {
+                    assert_eq!(
+                        array.nb_rows(),
+                        other.nb_rows(),
+                        "{:?} has {} rows but {:?} has {}",
+                        name,
+                        array.nb_rows(),
+                        other.name,
+                        other.nb_rows()
+                    );
+                }
+
+                match self.get_mut(name) {
+                    Some(old) => Some(std::mem::replace(old, array)),
+                    None => {
+                        self.arrays.push(array);
+                        None
+                    }
+                }
+            }
+
+            pub fn remove(&mut self, name: &str) -> Option<DataArray> {
+                let i = self.arrays.iter().position(|array| array.name == name)?;
+                Some(self.arrays.remove(i))
+            }
+
+            pub fn iter(&self) -> std::slice::Iter<'_, DataArray> {
+                self.arrays.iter()
+            }
+        }
+
+        impl<'a> IntoIterator for &'a $name {
+            type Item = &'a DataArray;
+            type IntoIter = std::slice::Iter<'a, DataArray>;
+
+            fn into_iter(self) -> Self::IntoIter {
+                self.arrays.iter()
+            }
+        }
+    };
+}
+
+data_arrays!(
+    /// Named arrays with one row per point, e.g. `colors` with 3 components. Saved as the
+    /// scalars of a trk file.
+    DataPerPoint,
+    "point"
+);
+
+data_arrays!(
+    /// Named arrays with one row per streamline, e.g. `length` with 1 component. Saved as the
+    /// properties of a trk file.
+    DataPerStreamline,
+    "streamline"
+);
+
+impl Tractogram {
+    /// Scalars of all points, as named arrays. The names are read from `header`.
+    pub fn data_per_point(&self, header: &Header) -> Result<DataPerPoint> {
+        DataPerPoint::from_columns(&header.scalars_name, &self.scalars.data)
+    }
+
+    /// Properties of all streamlines, as named arrays. The names are read from `header`.
+    pub fn data_per_streamline(&self, header: &Header) -> Result<DataPerStreamline> {
+        DataPerStreamline::from_columns(&header.properties_name, &self.properties.data)
+    }
+
+    /// Replace the scalars of all points by `data`, and their names in `header`.
+    ///
+    /// Returns an error, without modifying anything, if `header` can't hold the names. Panics if
+    /// `data` doesn't have one row per point.
+    pub fn set_data_per_point(&mut self, header: &mut Header, data: &DataPerPoint) -> Result<()> {
+        let nb_points = self.streamlines.data.len();
+        if !data.is_empty() {
+            assert_eq!(data.nb_rows(), nb_points, "There must be one row per point");
+        }
+
+        let mut new_header = header.clone();
+        new_header.clear_scalars();
+        for array in data {
+            new_header.add_scalar_array(&array.name, array.nb_components)?;
+        }
+
+        let (_, columns) = data.to_columns();
+        self.scalars = if data.is_empty() {
+            ArraySequence::empty()
+        } else {
+            let nb_components = data.nb_components();
+            let lengths = self.streamlines.iter().map(|s| s.len() * nb_components).collect();
+            ArraySequence::new(lengths, columns)
+        };
+        *header = new_header;
+        Ok(())
+    }
+
+    /// Replace the properties of all streamlines by `data`, and their names in `header`.
+    ///
+    /// Returns an error, without modifying anything, if `header` can't hold the names. Panics if
+    /// `data` doesn't have one row per streamline.
+    pub fn set_data_per_streamline(
+        &mut self,
+        header: &mut Header,
+        data: &DataPerStreamline,
+    ) -> Result<()> {
+        let nb_streamlines = self.streamlines.len();
+        if !data.is_empty() {
+            assert_eq!(data.nb_rows(), nb_streamlines, "There must be one row per streamline");
+        }
+
+        let mut new_header = header.clone();
+        new_header.clear_properties();
+        for array in data {
+            new_header.add_property_array(&array.name, array.nb_components)?;
+        }
+
+        let (_, columns) = data.to_columns();
+        self.properties = if data.is_empty() {
+            ArraySequence::empty()
+        } else {
+            ArraySequence::new(vec![data.nb_components(); nb_streamlines], columns)
+        };
+        *header = new_header;
+        Ok(())
+    }
+}
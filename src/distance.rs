@@ -0,0 +1,153 @@
+//! Distances between streamlines and between bundles.
+//!
+//! All distances are in the space of the points, so they are in mm when the streamlines are read
+//! in world space, which is the default. The matrices are saved in row-major order, i.e. the
+//! distance between `a[i]` and `b[j]` is at `i * b.len() + j`.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{Point, Streamlines};
+
+/// How the two directed mean minimum distances are combined in `mam`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mam {
+    /// Mean of both directions.
+    Mean,
+    /// Smallest of both directions.
+    Min,
+    /// Largest of both directions.
+    Max,
+}
+
+/// Minimum average direct-flip (MDF) distance, i.e. the mean distance between the corresponding
+/// points of `a` and `b`, or of `a` and the reversed `b`, whichever is smaller.
+///
+/// Panics if `a` and `b` don't have the same number of points. Use `resample` first. Returns
+/// `f32::INFINITY` if both are empty, like `mam`.
+pub fn mdf(a: &[Point], b: &[Point]) -> f32 {
+    direct_flip(a, b).0
+}
+
+/// Mean average minimum (MAM) distance. For each point of `a`, the distance to the nearest point
+/// of `b` is averaged, and vice versa. Both directions are then combined by `mam`.
+///
+/// `a` and `b` can have different numbers of points. Returns `f32::INFINITY` if any of them is
+/// empty.
+pub fn mam(a: &[Point], b: &[Point], mam: Mam) -> f32 {
+    let (ab, ba) = (mean_minimum(a, b), mean_minimum(b, a));
+    match mam {
+        Mam::Mean => (ab + ba) / 2.0,
+        Mam::Min => ab.min(ba),
+        Mam::Max => ab.max(ba),
+    }
+}
+
+/// MDF distance between all streamlines of `a` and all streamlines of `b`.
+pub fn mdf_matrix(a: &Streamlines, b: &Streamlines) -> Vec<f32> {
+    matrix(a, b, mdf)
+}
+
+/// MAM distance between all streamlines of `a` and all streamlines of `b`.
+pub fn mam_matrix(a: &Streamlines, b: &Streamlines, mam_type: Mam) -> Vec<f32> {
+    matrix(a, b, |s1, s2| mam(s1, s2, mam_type))
+}
+
+/// Same as `mdf_matrix`, but the rows are computed on all threads.
+#[cfg(feature = "rayon")]
+pub fn mdf_matrix_parallel(a: &Streamlines, b: &Streamlines) -> Vec<f32> {
+    matrix_parallel(a, b, mdf)
+}
+
+/// Same as `mam_matrix`, but the rows are computed on all threads.
+#[cfg(feature = "rayon")]
+pub fn mam_matrix_parallel(a: &Streamlines, b: &Streamlines, mam_type: Mam) -> Vec<f32> {
+    matrix_parallel(a, b, |s1, s2| mam(s1, s2, mam_type))
+}
+
+/// Bundle minimum distance. For each streamline of `a`, the MDF distance to the nearest
+/// streamline of `b` is averaged, and vice versa. Returns the mean of both directions.
+///
+/// Panics if the streamlines don't all have the same number of points. Returns `f32::INFINITY`
+/// if any bundle is empty.
+pub fn bundle_distance(a: &Streamlines, b: &Streamlines) -> f32 {
+    bundle_distance_from_matrix(&mdf_matrix(a, b), a.len(), b.len())
+}
+
+/// Same as `bundle_distance`, but the distances are computed on all threads.
+#[cfg(feature = "rayon")]
+pub fn bundle_distance_parallel(a: &Streamlines, b: &Streamlines) -> f32 {
+    bundle_distance_from_matrix(&mdf_matrix_parallel(a, b), a.len(), b.len())
+}
+
+/// MDF distance between `a` and `b`, and whether `b` had to be flipped to get it.
+pub(crate) fn direct_flip(a: &[Point], b: &[Point]) -> (f32, bool) {
+    assert_eq!(a.len(), b.len(), "MDF needs streamlines with the same number of points");
+    if a.is_empty() {
+        return (f32::INFINITY, false);
+    }
+    let n = a.len() as f32;
+    let direct = a.iter().zip(b).map(|(p, q)| (p - q).norm()).sum::<f32>() / n;
+    let flipped = a.iter().zip(b.iter().rev()).map(|(p, q)| (p - q).norm()).sum::<f32>() / n;
+    if flipped < direct {
+        (flipped, true)
+    } else {
+        (direct, false)
+    }
+}
+
+/// Mean, over all points of `a`, of the distance to the nearest point of `b`.
+fn mean_minimum(a: &[Point], b: &[Point]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return f32::INFINITY;
+    }
+    let sum = a
+        .iter()
+        .map(|p| b.iter().map(|q| (p - q).norm_squared()).fold(f32::INFINITY, f32::min).sqrt())
+        .sum::<f32>();
+    sum / a.len() as f32
+}
+
+fn matrix<F>(a: &Streamlines, b: &Streamlines, distance: F) -> Vec<f32>
+where
+    F: Fn(&[Point], &[Point]) -> f32,
+{
+    let mut matrix = Vec::with_capacity(a.len() * b.len());
+    for s1 in a {
+        matrix.extend(b.into_iter().map(|s2| distance(s1, s2)));
+    }
+    matrix
+}
+
+#[cfg(feature = "rayon")]
+fn matrix_parallel<F>(a: &Streamlines, b: &Streamlines, distance: F) -> Vec<f32>
+where
+    F: Fn(&[Point], &[Point]) -> f32 + Sync,
+{
+    let mut matrix = vec![0.0; a.len() * b.len()];
+    if b.is_empty() {
+        return matrix;
+    }
+    matrix.par_chunks_mut(b.len()).enumerate().for_each(|(i, row)| {
+        for (d, s2) in row.iter_mut().zip(b) {
+            *d = distance(&a[i], s2);
+        }
+    });
+    matrix
+}
+
+fn bundle_distance_from_matrix(matrix: &[f32], nb_rows: usize, nb_cols: usize) -> f32 {
+    if nb_rows == 0 || nb_cols == 0 {
+        return f32::INFINITY;
+    }
+    let rows = matrix.chunks(nb_cols).map(|row| row.iter().cloned().fold(f32::INFINITY, f32::min));
+    let mut cols = vec![f32::INFINITY; nb_cols];
+    for row in matrix.chunks(nb_cols) {
+        for (min, &d) in cols.iter_mut().zip(row) {
+            *min = min.min(d);
+        }
+    }
+    let rows = rows.sum::<f32>() / nb_rows as f32;
+    let cols = cols.iter().sum::<f32>() / nb_cols as f32;
+    (rows + cols) / 2.0
+}
@@ -0,0 +1,120 @@
+use std::{fmt, io};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// All errors that can be returned while reading or writing a tractogram.
+#[derive(Debug)]
+pub enum Error {
+    /// An I/O error occurred, outside of the streamlines data.
+    Io(io::Error),
+
+    /// The file doesn't start with the expected signature (magic number).
+    BadMagic,
+
+    /// The file ended in the middle of a streamline.
+    ///
+    /// `byte_offset` is the position, in the file, of the first byte of the truncated streamline.
+    Truncated { streamline_index: usize, byte_offset: u64 },
+
+    /// A streamline declares an impossible number of points.
+    InvalidNbPoints { streamline_index: usize, nb_points: i32 },
+
+    /// The header is malformed or uses an unsupported feature.
+    InvalidHeader(String),
+
+    /// The 4x4 affine of the header can't be inverted.
+    AffineNotInvertible,
+
+    /// The header can't hold more scalars or properties names.
+    TooManyNames { kind: &'static str, max: usize },
+
+    /// A scalar or property name can't be saved in a header.
+    InvalidName(String),
+
+    /// The number of streamlines declared upfront doesn't match what has been written.
+    CountMismatch { declared: usize, written: usize },
+
+    /// A sidecar index is malformed or doesn't describe its trk file anymore.
+    InvalidIndex(String),
+
+    /// The format of a file can't be detected from its content nor from its extension.
+    UnknownFormat(String),
+
+    /// The data given to a function can't be used, e.g. an empty streamline where at least one
+    /// point is needed.
+    InvalidInput(String),
+
+    /// A NIfTI image can't be written.
+    #[cfg(feature = "nifti_images")]
+    Nifti(nifti::NiftiError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::BadMagic => write!(f, "Wrong file signature"),
+            Error::Truncated { streamline_index, byte_offset } => write!(
+                f,
+                "File is truncated in streamline {} (starting at byte {})",
+                streamline_index, byte_offset
+            ),
+            Error::InvalidNbPoints { streamline_index, nb_points } => {
+                write!(f, "Streamline {} declares {} points", streamline_index, nb_points)
+            }
+            Error::InvalidHeader(msg) => write!(f, "Invalid header: {}", msg),
+            Error::AffineNotInvertible => write!(f, "Unable to inverse 4x4 affine matrix"),
+            Error::TooManyNames { kind, max } => {
+                write!(f, "Header is already full of {} ({})", kind, max)
+            }
+            Error::InvalidName(msg) => write!(f, "Invalid name: {}", msg),
+            Error::CountMismatch { declared, written } => {
+                write!(f, "{} streamlines were declared but {} were written", declared, written)
+            }
+            Error::InvalidIndex(msg) => write!(f, "Invalid index: {}", msg),
+            Error::UnknownFormat(path) => write!(f, "Unknown tractogram format: {}", path),
+            Error::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            #[cfg(feature = "nifti_images")]
+            Error::Nifti(e) => write!(f, "NIfTI error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            #[cfg(feature = "nifti_images")]
+            Error::Nifti(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "nifti_images")]
+impl From<nifti::NiftiError> for Error {
+    fn from(e: nifti::NiftiError) -> Error {
+        Error::Nifti(e)
+    }
+}
+
+#[cfg(feature = "trx")]
+impl From<zip::result::ZipError> for Error {
+    fn from(e: zip::result::ZipError) -> Error {
+        match e {
+            zip::result::ZipError::Io(e) => Error::Io(e),
+            e => Error::InvalidHeader(format!("Invalid zip file: {}", e)),
+        }
+    }
+}
+
+/// Add the path to an I/O error, because `io::Error` doesn't keep it.
+pub(crate) fn path_error<P: AsRef<std::path::Path>>(e: io::Error, action: &str, path: P) -> Error {
+    Error::Io(io::Error::new(e.kind(), format!("{} {:?}: {}", action, path.as_ref(), e)))
+}
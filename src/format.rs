@@ -0,0 +1,344 @@
+//! A common entry point for all supported formats: trk, tck, vtk, vtp and trx (`trx` feature).
+//!
+//! The format of an existing file is detected from its first bytes, then from its extension. The
+//! format of a new file is detected from its extension.
+//!
+//! All formats are loaded as a trk `Header` and a `Tractogram` in world space (RAS+ mm). The
+//! formats without a reference space (tck, vtk and vtp) get a default header, holding only the
+//! number of streamlines and the scalars and properties names. A tck file can't hold scalars nor
+//! properties, so they are dropped when saving one.
+//!
+//! Only trk files can be compressed, with gzip (`.trk.gz`, `gzip` feature) or zstd (`.trk.zst`,
+//! `zstd` feature).
+
+use std::{
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "trx")]
+use crate::trx::Trx;
+use crate::{
+    error::path_error,
+    vtk::{read_vtk, write_vtk, VtkFormat},
+    vtp::{read_vtp, write_vtp, VtpEncoding},
+    ArraySequence, Compression, Error, Header, Reader, Result, Stats, TckReader, TckWriter,
+    Tractogram, TractogramItem, Writer,
+};
+
+/// All formats that can be loaded and saved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TractogramFormat {
+    /// TrackVis. Streamed when reading and writing.
+    Trk,
+    /// MRtrix. Streamed when reading and writing.
+    Tck,
+    /// Legacy VTK, saved as binary.
+    Vtk,
+    /// VTK XML PolyData, saved as appended raw data.
+    Vtp,
+    /// Tractography exchange, saved as a zip file.
+    #[cfg(feature = "trx")]
+    Trx,
+}
+
+impl TractogramFormat {
+    /// Format of `path`, from its extension only.
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Option<TractogramFormat> {
+        let path = path.as_ref();
+        if Compression::from_extension(path) != Compression::None {
+            let stem = Path::new(path.file_stem()?);
+            return match TractogramFormat::from_extension(stem)? {
+                TractogramFormat::Trk => Some(TractogramFormat::Trk),
+                _ => None,
+            };
+        }
+
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "trk" => Some(TractogramFormat::Trk),
+            "tck" => Some(TractogramFormat::Tck),
+            "vtk" => Some(TractogramFormat::Vtk),
+            "vtp" => Some(TractogramFormat::Vtp),
+            #[cfg(feature = "trx")]
+            "trx" => Some(TractogramFormat::Trx),
+            _ => None,
+        }
+    }
+
+    /// Format of the existing file (or TRX directory) `path`, from its first bytes, or from its
+    /// extension if they are not recognized.
+    pub fn detect<P: AsRef<Path>>(path: P) -> Result<TractogramFormat> {
+        let path = path.as_ref();
+        #[cfg(feature = "trx")]
+        if path.is_dir() && path.join("header.json").is_file() {
+            return Ok(TractogramFormat::Trx);
+        }
+
+        let mut magic = Vec::with_capacity(64);
+        File::open(path)
+            .and_then(|f| f.take(64).read_to_end(&mut magic))
+            .map_err(|e| path_error(e, "Failed to load", path))?;
+        let format =
+            if magic.starts_with(b"TRACK") || Compression::detect(&magic) != Compression::None {
+                Some(TractogramFormat::Trk)
+            } else if magic.starts_with(b"mrtrix tracks") {
+                Some(TractogramFormat::Tck)
+            } else if magic.starts_with(b"# vtk DataFile") {
+                Some(TractogramFormat::Vtk)
+            } else if magic.starts_with(b"<?xml") || magic.starts_with(b"<VTKFile") {
+                Some(TractogramFormat::Vtp)
+            } else {
+                #[cfg(feature = "trx")]
+                if magic.starts_with(b"PK\x03\x04") {
+                    return Ok(TractogramFormat::Trx);
+                }
+                None
+            };
+        format.or_else(|| TractogramFormat::from_extension(path)).ok_or_else(|| unknown(path))
+    }
+
+    /// Load the complete file `path`.
+    pub fn load<P: AsRef<Path>>(self, path: P) -> Result<(Header, Tractogram)> {
+        match self {
+            TractogramFormat::Trk => {
+                let mut reader = Reader::new(path)?;
+                let tractogram = reader.tractogram()?;
+                Ok((reader.header, tractogram))
+            }
+            TractogramFormat::Tck => {
+                let mut reader = TckReader::new(path)?;
+                let tractogram = reader.tractogram()?;
+                Ok((default_header(tractogram.streamlines.len()), tractogram))
+            }
+            TractogramFormat::Vtk => read_vtk(path),
+            TractogramFormat::Vtp => read_vtp(path),
+            #[cfg(feature = "trx")]
+            TractogramFormat::Trx => {
+                let trx = Trx::load(path)?;
+                Ok((trx.trk_header(), trx.tractogram))
+            }
+        }
+    }
+
+    /// Save `tractogram` in the file `path`. The scalars and properties names are read from
+    /// `header`, which is also used as reference space by the trk and trx formats.
+    pub fn save<P: AsRef<Path>>(
+        self,
+        path: P,
+        tractogram: &Tractogram,
+        header: &Header,
+    ) -> Result<()> {
+        match self {
+            TractogramFormat::Trk => {
+                let mut writer = Writer::new(path, Some(header))?;
+                for item in tractogram {
+                    writer.write(item)?;
+                }
+                writer.finish()?;
+            }
+            TractogramFormat::Tck => {
+                let mut writer = TckWriter::new(path, None)?;
+                for item in tractogram {
+                    writer.write(item)?;
+                }
+                writer.finish()?;
+            }
+            TractogramFormat::Vtk => write_vtk(path, tractogram, header, VtkFormat::Binary)?,
+            TractogramFormat::Vtp => write_vtp(path, tractogram, header, VtpEncoding::AppendedRaw)?,
+            #[cfg(feature = "trx")]
+            TractogramFormat::Trx => Trx::save_trk_zip(path, header, tractogram)?,
+        }
+        Ok(())
+    }
+
+    /// Open `path` to read it one streamline at a time. The formats that can't be streamed are
+    /// loaded completely first.
+    pub fn open<P: AsRef<Path>>(self, path: P) -> Result<Box<dyn TractogramReader>> {
+        match self {
+            TractogramFormat::Trk => Ok(Box::new(Reader::new(path)?)),
+            TractogramFormat::Tck => {
+                let reader = TckReader::new(path)?;
+                let header = default_header(reader.header.nb_streamlines);
+                Ok(Box::new(HeaderReader { header, items: reader }))
+            }
+            _ => {
+                let (header, tractogram) = self.load(path)?;
+                let nb_scalars = header.scalars_name.len();
+                let items = (0..tractogram.streamlines.len()).map(move |i| {
+                    let (streamline, scalars, properties) = tractogram.item(i);
+                    let scalars = if nb_scalars == 0 {
+                        ArraySequence::empty()
+                    } else {
+                        ArraySequence::new(vec![nb_scalars; streamline.len()], scalars.to_vec())
+                    };
+                    Ok((streamline.to_vec(), scalars, properties.to_vec()))
+                });
+                Ok(Box::new(HeaderReader { header, items }))
+            }
+        }
+    }
+
+    /// Create `path` to write it one streamline at a time. The formats that can't be streamed are
+    /// kept in memory and saved by `TractogramWriter::finish`.
+    pub fn create<P: AsRef<Path>>(
+        self,
+        path: P,
+        header: &Header,
+    ) -> Result<Box<dyn TractogramWriter>> {
+        match self {
+            TractogramFormat::Trk => Ok(Box::new(Writer::new(path, Some(header))?)),
+            TractogramFormat::Tck => Ok(Box::new(TckWriter::new(path, None)?)),
+            _ => Ok(Box::new(MemoryWriter {
+                format: self,
+                path: path.as_ref().to_path_buf(),
+                header: header.clone(),
+                tractogram: Tractogram::new(
+                    ArraySequence::empty(),
+                    ArraySequence::empty(),
+                    ArraySequence::empty(),
+                ),
+            })),
+        }
+    }
+}
+
+/// Load the complete file `path`, in any supported format.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<(Header, Tractogram)> {
+    TractogramFormat::detect(&path)?.load(path)
+}
+
+/// Save `tractogram` in the file `path`, in the format given by its extension.
+pub fn save<P: AsRef<Path>>(path: P, tractogram: &Tractogram, header: &Header) -> Result<()> {
+    format_of_new_file(path.as_ref())?.save(path, tractogram, header)
+}
+
+/// Open `path`, in any supported format, to read it one streamline at a time.
+pub fn open<P: AsRef<Path>>(path: P) -> Result<Box<dyn TractogramReader>> {
+    TractogramFormat::detect(&path)?.open(path)
+}
+
+/// Create `path`, in the format given by its extension, to write it one streamline at a time.
+pub fn create<P: AsRef<Path>>(path: P, header: &Header) -> Result<Box<dyn TractogramWriter>> {
+    format_of_new_file(path.as_ref())?.create(path, header)
+}
+
+/// Streamlines, with their scalars and properties, read one at a time from any format.
+pub trait TractogramReader: Iterator<Item = Result<TractogramItem>> {
+    /// Header of the file, or a default header for the formats that don't have one.
+    fn header(&self) -> &Header;
+}
+
+/// Streamlines, with their scalars and properties, written one at a time to any format.
+pub trait TractogramWriter {
+    fn write_item(&mut self, item: TractogramItem) -> Result<()>;
+
+    /// Write everything that is still buffered and close the file.
+    fn finish(self: Box<Self>) -> Result<Stats>;
+}
+
+impl TractogramReader for Reader {
+    fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+impl TractogramWriter for Writer {
+    fn write_item(&mut self, item: TractogramItem) -> Result<()> {
+        self.write(item)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Stats> {
+        Writer::finish(*self)
+    }
+}
+
+impl TractogramWriter for TckWriter {
+    fn write_item(&mut self, item: TractogramItem) -> Result<()> {
+        self.write(item)
+    }
+
+    fn finish(self: Box<Self>) -> Result<Stats> {
+        TckWriter::finish(*self)
+    }
+}
+
+/// Any iterator of items, with a header.
+struct HeaderReader<I> {
+    header: Header,
+    items: I,
+}
+
+impl<I: Iterator<Item = Result<TractogramItem>>> Iterator for HeaderReader<I> {
+    type Item = Result<TractogramItem>;
+
+    fn next(&mut self) -> Option<Result<TractogramItem>> {
+        self.items.next()
+    }
+}
+
+impl<I: Iterator<Item = Result<TractogramItem>>> TractogramReader for HeaderReader<I> {
+    fn header(&self) -> &Header {
+        &self.header
+    }
+}
+
+/// Keeps all items in memory, then saves them on `finish`.
+struct MemoryWriter {
+    format: TractogramFormat,
+    path: PathBuf,
+    header: Header,
+    tractogram: Tractogram,
+}
+
+impl TractogramWriter for MemoryWriter {
+    fn write_item(&mut self, item: TractogramItem) -> Result<()> {
+        let (streamline, scalars, properties) = item;
+        let nb_scalars = self.header.scalars_name.len();
+        let nb_properties = self.header.properties_name.len();
+        if scalars.data.len() != streamline.len() * nb_scalars || properties.len() != nb_properties
+        {
+            return Err(Error::InvalidHeader(format!(
+                "Streamline {} has {} points, {} scalars and {} properties, but the header names \
+                 {} scalars per point and {} properties",
+                self.tractogram.streamlines.len(),
+                streamline.len(),
+                scalars.data.len(),
+                properties.len(),
+                nb_scalars,
+                nb_properties
+            )));
+        }
+
+        // Empty streamlines are kept, with empty scalars, so that everything stays aligned
+        let Tractogram { streamlines, scalars: all_scalars, properties: all_properties } =
+            &mut self.tractogram;
+        streamlines.insert(streamlines.len(), &streamline);
+        if nb_scalars > 0 {
+            all_scalars.insert(all_scalars.len(), &scalars.data);
+        }
+        all_properties.extend_from_slice(&properties);
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<Stats> {
+        self.format.save(&self.path, &self.tractogram, &self.header)?;
+        let streamlines = &self.tractogram.streamlines;
+        Ok(Stats { nb_streamlines: streamlines.len(), nb_points: streamlines.data.len() })
+    }
+}
+
+fn default_header(nb_streamlines: usize) -> Header {
+    let mut header = Header::default();
+    header.nb_streamlines = nb_streamlines;
+    header
+}
+
+fn format_of_new_file(path: &Path) -> Result<TractogramFormat> {
+    TractogramFormat::from_extension(path).ok_or_else(|| unknown(path))
+}
+
+fn unknown(path: &Path) -> Error {
+    Error::UnknownFormat(path.display().to_string())
+}
@@ -1,6 +1,8 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
 
-use anyhow::{Context, Result};
 use byteorder::WriteBytesExt;
 #[cfg(feature = "nifti_images")]
 use nifti::NiftiHeader;
@@ -8,7 +10,7 @@ use nifti::NiftiHeader;
 use crate::{
     affine::get_affine_and_translation,
     cheader::{CHeader, Endianness},
-    Affine, Affine4, Translation,
+    Affine, Affine4, Error, Result, Translation, TrkInput,
 };
 
 #[derive(Clone)]
@@ -28,6 +30,18 @@ impl Header {
     /// Build a trk header using the affine from a Nifti header.
     pub fn from_nifti(h: &NiftiHeader) -> Header {
         let c_header = CHeader::from_nifti(h.dim, h.pixdim, h.srow_x, h.srow_y, h.srow_z);
+        Header::from_c_header(c_header)
+    }
+
+    /// Build a trk header for a grid of shape `dim`, with `affine4` as voxel to RAS+ mm
+    /// transformation. The voxel size is the norm of the columns of `affine4`.
+    pub fn from_affine4(affine4: &Affine4, dim: [u16; 3]) -> Header {
+        let voxel_size = [0, 1, 2].map(|i| affine4.fixed_view::<3, 1>(0, i).norm());
+        let dim = dim.map(|d| d as i16);
+        Header::from_c_header(CHeader::from_affine4(dim, voxel_size, affine4))
+    }
+
+    fn from_c_header(c_header: CHeader) -> Header {
         let affine4 = c_header.get_affine_to_rasmm();
         let (affine, translation) = get_affine_and_translation(&affine4);
         Header {
@@ -41,12 +55,9 @@ impl Header {
         }
     }
 
-    /// Retrieve a trk header, along with its byte order, from a file in the file system.
+    /// Retrieve a trk header from a file in the file system, compressed or not.
     pub fn from_trk<P: AsRef<Path>>(path: P) -> Result<Header> {
-        let f = File::open(path.as_ref())
-            .with_context(|| format!("Failed to load {:?}", path.as_ref()))?;
-        let mut reader = BufReader::new(f);
-        let (header, _) = Self::read(&mut reader)?;
+        let (header, _) = Self::read_stream(&mut TrkInput::open(path)?)?;
         Ok(header)
     }
 
@@ -55,15 +66,21 @@ impl Header {
         self.c_header.clone()
     }
 
-    /// Retrieve a trk header, along with its byte order, from the given `BufReader`. It is assumed
-    /// that the `reader` is currently at the start of the trk header.
-    pub fn read(reader: &mut BufReader<File>) -> Result<(Header, Endianness)> {
-        let (c_header, endianness) = CHeader::read(reader)?;
+    /// Retrieve a trk header, along with its byte order, from the start of the given `reader`.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<(Header, Endianness)> {
+        reader.seek(SeekFrom::Start(0))?;
+        Header::read_stream(reader)
+    }
+
+    /// Retrieve a trk header, along with its byte order, from the given `reader`, without seeking.
+    /// It is assumed that the `reader` is currently at the start of the trk header.
+    pub fn read_stream<R: Read>(reader: &mut R) -> Result<(Header, Endianness)> {
+        let (c_header, endianness) = CHeader::read_stream(reader)?;
         let affine4 = c_header.get_affine_to_rasmm();
         let (affine, translation) = get_affine_and_translation(&affine4);
         let nb_streamlines = c_header.n_count as usize;
-        let scalars_name = c_header.get_scalars_name();
-        let properties_name = c_header.get_properties_name();
+        let scalars_name = c_header.get_scalars_name()?;
+        let properties_name = c_header.get_properties_name()?;
 
         let header = Header {
             c_header,
@@ -102,35 +119,108 @@ impl Header {
     }
 
     /// Clear all scalars from `self` and copy scalars from `rhs`.
+    ///
+    /// Consecutive identical names are saved once, with a repeat count. The names of `rhs` are
+    /// always copied, even if they don't fit in a trk header, e.g. when `rhs` comes from another
+    /// format. In that case, writing a trk file with `self` returns the error.
     pub fn copy_scalars(&mut self, rhs: &Self) {
-        self.clear_scalars();
-        for scalar in &rhs.scalars_name {
-            self.add_scalar(scalar).unwrap(); // Can't fail
-        }
+        let _ = self.set_names(rhs.scalars_name.clone(), self.properties_name.clone());
     }
 
     /// Clear all properties from `self` and copy properties from `rhs`.
+    ///
+    /// Consecutive identical names are saved once, with a repeat count. The names of `rhs` are
+    /// always copied, even if they don't fit in a trk header, e.g. when `rhs` comes from another
+    /// format. In that case, writing a trk file with `self` returns the error.
     pub fn copy_properties(&mut self, rhs: &Self) {
-        self.clear_properties();
-        for property in &rhs.properties_name {
-            self.add_property(property).unwrap(); // Can't fail
+        let _ = self.set_names(self.scalars_name.clone(), rhs.properties_name.clone());
+    }
+
+    /// Replace all scalars and properties names, without the limits of the trk header.
+    ///
+    /// Other formats can hold more names, or longer names, than a trk header. They are always
+    /// kept in `scalars_name` and `properties_name`, but an error is returned if they don't fit in
+    /// a trk header. Writing a trk file with such a header will return the same error.
+    pub(crate) fn set_names(
+        &mut self,
+        scalars_name: Vec<String>,
+        properties_name: Vec<String>,
+    ) -> Result<()> {
+        self.scalars_name = scalars_name;
+        self.properties_name = properties_name;
+        match self.c_header_with_names() {
+            Ok(c_header) => {
+                self.c_header = c_header;
+                Ok(())
+            }
+            Err(e) => {
+                self.c_header.clear_scalars();
+                self.c_header.clear_properties();
+                Err(e)
+            }
+        }
+    }
+
+    /// The trk header, with the current scalars and properties names. The names that were read
+    /// are kept as-is, if they haven't been modified.
+    ///
+    /// Returns an error if the names don't fit in a trk header.
+    fn c_header_with_names(&self) -> Result<CHeader> {
+        let mut c_header = self.c_header.clone();
+        if c_header.get_scalars_name().ok().as_ref() != Some(&self.scalars_name) {
+            c_header.clear_scalars();
+            for names in self.scalars_name.chunk_by(|a, b| a == b) {
+                c_header.add_scalar_array(&names[0], names.len())?;
+            }
+        }
+        if c_header.get_properties_name().ok().as_ref() != Some(&self.properties_name) {
+            c_header.clear_properties();
+            for names in self.properties_name.chunk_by(|a, b| a == b) {
+                c_header.add_property_array(&names[0], names.len())?;
+            }
         }
+        Ok(c_header)
     }
 
     pub fn add_scalar(&mut self, name: &str) -> Result<()> {
-        self.c_header.add_scalar(name)?;
-        self.scalars_name.push(name.to_string());
+        self.add_scalar_array(name, 1)
+    }
+
+    /// Add a scalar of `nb_components` values per point, e.g. `colors` with 3 components.
+    ///
+    /// The name is repeated `nb_components` times in `scalars_name`, but the trk header saves it
+    /// only once, with a repeat count.
+    pub fn add_scalar_array(&mut self, name: &str, nb_components: usize) -> Result<()> {
+        self.c_header.add_scalar_array(name, nb_components)?;
+        self.scalars_name.extend(std::iter::repeat_n(name.to_string(), nb_components));
         Ok(())
     }
 
     pub fn add_property(&mut self, name: &str) -> Result<()> {
-        self.c_header.add_property(name)?;
-        self.properties_name.push(name.to_string());
+        self.add_property_array(name, 1)
+    }
+
+    /// Add a property of `nb_components` values per streamline, as in `add_scalar_array`.
+    pub fn add_property_array(&mut self, name: &str, nb_components: usize) -> Result<()> {
+        self.c_header.add_property_array(name, nb_components)?;
+        self.properties_name.extend(std::iter::repeat_n(name.to_string(), nb_components));
         Ok(())
     }
 
+    /// Write the trk header. Returns an error if the scalars or properties names don't fit in it.
     pub fn write<W: WriteBytesExt>(&self, writer: &mut W) -> Result<()> {
-        Ok(self.c_header.write(writer)?)
+        self.c_header_with_names()?.write(writer)
+    }
+
+    /// Write the header with `n_count` instead of the value that was read.
+    pub(crate) fn write_with_n_count<W: WriteBytesExt>(
+        &self,
+        writer: &mut W,
+        n_count: i32,
+    ) -> Result<()> {
+        let mut c_header = self.c_header_with_names()?;
+        c_header.n_count = n_count;
+        c_header.write(writer)
     }
 }
 
@@ -157,3 +247,30 @@ impl PartialEq for Header {
             && self.properties_name == other.properties_name
     }
 }
+
+/// Group the consecutive identical names, as `(name, first column, number of columns)`.
+///
+/// Returns an error if a name is used by non-consecutive columns.
+pub(crate) fn split_names(names: &[String]) -> Result<Vec<(&str, usize, usize)>> {
+    let mut groups: Vec<(&str, usize, usize)> = vec![];
+    for (i, name) in names.iter().enumerate() {
+        if groups.last().is_some_and(|(last, _, _)| last == name) {
+            groups.last_mut().unwrap().2 += 1;
+        } else if groups.iter().any(|(n, _, _)| n == name) {
+            return Err(Error::InvalidName(format!("{:?} is used twice", name)));
+        } else {
+            groups.push((name, i, 1));
+        }
+    }
+    Ok(groups)
+}
+
+/// Values of `dim` columns, starting at `start`, of a row-major array of `nb_columns` columns.
+pub(crate) fn select_columns(
+    data: &[f32],
+    nb_columns: usize,
+    start: usize,
+    dim: usize,
+) -> impl Iterator<Item = f32> + '_ {
+    data.chunks_exact(nb_columns).flat_map(move |row| row[start..start + dim].iter().cloned())
+}
@@ -1,23 +1,58 @@
 pub mod affine;
 mod array_sequence;
 mod cheader;
+pub mod cluster;
+pub mod compress;
+mod compression;
+#[cfg(feature = "nifti_images")]
+pub mod connectivity;
+mod data;
+pub mod distance;
+mod error;
+pub mod format;
 mod header;
+pub mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap_tractogram;
 pub mod orientation;
 mod reader;
+pub mod reorient;
+pub mod resample;
+#[cfg(feature = "nifti_images")]
+pub mod roi;
+mod tck_header;
+mod tck_reader;
+mod tck_writer;
+#[cfg(feature = "nifti_images")]
+pub mod tdi;
 mod tractogram;
+mod trk_index;
+#[cfg(feature = "trx")]
+pub mod trx;
 mod vs_reader;
+pub mod vtk;
+pub mod vtp;
 mod writer;
 
 use byteorder::LittleEndian;
 use nalgebra::{Matrix3, Matrix4, Vector3};
 
-pub use array_sequence::ArraySequence;
-pub use cheader::CHeader;
+pub use array_sequence::{ArraySequence, ArraySequenceIterator, ArraySequenceView};
+pub use cheader::{CHeader, Endianness};
+pub use compression::{Compression, TrkInput, TrkOutput};
+pub use data::{DataArray, DataPerPoint, DataPerStreamline};
+pub use error::{Error, Result};
 pub use header::Header;
+#[cfg(feature = "mmap")]
+pub use mmap_tractogram::MmapTractogram;
 pub use reader::{Reader, StreamlinesIter};
-pub use tractogram::{Point, Points, Streamlines, Tractogram, TractogramItem};
+pub use tck_header::{TckDataType, TckHeader};
+pub use tck_reader::{TckReader, TckStreamlinesIter};
+pub use tck_writer::TckWriter;
+pub use tractogram::{Point, Points, Streamlines, Tractogram, TractogramItem, TractogramView};
+pub use trk_index::TrkIndex;
 pub use vs_reader::VoxelSpaceReader;
-pub use writer::Writer;
+pub use writer::{Stats, Writer};
 
 pub type Affine = Matrix3<f32>;
 pub type Affine4 = Matrix4<f32>;
@@ -0,0 +1,175 @@
+//! Geometric measures of streamlines.
+//!
+//! All measures are computed in the space of the points, so they are in mm (or 1/mm) when the
+//! streamlines are read in world space, which is the default.
+
+use crate::{ArraySequence, Error, Header, Point, Result, Streamlines, Tractogram};
+
+/// A per-streamline measure that can be computed and saved as a property.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Arc length, see `length`.
+    Length,
+    /// Distance between the first and last points, see `endpoint_distance`.
+    EndpointDistance,
+    /// Arc length divided by the endpoint distance, see `tortuosity`.
+    Tortuosity,
+    /// Mean of the discrete curvature at all inner points, see `curvatures`.
+    MeanCurvature,
+    /// Maximum of the discrete curvature at all inner points, see `curvatures`.
+    MaxCurvature,
+    /// Mean of the discrete torsion of all inner segments, see `torsions`.
+    MeanTorsion,
+}
+
+impl Metric {
+    /// Name used when the metric is saved as a property.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Length => "length",
+            Metric::EndpointDistance => "endpoint_distance",
+            Metric::Tortuosity => "tortuosity",
+            Metric::MeanCurvature => "mean_curvature",
+            Metric::MaxCurvature => "max_curvature",
+            Metric::MeanTorsion => "mean_torsion",
+        }
+    }
+
+    /// Compute the metric on a single streamline.
+    pub fn compute(&self, streamline: &[Point]) -> f32 {
+        match self {
+            Metric::Length => length(streamline),
+            Metric::EndpointDistance => endpoint_distance(streamline),
+            Metric::Tortuosity => tortuosity(streamline),
+            Metric::MeanCurvature => mean(&curvatures(streamline)),
+            Metric::MaxCurvature => curvatures(streamline).into_iter().fold(0.0, f32::max),
+            Metric::MeanTorsion => mean(&torsions(streamline)),
+        }
+    }
+
+    /// Compute the metric on all streamlines.
+    pub fn compute_all(&self, streamlines: &Streamlines) -> Vec<f32> {
+        streamlines.iter().map(|streamline| self.compute(streamline)).collect()
+    }
+}
+
+/// Compute `metrics` on all streamlines of `tractogram` and save them as new properties, after
+/// the existing ones. The property names are added to `header`.
+///
+/// Returns an error, without modifying anything, if `header` can't hold all the new names, or if
+/// the streamlines of `tractogram` don't have one property per name of `header`.
+pub fn add_metrics_as_properties(
+    header: &mut Header,
+    tractogram: &mut Tractogram,
+    metrics: &[Metric],
+) -> Result<()> {
+    let nb_streamlines = tractogram.streamlines.len();
+    let nb_old_properties = header.properties_name.len();
+    if !tractogram.properties.is_empty() && tractogram.properties.len() != nb_streamlines {
+        return Err(Error::InvalidInput(format!(
+            "{} streamlines but {} rows of properties",
+            nb_streamlines,
+            tractogram.properties.len()
+        )));
+    }
+    for (i, (_, _, properties)) in tractogram.into_iter().enumerate() {
+        if properties.len() != nb_old_properties {
+            return Err(Error::InvalidInput(format!(
+                "Streamline {} has {} properties but the header names {}",
+                i,
+                properties.len(),
+                nb_old_properties
+            )));
+        }
+    }
+
+    let mut new_header = header.clone();
+    for metric in metrics {
+        new_header.add_property(metric.name())?;
+    }
+    *header = new_header;
+
+    let nb_properties = nb_old_properties + metrics.len();
+    let mut properties = Vec::with_capacity(nb_streamlines * nb_properties);
+    for (streamline, _, old_properties) in &*tractogram {
+        properties.extend_from_slice(old_properties);
+        properties.extend(metrics.iter().map(|metric| metric.compute(streamline)));
+    }
+    let lengths = if nb_properties == 0 { vec![] } else { vec![nb_properties; nb_streamlines] };
+    tractogram.properties = ArraySequence::new(lengths, properties);
+    Ok(())
+}
+
+/// Arc length, i.e. the sum of the length of all segments.
+pub fn length(streamline: &[Point]) -> f32 {
+    streamline.windows(2).map(|w| (w[1] - w[0]).norm()).sum()
+}
+
+/// Euclidean distance between the first and last points. 0.0 for an empty streamline.
+pub fn endpoint_distance(streamline: &[Point]) -> f32 {
+    match (streamline.first(), streamline.last()) {
+        (Some(first), Some(last)) => (last - first).norm(),
+        _ => 0.0,
+    }
+}
+
+/// Arc length divided by the endpoint distance. 1.0 for a straight line, and higher for more
+/// winding streamlines.
+///
+/// 1.0 for a streamline with a length of 0 and `f32::INFINITY` for a loop.
+pub fn tortuosity(streamline: &[Point]) -> f32 {
+    let length = length(streamline);
+    if length == 0.0 {
+        1.0
+    } else {
+        length / endpoint_distance(streamline)
+    }
+}
+
+/// Discrete (Menger) curvature at all inner points, i.e. the inverse of the radius of the circle
+/// passing through each point and its two neighbors.
+///
+/// Returns `len() - 2` values, or nothing if there are less than 3 points.
+pub fn curvatures(streamline: &[Point]) -> Vec<f32> {
+    streamline
+        .windows(3)
+        .map(|w| {
+            let (a, b, c) = (w[1] - w[0], w[2] - w[1], w[2] - w[0]);
+            let denominator = a.norm() * b.norm() * c.norm();
+            if denominator == 0.0 {
+                0.0
+            } else {
+                // 4 * triangle area / product of the sides. |a x b| is twice the area.
+                2.0 * a.cross(&b).norm() / denominator
+            }
+        })
+        .collect()
+}
+
+/// Discrete torsion of all inner segments, i.e. the angle between the osculating planes at both
+/// ends of a segment, divided by its length. The sign tells on which side the streamline twists.
+///
+/// Returns `len() - 3` values, or nothing if there are less than 4 points.
+pub fn torsions(streamline: &[Point]) -> Vec<f32> {
+    streamline
+        .windows(4)
+        .map(|w| {
+            let (a, b, c) = (w[1] - w[0], w[2] - w[1], w[3] - w[2]);
+            let (n1, n2) = (a.cross(&b), b.cross(&c));
+            let segment_length = b.norm();
+            if n1.norm() == 0.0 || n2.norm() == 0.0 || segment_length == 0.0 {
+                return 0.0;
+            }
+            let angle = n1.cross(&n2).dot(&b).atan2(n1.dot(&n2) * segment_length);
+            angle / segment_length
+        })
+        .collect()
+}
+
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+}
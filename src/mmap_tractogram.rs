@@ -0,0 +1,256 @@
+use std::{borrow::Cow, fs::File, path::Path};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use memmap2::Mmap;
+
+use crate::{
+    cheader::{Endianness, HEADER_SIZE},
+    error::path_error,
+    tractogram::{Point, Points, Streamlines, Tractogram, TractogramItem},
+    ArraySequence, Error, Header, Result,
+};
+
+/// Memory-mapped TrackVis file.
+///
+/// Opening a file only scans it once to find where each streamline starts; nothing is copied.
+/// Points are decoded, and transformed to RAS+ mm, only when a streamline is accessed. When the
+/// file is little-endian and has no scalars, `raw_points` gives direct access to the points
+/// stored on disk.
+///
+/// The file must not be modified while it is mapped.
+pub struct MmapTractogram {
+    mmap: Mmap,
+    endianness: Endianness,
+    pub header: Header,
+
+    /// Offsets of the streamlines, in points, as in `ArraySequence`. The last offset is the total
+    /// number of points.
+    offsets: Vec<usize>,
+    /// Byte position of the first point of each streamline.
+    positions: Vec<usize>,
+    floats_per_point: usize,
+}
+
+impl MmapTractogram {
+    /// Map a TrackVis file in memory and find all its streamlines.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<MmapTractogram> {
+        let mut f =
+            File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let (header, endianness) = Header::read(&mut f)?;
+
+        // SAFETY: The mapping is read-only. It's documented that the file must not be modified
+        // while it's mapped, like all memory-mapped APIs.
+        let mmap = unsafe { Mmap::map(&f) }.map_err(|e| path_error(e, "Failed to map", &path))?;
+
+        let mut tractogram = MmapTractogram {
+            mmap,
+            endianness,
+            floats_per_point: 3 + header.scalars_name.len(),
+            header,
+            offsets: vec![],
+            positions: vec![],
+        };
+        match endianness {
+            Endianness::Little => tractogram.scan::<LittleEndian>()?,
+            Endianness::Big => tractogram.scan::<BigEndian>()?,
+        }
+        Ok(tractogram)
+    }
+
+    /// Read all `nb_points` fields to know where each streamline starts.
+    fn scan<E: ByteOrder>(&mut self) -> Result<()> {
+        // `n_count` is allowed to be 0 (unknown), but when it's not, it's a good reservation. A
+        // streamline takes at least 4 bytes, whatever `n_count` says.
+        let capacity = self.header.nb_streamlines.min(self.mmap.len() / 4);
+        self.offsets = Vec::with_capacity(capacity + 1);
+        self.positions = Vec::with_capacity(capacity);
+        self.offsets.push(0);
+
+        let nb_properties = self.header.properties_name.len();
+        let bytes = &self.mmap[..];
+        let mut position = HEADER_SIZE;
+        while position < bytes.len() {
+            let streamline_index = self.positions.len();
+            let truncated = Error::Truncated { streamline_index, byte_offset: position as u64 };
+            if bytes.len() - position < 4 {
+                return Err(truncated);
+            }
+
+            let nb_points = E::read_i32(&bytes[position..]);
+            if nb_points < 0 {
+                return Err(Error::InvalidNbPoints { streamline_index, nb_points });
+            }
+            let nb_points = nb_points as usize;
+            let nb_floats = nb_points * self.floats_per_point + nb_properties;
+            let end = position + 4 + 4 * nb_floats;
+            if end > bytes.len() {
+                return Err(truncated);
+            }
+
+            self.positions.push(position + 4);
+            self.offsets.push(self.offsets.last().unwrap() + nb_points);
+            position = end;
+        }
+        Ok(())
+    }
+
+    /// Number of streamlines.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Total number of points.
+    pub fn nb_points(&self) -> usize {
+        *self.offsets.last().unwrap()
+    }
+
+    /// Offsets of the streamlines, in points, as in `ArraySequence`.
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    /// Number of points of streamline `i`.
+    pub fn length_of_streamline(&self, i: usize) -> usize {
+        self.offsets[i + 1] - self.offsets[i]
+    }
+
+    /// Returns `true` if the points can be viewed without any copy, i.e. if the file and the
+    /// machine are little-endian and the file has no scalars.
+    pub fn is_zero_copy(&self) -> bool {
+        cfg!(target_endian = "little")
+            && self.endianness == Endianness::Little
+            && self.floats_per_point == 3
+    }
+
+    /// Points of streamline `i`, as stored on disk (in voxmm space), without any copy.
+    ///
+    /// Returns `None` if `is_zero_copy` is `false`. Use `raw_streamline` in this case.
+    pub fn raw_points(&self, i: usize) -> Option<&[Point]> {
+        if !self.is_zero_copy() {
+            return None;
+        }
+
+        let bytes = self.points_bytes(i);
+        let ptr = bytes.as_ptr();
+        assert_eq!(ptr.align_offset(std::mem::align_of::<Point>()), 0);
+        // SAFETY: `Point` is a `repr(C)` struct of 3 `f32`, so it has no padding and any bit
+        // pattern is valid. The file and the machine are both little-endian, the pointer is
+        // aligned (the mapping is page-aligned and each streamline starts on a multiple of 4) and
+        // `scan` has checked that the streamline is entirely in the file.
+        let points = unsafe {
+            std::slice::from_raw_parts(ptr as *const Point, self.length_of_streamline(i))
+        };
+        Some(points)
+    }
+
+    /// Points of streamline `i`, as stored on disk (in voxmm space). Borrowed if possible.
+    pub fn raw_streamline(&self, i: usize) -> Cow<'_, [Point]> {
+        match self.raw_points(i) {
+            Some(points) => Cow::Borrowed(points),
+            None => Cow::Owned(self.decode_points(i, false)),
+        }
+    }
+
+    /// Points of streamline `i` in world space (RAS+ mm).
+    pub fn streamline(&self, i: usize) -> Points {
+        self.decode_points(i, true)
+    }
+
+    /// Scalars of streamline `i`, chunked by the number of scalars.
+    pub fn scalars(&self, i: usize) -> Vec<f32> {
+        let nb_scalars = self.floats_per_point - 3;
+        let mut scalars = Vec::with_capacity(self.length_of_streamline(i) * nb_scalars);
+        if nb_scalars > 0 {
+            let floats = self.floats(self.points_bytes(i));
+            for point in floats.chunks(self.floats_per_point) {
+                scalars.extend_from_slice(&point[3..]);
+            }
+        }
+        scalars
+    }
+
+    /// Properties of streamline `i`.
+    pub fn properties(&self, i: usize) -> Vec<f32> {
+        let start = self.positions[i] + 4 * self.length_of_streamline(i) * self.floats_per_point;
+        let end = start + 4 * self.header.properties_name.len();
+        self.floats(&self.mmap[start..end])
+    }
+
+    /// Streamline `i` in world space (RAS+ mm), with its scalars and properties.
+    pub fn item(&self, i: usize) -> TractogramItem {
+        let mut scalars = ArraySequence::empty();
+        scalars.extend(self.scalars(i));
+        (self.streamline(i), scalars, self.properties(i))
+    }
+
+    /// Iterate on all streamlines in world space (RAS+ mm).
+    pub fn streamlines_iter(&self) -> impl Iterator<Item = Points> + '_ {
+        (0..self.len()).map(move |i| self.streamline(i))
+    }
+
+    /// Copy the complete tractogram in memory, in world space (RAS+ mm).
+    ///
+    /// Contrary to `Reader::tractogram`, all sizes are known so all allocations are done once.
+    pub fn tractogram(&self) -> Tractogram {
+        let nb_scalars = self.floats_per_point - 3;
+        let nb_properties = self.header.properties_name.len();
+
+        let mut lengths = Vec::with_capacity(self.len());
+        let mut points = Vec::with_capacity(self.nb_points());
+        let mut scalars = Vec::with_capacity(self.nb_points() * nb_scalars);
+        let mut properties = Vec::with_capacity(self.len() * nb_properties);
+        for i in 0..self.len() {
+            lengths.push(self.length_of_streamline(i));
+            points.extend(self.decode_points(i, true));
+            scalars.extend(self.scalars(i));
+            properties.extend(self.properties(i));
+        }
+
+        let scalars = if nb_scalars == 0 {
+            ArraySequence::empty()
+        } else {
+            ArraySequence::new(lengths.iter().map(|l| l * nb_scalars).collect(), scalars)
+        };
+        let properties = if nb_properties == 0 {
+            ArraySequence::empty()
+        } else {
+            ArraySequence::new(vec![nb_properties; self.len()], properties)
+        };
+        Tractogram::new(Streamlines::new(lengths, points), scalars, properties)
+    }
+
+    /// Bytes of all points and scalars of streamline `i`.
+    fn points_bytes(&self, i: usize) -> &[u8] {
+        let start = self.positions[i];
+        let end = start + 4 * self.length_of_streamline(i) * self.floats_per_point;
+        &self.mmap[start..end]
+    }
+
+    fn floats(&self, bytes: &[u8]) -> Vec<f32> {
+        let mut floats = vec![0.0; bytes.len() / 4];
+        match self.endianness {
+            Endianness::Little => LittleEndian::read_f32_into(bytes, &mut floats),
+            Endianness::Big => BigEndian::read_f32_into(bytes, &mut floats),
+        }
+        floats
+    }
+
+    fn decode_points(&self, i: usize, to_rasmm: bool) -> Points {
+        let floats = self.floats(self.points_bytes(i));
+        floats
+            .chunks(self.floats_per_point)
+            .map(|floats| {
+                let p = Point::new(floats[0], floats[1], floats[2]);
+                if to_rasmm {
+                    (self.header.affine_to_rasmm * p) + self.header.translation
+                } else {
+                    p
+                }
+            })
+            .collect()
+    }
+}
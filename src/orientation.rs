@@ -110,7 +110,7 @@ pub fn io_orientations(affine: &Affine) -> Orientations {
     let mut r = u * v_t;
 
     let mut orientations = [(0, Direction::Normal), (0, Direction::Normal), (0, Direction::Normal)];
-    for c in 0..3 {
+    for (c, orientation) in orientations.iter_mut().enumerate() {
         let mut argmax = 0;
         let mut max = 0.0;
         let mut sign_max = 0.0;
@@ -124,9 +124,9 @@ pub fn io_orientations(affine: &Affine) -> Orientations {
         }
 
         if sign_max >= 0.0 {
-            orientations[c] = (argmax, Direction::Normal);
+            *orientation = (argmax, Direction::Normal);
         } else {
-            orientations[c] = (argmax, Direction::Reversed);
+            *orientation = (argmax, Direction::Reversed);
         }
 
         // Remove the identified axis from further consideration, by zeroing
@@ -183,10 +183,8 @@ pub fn orientations_transform(
     end_orientations: &Orientations,
 ) -> Orientations {
     let mut result = [(0, Direction::Normal), (0, Direction::Normal), (0, Direction::Normal)];
-    for (end_in_idx, &(ref end_out_idx, ref end_flip)) in end_orientations.iter().enumerate() {
-        for (start_in_idx, &(ref start_out_idx, ref start_flip)) in
-            start_orientations.iter().enumerate()
-        {
+    for (end_in_idx, (end_out_idx, end_flip)) in end_orientations.iter().enumerate() {
+        for (start_in_idx, (start_out_idx, start_flip)) in start_orientations.iter().enumerate() {
             if end_out_idx == start_out_idx {
                 if start_flip == end_flip {
                     result[start_in_idx] = (end_in_idx, Direction::Normal)
@@ -1,17 +1,28 @@
-use std::{fs::File, io::BufReader, path::Path};
+use std::{
+    io::{self, ErrorKind, Read, Seek, SeekFrom},
+    ops::Range,
+    path::Path,
+};
 
-use anyhow::{Context, Result};
 use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
 use nalgebra::Vector3;
 
 use crate::{
-    cheader::Endianness,
+    cheader::{Endianness, HEADER_SIZE},
     tractogram::{Point, Points, Streamlines, Tractogram, TractogramItem},
-    Affine, ArraySequence, Header, Spacing, Translation, Writer,
+    Affine, ArraySequence, Error, Header, Result, Spacing, Translation, TrkIndex, TrkInput, Writer,
 };
 
-pub struct Reader {
-    reader: BufReader<File>,
+/// Maximal number of floats read at once. Also bounds what is preallocated from a count read in
+/// the file, which may be corrupted.
+const READ_CHUNK: usize = 1 << 16;
+
+/// Reader for TrackVis files.
+///
+/// By default, a `Reader` reads from a file, but it can read from any `Read` source using
+/// `from_reader` or `from_stream`.
+pub struct Reader<R: Read = TrkInput> {
+    reader: R,
     endianness: Endianness,
     pub header: Header,
 
@@ -20,24 +31,140 @@ pub struct Reader {
 
     floats_per_point: usize,
     buffer: Vec<f32>,
+
+    /// Index and position in the file of the next streamline to read. Only used to report errors.
+    streamline_index: usize,
+    byte_offset: u64,
+    /// `true` when an error has been returned. Iterators will not try to read anything else.
+    failed: bool,
 }
 
 impl Reader {
     /// Create an object to read all points of a TrackVis file in world space.
     ///
     /// Will also read the scalars and properties, if requested.
+    ///
+    /// gzip and zstd compressed files are decompressed on the fly (`gzip` and `zstd` features).
+    /// They can be read, but not seeked.
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Reader> {
-        let f = File::open(path.as_ref())
-            .with_context(|| format!("Failed to load {:?}", path.as_ref()))?;
-        let mut reader = BufReader::new(f);
-        let (header, endianness) = Header::read(&mut reader)?;
+        Reader::from_stream(TrkInput::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> Reader<R> {
+    /// Create an object to read all points of TrackVis data in world space from any seekable
+    /// source, e.g. a `Cursor<Vec<u8>>`. The header is read from the start of `reader`.
+    ///
+    /// `reader` is used as-is, so it should be buffered if reading from it is slow.
+    pub fn from_reader(mut reader: R) -> Result<Reader<R>> {
+        reader.seek(SeekFrom::Start(0))?;
+        Reader::from_stream(reader)
+    }
+
+    /// Move to streamline `i`, so that the next read item is streamline `i`.
+    ///
+    /// `index` must have been built from the same trk file. Panics if `i > index.len()`.
+    pub fn seek_streamline(&mut self, index: &TrkIndex, i: usize) -> Result<()> {
+        let position = index.position(i);
+        self.reader.seek(SeekFrom::Start(position))?;
+        self.streamline_index = i;
+        self.byte_offset = position;
+        self.failed = false;
+        Ok(())
+    }
+
+    /// Read streamline `i`, with its scalars and properties.
+    ///
+    /// Panics if `i >= index.len()`.
+    pub fn item_at(&mut self, index: &TrkIndex, i: usize) -> Result<TractogramItem> {
+        assert!(i < index.len(), "Streamline {} is out of bounds ({})", i, index.len());
+        self.seek_streamline(index, i)?;
+        self.next_item()?.ok_or_else(|| self.truncated(ErrorKind::UnexpectedEof.into()))
+    }
+
+    /// Read streamlines `range.start` up to `range.end` (excluded) as a `Tractogram`.
+    ///
+    /// Panics if `range.end > index.len()`.
+    pub fn tractogram_range(
+        &mut self,
+        index: &TrkIndex,
+        range: Range<usize>,
+    ) -> Result<Tractogram> {
+        assert!(
+            range.end <= index.len(),
+            "Range end {} is out of bounds ({})",
+            range.end,
+            index.len()
+        );
+        self.tractogram_at(index, range)
+    }
+
+    /// Read the requested streamlines as a `Tractogram`, in the order of `indices`.
+    ///
+    /// A seek is only done when the next index isn't the next streamline in the file, so sorted
+    /// indices are much faster to read. Panics if an index is out of bounds.
+    pub fn tractogram_indices(
+        &mut self,
+        index: &TrkIndex,
+        indices: &[usize],
+    ) -> Result<Tractogram> {
+        if let Some(&i) = indices.iter().find(|&&i| i >= index.len()) {
+            panic!("Streamline {} is out of bounds ({})", i, index.len());
+        }
+        self.tractogram_at(index, indices.iter().cloned())
+    }
+
+    fn tractogram_at<I>(&mut self, index: &TrkIndex, indices: I) -> Result<Tractogram>
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut lengths = Vec::new();
+        let mut v = Vec::with_capacity(300);
+        let mut scalars = ArraySequence::with_capacity(300);
+        let mut properties = ArraySequence::with_capacity(300);
+        for i in indices {
+            if i != self.streamline_index || self.failed {
+                self.seek_streamline(index, i)?;
+            }
+            let (streamline, streamline_scalars, streamline_properties) =
+                self.next_item()?.ok_or_else(|| self.truncated(ErrorKind::UnexpectedEof.into()))?;
+            lengths.push(streamline.len());
+            v.extend(streamline);
+            scalars.data.extend(streamline_scalars.data);
+            self.end_scalars(&mut scalars);
+            properties.extend(streamline_properties);
+            properties.end_push();
+        }
+        Ok(Tractogram::new(Streamlines::new(lengths, v), scalars, properties))
+    }
+}
+
+impl<R: Read> Reader<R> {
+    /// Create an object to read all points of TrackVis data in world space from a source that
+    /// can't seek, e.g. stdin or a decompressor. It is assumed that `reader` is currently at the
+    /// start of the trk header.
+    ///
+    /// `reader` is used as-is, so it should be buffered if reading from it is slow.
+    pub fn from_stream(mut reader: R) -> Result<Reader<R>> {
+        let (header, endianness) = Header::read_stream(&mut reader)?;
         let floats_per_point = 3 + header.scalars_name.len();
         let buffer = Vec::with_capacity(300);
 
         let raw = false;
         let voxel_space = None;
 
-        Ok(Reader { reader, endianness, header, raw, voxel_space, floats_per_point, buffer })
+        Ok(Reader {
+            reader,
+            endianness,
+            header,
+            raw,
+            voxel_space,
+            floats_per_point,
+            buffer,
+            streamline_index: 0,
+            byte_offset: HEADER_SIZE as u64,
+            failed: false,
+        })
     }
 
     /// Modify the affine in order to read all streamlines in voxel space.
@@ -81,12 +208,12 @@ impl Reader {
     }
 
     /// Iterate only on streamlines (`Vec<Point>`), ignoring scalars and properties.
-    pub fn into_streamlines_iter(self) -> StreamlinesIter {
+    pub fn into_streamlines_iter(self) -> StreamlinesIter<R> {
         StreamlinesIter { reader: self }
     }
 
     /// Read the complete tractogram, that is, all points, scalars and properties, if any.
-    pub fn tractogram(&mut self) -> Tractogram {
+    pub fn tractogram(&mut self) -> Result<Tractogram> {
         match self.endianness {
             Endianness::Little => self.read_tractogram_::<LittleEndian>(),
             Endianness::Big => self.read_tractogram_::<BigEndian>(),
@@ -94,40 +221,42 @@ impl Reader {
     }
 
     /// Read all points, ignoring the scalars and properties.
-    pub fn streamlines(&mut self) -> Streamlines {
+    pub fn streamlines(&mut self) -> Result<Streamlines> {
         match self.endianness {
             Endianness::Little => self.read_points_::<LittleEndian>(),
             Endianness::Big => self.read_points_::<BigEndian>(),
         }
     }
 
-    fn read_tractogram_<E: ByteOrder>(&mut self) -> Tractogram {
+    fn read_tractogram_<E: ByteOrder>(&mut self) -> Result<Tractogram> {
         // TODO Anything we can do to reserve?
         let mut lengths = Vec::new();
         let mut v = Vec::with_capacity(300);
         let mut scalars = ArraySequence::with_capacity(300);
         let mut properties = ArraySequence::with_capacity(300);
-        while let Ok(nb_points) = self.reader.read_i32::<E>() {
-            lengths.push(nb_points as usize);
-            self.read_streamline::<E>(&mut v, &mut scalars, nb_points as usize);
-            self.read_properties_to_arr::<E>(&mut properties);
+        while let Some(nb_points) = self.read_nb_points_::<E>()? {
+            lengths.push(nb_points);
+            self.read_streamline::<E>(&mut v, &mut scalars, nb_points)?;
+            self.read_properties_to_arr::<E>(&mut properties)?;
+            self.end_streamline(nb_points);
         }
 
         self.buffer = vec![];
-        Tractogram::new(Streamlines::new(lengths, v), scalars, properties)
+        Ok(Tractogram::new(Streamlines::new(lengths, v), scalars, properties))
     }
 
-    fn read_points_<E: ByteOrder>(&mut self) -> Streamlines {
+    fn read_points_<E: ByteOrder>(&mut self) -> Result<Streamlines> {
         // TODO Anything we can do to reserve?
         let mut lengths = Vec::new();
         let mut v = Vec::with_capacity(300);
-        while let Ok(nb_points) = self.reader.read_i32::<E>() {
-            lengths.push(nb_points as usize);
-            self.read_streamline_fast::<E>(&mut v, nb_points as usize);
+        while let Some(nb_points) = self.read_nb_points_::<E>()? {
+            lengths.push(nb_points);
+            self.read_streamline_fast::<E>(&mut v, nb_points)?;
+            self.end_streamline(nb_points);
         }
 
         self.buffer = vec![];
-        Streamlines::new(lengths, v)
+        Ok(Streamlines::new(lengths, v))
     }
 
     fn read_streamline<E: ByteOrder>(
@@ -135,20 +264,32 @@ impl Reader {
         points: &mut Points,
         scalars: &mut ArraySequence<f32>,
         nb_points: usize,
-    ) {
-        self.read_floats::<E>(nb_points);
+    ) -> Result<()> {
+        self.read_floats::<E>(nb_points)?;
         for floats in self.buffer.chunks(self.floats_per_point) {
             self.add_points(points, floats);
-            for f in &floats[3..] {
-                scalars.push(*f);
-            }
+            scalars.data.extend_from_slice(&floats[3..]);
+        }
+        self.end_scalars(scalars);
+        Ok(())
+    }
+
+    /// End the scalars of a streamline. Contrary to `ArraySequence::end_push`, an empty streamline
+    /// gets an empty array, so that the scalars stay aligned with the streamlines. Does nothing if
+    /// there are no scalars at all.
+    fn end_scalars(&self, scalars: &mut ArraySequence<f32>) {
+        if self.floats_per_point > 3 {
+            scalars.offsets.push(scalars.data.len());
         }
-        scalars.end_push();
     }
 
     /// Ignore the scalars and properties.
-    fn read_streamline_fast<E: ByteOrder>(&mut self, points: &mut Points, nb_points: usize) {
-        self.read_floats::<E>(nb_points);
+    fn read_streamline_fast<E: ByteOrder>(
+        &mut self,
+        points: &mut Points,
+        nb_points: usize,
+    ) -> Result<()> {
+        self.read_floats::<E>(nb_points)?;
         for floats in self.buffer.chunks(self.floats_per_point) {
             self.add_points(points, floats);
             // Scalars have been read in `floats`, but we do not save them
@@ -156,19 +297,29 @@ impl Reader {
 
         // Properties must be read to advance the cursor, but we do not save them
         for _ in 0..self.header.properties_name.len() {
-            self.reader.read_f32::<E>().unwrap();
+            self.reader.read_f32::<E>().map_err(|e| self.truncated(e))?;
         }
+        Ok(())
     }
 
     /// Read all points and scalars for the current streamline.
     ///
     /// Simply chunk the result by `nb_floats_per_point` to get the 3D point and the scalars.
-    fn read_floats<E: ByteOrder>(&mut self, nb_points: usize) {
-        // Vec::resize never decreases capacity, it can only increase it so there won't be any
-        // useless allocation.
+    fn read_floats<E: ByteOrder>(&mut self, nb_points: usize) -> Result<()> {
+        // `nb_points` comes from the file, so a corrupted value could ask for a huge buffer. The
+        // buffer grows in bounded chunks, as the floats are read, and a truncated file fails
+        // before anything too big is allocated. Vec::resize never decreases capacity, so there
+        // won't be any useless allocation.
         let nb_floats = nb_points * self.floats_per_point;
-        self.buffer.resize(nb_floats, 0.0);
-        self.reader.read_f32_into::<E>(self.buffer.as_mut_slice()).unwrap();
+        self.buffer.clear();
+        while self.buffer.len() < nb_floats {
+            let start = self.buffer.len();
+            self.buffer.resize(nb_floats.min(start + READ_CHUNK), 0.0);
+            self.reader
+                .read_f32_into::<E>(&mut self.buffer[start..])
+                .map_err(|e| self.truncated(e))?;
+        }
+        Ok(())
     }
 
     #[inline(always)]
@@ -180,69 +331,314 @@ impl Reader {
         points.push(p);
     }
 
-    fn read_properties_to_arr<E: ByteOrder>(&mut self, properties: &mut ArraySequence<f32>) {
+    fn read_properties_to_arr<E: ByteOrder>(
+        &mut self,
+        properties: &mut ArraySequence<f32>,
+    ) -> Result<()> {
         for _ in 0..self.header.properties_name.len() {
-            properties.push(self.reader.read_f32::<E>().unwrap());
+            properties.push(self.reader.read_f32::<E>().map_err(|e| self.truncated(e))?);
         }
         properties.end_push();
+        Ok(())
     }
 
-    fn read_properties_to_vec<E: ByteOrder>(&mut self, properties: &mut Vec<f32>) {
+    fn read_properties_to_vec<E: ByteOrder>(&mut self, properties: &mut Vec<f32>) -> Result<()> {
         for _ in 0..self.header.properties_name.len() {
-            properties.push(self.reader.read_f32::<E>().unwrap());
+            properties.push(self.reader.read_f32::<E>().map_err(|e| self.truncated(e))?);
         }
+        Ok(())
     }
 
-    fn read_nb_points(&mut self) -> Option<usize> {
+    /// Read the number of points of the next streamline. Returns `None` at the end of the file.
+    fn read_nb_points_<E: ByteOrder>(&mut self) -> Result<Option<usize>> {
+        let mut bytes = [0u8; 4];
+        match read_up_to(&mut self.reader, &mut bytes)? {
+            0 => Ok(None),
+            4 => match E::read_i32(&bytes) {
+                nb_points if nb_points < 0 => Err(Error::InvalidNbPoints {
+                    streamline_index: self.streamline_index,
+                    nb_points,
+                }),
+                nb_points => Ok(Some(nb_points as usize)),
+            },
+            _ => Err(self.truncated(ErrorKind::UnexpectedEof.into())),
+        }
+    }
+
+    fn read_nb_points(&mut self) -> Result<Option<usize>> {
         match self.endianness {
-            Endianness::Little => self.reader.read_i32::<LittleEndian>(),
-            Endianness::Big => self.reader.read_i32::<BigEndian>(),
+            Endianness::Little => self.read_nb_points_::<LittleEndian>(),
+            Endianness::Big => self.read_nb_points_::<BigEndian>(),
         }
-        .ok()
-        .map(|nb_points| nb_points as usize)
     }
-}
 
-impl Iterator for Reader {
-    type Item = TractogramItem;
+    /// Advance the error-reporting position to the next streamline.
+    fn end_streamline(&mut self, nb_points: usize) {
+        let nb_floats = nb_points * self.floats_per_point + self.header.properties_name.len();
+        self.byte_offset += 4 * (1 + nb_floats as u64);
+        self.streamline_index += 1;
+    }
 
-    fn next(&mut self) -> Option<TractogramItem> {
-        let nb_points = self.read_nb_points()?;
-        let mut streamline = Vec::with_capacity(nb_points);
-        let mut scalars = ArraySequence::with_capacity(nb_points * self.header.scalars_name.len());
+    /// Convert an `UnexpectedEof` to `Error::Truncated`. All other errors are kept as-is.
+    fn truncated(&self, e: io::Error) -> Error {
+        if e.kind() == ErrorKind::UnexpectedEof {
+            Error::Truncated {
+                streamline_index: self.streamline_index,
+                byte_offset: self.byte_offset,
+            }
+        } else {
+            e.into()
+        }
+    }
+
+    fn next_item(&mut self) -> Result<Option<TractogramItem>> {
+        let nb_points = match self.read_nb_points()? {
+            Some(nb_points) => nb_points,
+            None => return Ok(None),
+        };
+        let nb_points_hint = nb_points.min(READ_CHUNK);
+        let mut streamline = Vec::with_capacity(nb_points_hint);
+        let mut scalars =
+            ArraySequence::with_capacity(nb_points_hint * self.header.scalars_name.len());
         let mut properties = Vec::with_capacity(self.header.properties_name.len());
         match self.endianness {
             Endianness::Little => {
-                self.read_streamline::<LittleEndian>(&mut streamline, &mut scalars, nb_points);
-                self.read_properties_to_vec::<LittleEndian>(&mut properties);
+                self.read_streamline::<LittleEndian>(&mut streamline, &mut scalars, nb_points)?;
+                self.read_properties_to_vec::<LittleEndian>(&mut properties)?;
             }
             Endianness::Big => {
-                self.read_streamline::<BigEndian>(&mut streamline, &mut scalars, nb_points);
-                self.read_properties_to_vec::<BigEndian>(&mut properties);
+                self.read_streamline::<BigEndian>(&mut streamline, &mut scalars, nb_points)?;
+                self.read_properties_to_vec::<BigEndian>(&mut properties)?;
             }
         };
-        Some((streamline, scalars, properties))
+        self.end_streamline(nb_points);
+        Ok(Some((streamline, scalars, properties)))
     }
-}
 
-pub struct StreamlinesIter {
-    reader: Reader,
-}
-
-impl Iterator for StreamlinesIter {
-    type Item = Points;
-
-    fn next(&mut self) -> Option<Points> {
-        let nb_points = self.reader.read_nb_points()?;
-        let mut streamline = Vec::with_capacity(nb_points);
-        match self.reader.endianness {
+    fn next_streamline(&mut self) -> Result<Option<Points>> {
+        let nb_points = match self.read_nb_points()? {
+            Some(nb_points) => nb_points,
+            None => return Ok(None),
+        };
+        let mut streamline = Vec::with_capacity(nb_points.min(READ_CHUNK));
+        match self.endianness {
             Endianness::Little => {
-                self.reader.read_streamline_fast::<LittleEndian>(&mut streamline, nb_points);
+                self.read_streamline_fast::<LittleEndian>(&mut streamline, nb_points)?;
             }
             Endianness::Big => {
-                self.reader.read_streamline_fast::<BigEndian>(&mut streamline, nb_points);
+                self.read_streamline_fast::<BigEndian>(&mut streamline, nb_points)?;
             }
         };
-        Some(streamline)
+        self.end_streamline(nb_points);
+        Ok(Some(streamline))
+    }
+
+    /// Transpose the result of `f` for an iterator. Nothing else will be read after an error.
+    fn next_or_fail<T, F>(&mut self, f: F) -> Option<Result<T>>
+    where
+        F: FnOnce(&mut Self) -> Result<Option<T>>,
+    {
+        if self.failed {
+            return None;
+        }
+        let result = f(self).transpose();
+        self.failed = matches!(result, Some(Err(_)));
+        result
+    }
+}
+
+/// Number of bytes read at once by `Reader::tractogram_parallel`.
+#[cfg(feature = "rayon")]
+const PARALLEL_CHUNK: usize = 1 << 20;
+
+#[cfg(feature = "rayon")]
+impl<R: Read> Reader<R> {
+    /// Read the complete tractogram, like `tractogram`, but decode and transform the points on
+    /// all threads.
+    ///
+    /// The remaining data is read in chunks of about 1 MiB. The lengths of the streamlines of a
+    /// chunk are found first, so that their points, scalars and properties can be decoded in
+    /// place, in parallel. Apart from the tractogram itself, only one chunk is kept in memory,
+    /// unless a single streamline is bigger than that.
+    pub fn tractogram_parallel(&mut self) -> Result<Tractogram> {
+        match self.endianness {
+            Endianness::Little => self.read_parallel_::<LittleEndian>(),
+            Endianness::Big => self.read_parallel_::<BigEndian>(),
+        }
+    }
+
+    fn read_parallel_<E: ByteOrder>(&mut self) -> Result<Tractogram> {
+        let nb_scalars = self.floats_per_point - 3;
+        let nb_properties = self.header.properties_name.len();
+        let mut lengths = vec![];
+        let (mut points, mut scalars, mut properties) = (vec![], vec![], vec![]);
+
+        let mut bytes = vec![];
+        let mut wanted = PARALLEL_CHUNK;
+        let mut eof = false;
+        loop {
+            if !eof && bytes.len() < wanted {
+                let start = bytes.len();
+                bytes.resize(wanted, 0);
+                let nb_read = read_up_to(&mut self.reader, &mut bytes[start..])?;
+                bytes.truncate(start + nb_read);
+                eof = bytes.len() < wanted;
+            }
+
+            let (chunk_lengths, positions, consumed) = self.scan_streamlines_::<E>(&bytes)?;
+            if chunk_lengths.is_empty() {
+                if bytes.is_empty() {
+                    break;
+                } else if eof {
+                    return Err(self.truncated(ErrorKind::UnexpectedEof.into()));
+                }
+                // The next streamline doesn't fit. Grow by steps instead of trusting its number
+                // of points, which may be corrupted.
+                wanted = bytes.len() + PARALLEL_CHUNK;
+                continue;
+            }
+
+            let nb_points = chunk_lengths.iter().sum::<usize>();
+            let (old_points, old_scalars) = (points.len(), scalars.len());
+            let old_properties = properties.len();
+            points.resize(old_points + nb_points, Point::origin());
+            scalars.resize(old_scalars + nb_points * nb_scalars, 0.0);
+            properties.resize(old_properties + chunk_lengths.len() * nb_properties, 0.0);
+            self.decode_parallel_::<E>(
+                &bytes,
+                &chunk_lengths,
+                &positions,
+                (
+                    &mut points[old_points..],
+                    &mut scalars[old_scalars..],
+                    &mut properties[old_properties..],
+                ),
+            );
+            lengths.extend(chunk_lengths);
+            bytes.drain(..consumed);
+            wanted = PARALLEL_CHUNK;
+        }
+
+        // As in `tractogram`, empty streamlines have empty scalars
+        let scalars_lengths =
+            if nb_scalars == 0 { vec![] } else { lengths.iter().map(|l| l * nb_scalars).collect() };
+        let properties_lengths =
+            if nb_properties == 0 { vec![] } else { vec![nb_properties; lengths.len()] };
+        Ok(Tractogram::new(
+            Streamlines::new(lengths, points),
+            ArraySequence::new(scalars_lengths, scalars),
+            ArraySequence::new(properties_lengths, properties),
+        ))
+    }
+
+    /// Find the number of points and the position of all complete streamlines at the start of
+    /// `bytes`, and the number of bytes they use. This part is cheap and can't be done in
+    /// parallel.
+    fn scan_streamlines_<E: ByteOrder>(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<(Vec<usize>, Vec<usize>, usize)> {
+        let nb_properties = self.header.properties_name.len();
+        let (mut lengths, mut positions) = (vec![], vec![]);
+        let mut position = 0;
+        while bytes.len() - position >= 4 {
+            let nb_points = match E::read_i32(&bytes[position..]) {
+                nb_points if nb_points < 0 => {
+                    return Err(Error::InvalidNbPoints {
+                        streamline_index: self.streamline_index,
+                        nb_points,
+                    })
+                }
+                nb_points => nb_points as usize,
+            };
+            let end = position + 4 + 4 * (nb_points * self.floats_per_point + nb_properties);
+            if end > bytes.len() {
+                break;
+            }
+
+            lengths.push(nb_points);
+            positions.push(position + 4);
+            self.end_streamline(nb_points);
+            position = end;
+        }
+        Ok((lengths, positions, position))
+    }
+
+    /// Decode the streamlines starting at `positions` in `bytes` directly in `outputs`, which
+    /// have exactly the right size for all of them.
+    fn decode_parallel_<E: ByteOrder>(
+        &self,
+        bytes: &[u8],
+        lengths: &[usize],
+        positions: &[usize],
+        outputs: (&mut [Point], &mut [f32], &mut [f32]),
+    ) {
+        use rayon::prelude::*;
+
+        let nb_scalars = self.floats_per_point - 3;
+        let nb_properties = self.header.properties_name.len();
+
+        // Give each streamline its own part of the outputs
+        let mut parts = Vec::with_capacity(lengths.len());
+        let (mut points_left, mut scalars_left, mut properties_left) = outputs;
+        for (&nb_points, &position) in lengths.iter().zip(positions) {
+            let (p, p_left) = points_left.split_at_mut(nb_points);
+            let (s, s_left) = scalars_left.split_at_mut(nb_points * nb_scalars);
+            let (props, props_left) = properties_left.split_at_mut(nb_properties);
+            parts.push((position, p, s, props));
+            points_left = p_left;
+            scalars_left = s_left;
+            properties_left = props_left;
+        }
+
+        let (raw, affine, translation) =
+            (self.raw, self.header.affine_to_rasmm, self.header.translation);
+        let point_size = 4 * self.floats_per_point;
+        parts.into_par_iter().for_each(|(position, points, scalars, properties)| {
+            let end = position + points.len() * point_size;
+            for (i, b) in bytes[position..end].chunks_exact(point_size).enumerate() {
+                let p = Point::new(E::read_f32(b), E::read_f32(&b[4..]), E::read_f32(&b[8..]));
+                points[i] = if raw { p } else { (affine * p) + translation };
+                E::read_f32_into(&b[12..], &mut scalars[i * nb_scalars..(i + 1) * nb_scalars]);
+            }
+            E::read_f32_into(&bytes[end..end + 4 * properties.len()], properties);
+        });
+    }
+}
+
+/// Fill `buf` as much as possible and returns the number of bytes read.
+///
+/// Unlike `read_exact`, it's possible to know if the end of the file was reached before or in the
+/// middle of `buf`.
+pub(crate) fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut nb_read = 0;
+    while nb_read < buf.len() {
+        match reader.read(&mut buf[nb_read..]) {
+            Ok(0) => break,
+            Ok(n) => nb_read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(nb_read)
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<TractogramItem>;
+
+    fn next(&mut self) -> Option<Result<TractogramItem>> {
+        self.next_or_fail(Reader::next_item)
+    }
+}
+
+pub struct StreamlinesIter<R: Read = TrkInput> {
+    reader: Reader<R>,
+}
+
+impl<R: Read> Iterator for StreamlinesIter<R> {
+    type Item = Result<Points>;
+
+    fn next(&mut self) -> Option<Result<Points>> {
+        self.reader.next_or_fail(Reader::next_streamline)
     }
 }
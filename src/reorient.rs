@@ -0,0 +1,63 @@
+//! Flipping of streamlines so that they all go in the same direction.
+//!
+//! Tractography doesn't give any meaning to the direction of a streamline, but averaging
+//! streamlines or sampling a profile along a bundle needs all streamlines to start at the same end.
+//! All functions reverse the points and the per-point scalars of the flipped streamlines, and
+//! return how many streamlines were flipped.
+
+#[cfg(feature = "nifti_images")]
+use crate::roi::Mask;
+use crate::{Point, Tractogram};
+
+/// Flip all streamlines for which `should_flip` returns `true`.
+pub fn orient_with<F>(tractogram: &mut Tractogram, mut should_flip: F) -> usize
+where
+    F: FnMut(&[Point]) -> bool,
+{
+    let mut nb_flipped = 0;
+    for i in 0..tractogram.streamlines.len() {
+        if should_flip(&tractogram.streamlines[i]) {
+            tractogram.reverse(i);
+            nb_flipped += 1;
+        }
+    }
+    nb_flipped
+}
+
+/// Flip the streamlines so that their first point is nearer to `point` than their last point.
+pub fn orient_to_point(tractogram: &mut Tractogram, point: &Point) -> usize {
+    orient_with(tractogram, |streamline| match (streamline.first(), streamline.last()) {
+        (Some(first), Some(last)) => (last - point).norm() < (first - point).norm(),
+        _ => false,
+    })
+}
+
+/// Flip the streamlines so that they go in the same direction as `reference`, i.e. so that their
+/// endpoints are nearest to the corresponding endpoints of `reference`.
+///
+/// The streamlines and `reference` can have different numbers of points.
+pub fn orient_to_streamline(tractogram: &mut Tractogram, reference: &[Point]) -> usize {
+    let (start, end) = match (reference.first(), reference.last()) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return 0,
+    };
+    orient_with(tractogram, |streamline| match (streamline.first(), streamline.last()) {
+        (Some(first), Some(last)) => {
+            let direct = (first - start).norm() + (last - end).norm();
+            let flipped = (last - start).norm() + (first - end).norm();
+            flipped < direct
+        }
+        _ => false,
+    })
+}
+
+/// Flip the streamlines that end in `mask` but don't start in it, so that they start in it.
+///
+/// `tractogram` must be in voxel space, see `roi`.
+#[cfg(feature = "nifti_images")]
+pub fn orient_to_mask(tractogram: &mut Tractogram, mask: &Mask) -> usize {
+    orient_with(tractogram, |streamline| match (streamline.first(), streamline.last()) {
+        (Some(first), Some(last)) => !mask.contains(first) && mask.contains(last),
+        _ => false,
+    })
+}
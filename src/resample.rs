@@ -0,0 +1,162 @@
+//! Resampling of streamlines to a fixed number of points or to a fixed step size.
+//!
+//! All points are linearly interpolated along the streamline, so the first and last points are
+//! always kept. The per-point scalars of a `Tractogram` are interpolated in the same way.
+
+use crate::{metrics, ArraySequence, Point, Points, Streamlines, Tractogram};
+
+/// Resample `streamline` to `nb_points` equidistant points.
+pub fn resample_streamline(streamline: &[Point], nb_points: usize) -> Points {
+    sample_positions(streamline, nb_points)
+        .into_iter()
+        .map(|(i, t)| interpolate_point(streamline, i, t))
+        .collect()
+}
+
+/// Resample `streamline` to equidistant points, separated by at most `step` mm.
+///
+/// Panics if `step` is not strictly positive.
+pub fn resample_streamline_step(streamline: &[Point], step: f32) -> Points {
+    resample_streamline(streamline, nb_points_for_step(streamline, step))
+}
+
+/// Resample all streamlines to `nb_points` equidistant points. Empty streamlines stay empty.
+///
+/// Panics if `nb_points` is 0.
+pub fn resample_streamlines(streamlines: &Streamlines, nb_points: usize) -> Streamlines {
+    assert_valid_nb_points(nb_points);
+    let mut resampled = ArraySequence::with_capacity(streamlines.len() * nb_points);
+    for streamline in streamlines {
+        resampled.data.extend(resample_streamline(streamline, nb_points));
+        resampled.offsets.push(resampled.data.len());
+    }
+    resampled
+}
+
+/// Resample all streamlines to equidistant points, separated by at most `step` mm. Empty
+/// streamlines stay empty.
+///
+/// Panics if `step` is not strictly positive.
+pub fn resample_streamlines_step(streamlines: &Streamlines, step: f32) -> Streamlines {
+    let mut resampled = ArraySequence::with_capacity(streamlines.data.len());
+    for streamline in streamlines {
+        resampled.data.extend(resample_streamline_step(streamline, step));
+        resampled.offsets.push(resampled.data.len());
+    }
+    resampled
+}
+
+/// Resample all streamlines of `tractogram` to `nb_points` equidistant points. The scalars are
+/// interpolated and the properties are copied. Empty streamlines stay empty.
+///
+/// Panics if `nb_points` is 0.
+pub fn resample_tractogram(tractogram: &Tractogram, nb_points: usize) -> Tractogram {
+    assert_valid_nb_points(nb_points);
+    resample_tractogram_with(tractogram, |_| nb_points)
+}
+
+/// Resample all streamlines of `tractogram` to equidistant points, separated by at most `step`
+/// mm. The scalars are interpolated and the properties are copied. Empty streamlines stay empty.
+///
+/// Panics if `step` is not strictly positive.
+pub fn resample_tractogram_step(tractogram: &Tractogram, step: f32) -> Tractogram {
+    resample_tractogram_with(tractogram, |streamline| nb_points_for_step(streamline, step))
+}
+
+fn resample_tractogram_with<F>(tractogram: &Tractogram, nb_points: F) -> Tractogram
+where
+    F: Fn(&[Point]) -> usize,
+{
+    let nb_streamlines = tractogram.streamlines.len();
+    let nb_scalars = if nb_streamlines == 0 || tractogram.scalars.is_empty() {
+        0
+    } else {
+        tractogram.scalars.data.len() / tractogram.streamlines.data.len()
+    };
+
+    let mut streamlines = ArraySequence::with_capacity(tractogram.streamlines.data.len());
+    let mut scalars = ArraySequence::with_capacity(tractogram.scalars.data.len());
+    for (streamline, streamline_scalars, _) in tractogram {
+        let positions = sample_positions(streamline, nb_points(streamline));
+        // Always push the offsets, even for an empty streamline, so that the streamlines, scalars
+        // and properties stay aligned.
+        streamlines
+            .data
+            .extend(positions.iter().map(|&(i, t)| interpolate_point(streamline, i, t)));
+        streamlines.offsets.push(streamlines.data.len());
+        if nb_scalars > 0 {
+            for &(i, t) in &positions {
+                let current = &streamline_scalars[i * nb_scalars..(i + 1) * nb_scalars];
+                let next = match streamline_scalars.get((i + 1) * nb_scalars..(i + 2) * nb_scalars)
+                {
+                    Some(next) => next,
+                    None => current,
+                };
+                scalars.data.extend(current.iter().zip(next).map(|(a, b)| a + (b - a) * t));
+            }
+            scalars.offsets.push(scalars.data.len());
+        }
+    }
+    Tractogram::new(streamlines, scalars, tractogram.properties.clone())
+}
+
+fn assert_valid_nb_points(nb_points: usize) {
+    assert!(nb_points > 0, "Number of points must be strictly positive");
+}
+
+/// Number of points needed to have a step of at most `step` mm, including both ends.
+fn nb_points_for_step(streamline: &[Point], step: f32) -> usize {
+    assert!(step > 0.0, "Step size must be strictly positive, got {}", step);
+    if streamline.is_empty() {
+        return 0;
+    }
+    (metrics::length(streamline) / step).ceil() as usize + 1
+}
+
+/// For `nb_points` equidistant positions along `streamline`, returns the index of the segment
+/// where it lies and how far it is in this segment, from 0.0 to 1.0.
+///
+/// A streamline with a single point (or with a length of 0) is "resampled" by repeating its first
+/// point.
+fn sample_positions(streamline: &[Point], nb_points: usize) -> Vec<(usize, f32)> {
+    if streamline.is_empty() || nb_points == 0 {
+        return vec![];
+    }
+
+    let mut cumulative_lengths = Vec::with_capacity(streamline.len());
+    cumulative_lengths.push(0.0);
+    for w in streamline.windows(2) {
+        cumulative_lengths.push(cumulative_lengths.last().unwrap() + (w[1] - w[0]).norm());
+    }
+    let length = *cumulative_lengths.last().unwrap();
+    if length == 0.0 || nb_points == 1 {
+        return vec![(0, 0.0); nb_points];
+    }
+
+    let last_segment = streamline.len() - 2;
+    let step = length / (nb_points - 1) as f32;
+    let mut segment = 0;
+    let mut positions = Vec::with_capacity(nb_points);
+    for k in 0..nb_points {
+        let distance = if k == nb_points - 1 { length } else { k as f32 * step };
+        while segment < last_segment && cumulative_lengths[segment + 1] < distance {
+            segment += 1;
+        }
+
+        let segment_length = cumulative_lengths[segment + 1] - cumulative_lengths[segment];
+        let t = if segment_length == 0.0 {
+            0.0
+        } else {
+            ((distance - cumulative_lengths[segment]) / segment_length).clamp(0.0, 1.0)
+        };
+        positions.push((segment, t));
+    }
+    positions
+}
+
+fn interpolate_point(streamline: &[Point], i: usize, t: f32) -> Point {
+    match streamline.get(i + 1) {
+        Some(next) => streamline[i] + (next - streamline[i]) * t,
+        None => streamline[i],
+    }
+}
@@ -0,0 +1,182 @@
+//! Selection of streamlines with regions of interest (ROI), defined by binary NIfTI masks.
+//!
+//! The streamlines must be in voxel space, as read by `Reader::to_voxel_space` or by a
+//! `VoxelSpaceReader`, and the masks must be on the same grid as the trk file. A streamline touches
+//! a mask if any of its segments crosses a voxel of the mask, even if no point lies in it.
+
+use std::path::Path;
+
+use ndarray::{Array3, Ix3};
+use nifti::{IntoNdArray, NiftiObject, ReaderOptions};
+
+use crate::{
+    tdi::{traverse_segment, voxel_of},
+    Error, Header, Point, Reader, Result, Spacing, Stats, Tractogram,
+};
+
+/// A binary mask, indexed in voxel order `[i, j, k]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mask {
+    data: Array3<bool>,
+}
+
+impl Mask {
+    pub fn new(data: Array3<bool>) -> Mask {
+        Mask { data }
+    }
+
+    /// Load a 3D NIfTI image. All non-zero voxels are part of the mask.
+    pub fn from_nifti<P: AsRef<Path>>(path: P) -> Result<Mask> {
+        let (_, data) = read_volume(path, "A mask")?;
+        Ok(Mask::new(data.mapv(|v| v != 0.0)))
+    }
+
+    pub fn shape(&self) -> [usize; 3] {
+        let shape = self.data.shape();
+        [shape[0], shape[1], shape[2]]
+    }
+
+    /// Whether `point`, in voxel space, is in the mask. Everything outside of the grid is not.
+    pub fn contains(&self, point: &Point) -> bool {
+        self.contains_voxel(voxel_of(&point.coords))
+    }
+
+    /// Whether any segment of `streamline`, in voxel space, crosses the mask.
+    pub fn touches(&self, streamline: &[Point]) -> bool {
+        if streamline.len() == 1 {
+            return self.contains(&streamline[0]);
+        }
+        streamline.windows(2).any(|w| {
+            let mut touches = false;
+            traverse_segment(&w[0].coords, &w[1].coords, |voxel, _| {
+                touches = touches || self.contains_voxel(voxel);
+            });
+            touches
+        })
+    }
+
+    fn contains_voxel(&self, voxel: [isize; 3]) -> bool {
+        if voxel.iter().any(|&v| v < 0) {
+            return false;
+        }
+        self.data.get(voxel.map(|v| v as usize)).cloned().unwrap_or(false)
+    }
+}
+
+/// How a streamline must relate to a mask to satisfy a criterion.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoiMode {
+    /// The streamline must touch the mask.
+    Include,
+    /// The streamline must never touch the mask.
+    Exclude,
+    /// At least one endpoint of the streamline must be in the mask.
+    AnyEndpoint,
+    /// Both endpoints of the streamline must be in the mask.
+    BothEndpoints,
+}
+
+/// How the criteria of a `RoiSelection` are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoiLogic {
+    /// A streamline is selected if it satisfies all criteria.
+    All,
+    /// A streamline is selected if it satisfies at least one criterion.
+    Any,
+}
+
+/// A set of criteria, each made of a mask and a `RoiMode`.
+///
+/// A selection without any criterion selects all streamlines with `RoiLogic::All`, and none with
+/// `RoiLogic::Any`.
+#[derive(Clone, Debug)]
+pub struct RoiSelection {
+    criteria: Vec<(Mask, RoiMode)>,
+    logic: RoiLogic,
+}
+
+impl RoiSelection {
+    pub fn new(logic: RoiLogic) -> RoiSelection {
+        RoiSelection { criteria: vec![], logic }
+    }
+
+    /// Add a criterion to the selection.
+    pub fn add(mut self, mask: Mask, mode: RoiMode) -> Self {
+        self.criteria.push((mask, mode));
+        self
+    }
+
+    /// Whether `streamline`, in voxel space, is selected.
+    pub fn selects(&self, streamline: &[Point]) -> bool {
+        let satisfies = |(mask, mode): &(Mask, RoiMode)| match (mode, streamline) {
+            (RoiMode::Include, _) => mask.touches(streamline),
+            (RoiMode::Exclude, _) => !mask.touches(streamline),
+            (_, []) => false,
+            (RoiMode::AnyEndpoint, [first, .., last]) => {
+                mask.contains(first) || mask.contains(last)
+            }
+            (RoiMode::BothEndpoints, [first, .., last]) => {
+                mask.contains(first) && mask.contains(last)
+            }
+            (_, [point]) => mask.contains(point),
+        };
+        match self.logic {
+            RoiLogic::All => self.criteria.iter().all(satisfies),
+            RoiLogic::Any => self.criteria.iter().any(satisfies),
+        }
+    }
+
+    /// Copy the selected streamlines, with their scalars and properties, in a new `Tractogram`.
+    ///
+    /// `tractogram` must be in voxel space.
+    pub fn filter_tractogram(&self, tractogram: &Tractogram) -> Tractogram {
+        tractogram.filter(|(streamline, _, _)| self.selects(streamline))
+    }
+
+    /// Copy the selected streamlines of the trk file `input`, with their scalars and properties,
+    /// to the trk file `output`. Streamlines are read and written one at a time, so any file size
+    /// can be filtered.
+    ///
+    /// Returns an error if a mask is not on the same grid as `input`.
+    pub fn filter_trk<P: AsRef<Path>, Q: AsRef<Path>>(&self, input: P, output: Q) -> Result<Stats> {
+        let reader = Reader::new(input)?;
+        for (mask, _) in &self.criteria {
+            check_grid(&reader.header, mask.shape(), "Mask")?;
+        }
+
+        let spacing = Spacing::from(reader.header.raw_header().voxel_size);
+        let reader = reader.to_voxel_space(spacing);
+        let mut writer = reader.build_writer(output)?;
+        for item in reader {
+            let item = item?;
+            if self.selects(&item.0) {
+                writer.write(item)?;
+            }
+        }
+        writer.finish()
+    }
+}
+
+/// Load the 3D NIfTI image at `path`, with a trk header in the same space. `kind` describes the
+/// image in the error returned for other dimensions.
+pub(crate) fn read_volume<P: AsRef<Path>>(path: P, kind: &str) -> Result<(Header, Array3<f32>)> {
+    let object = ReaderOptions::new().read_file(path)?;
+    let header = Header::from_nifti(object.header());
+    let data = object.into_volume().into_ndarray::<f32>()?;
+    let data = data
+        .into_dimensionality::<Ix3>()
+        .map_err(|_| Error::InvalidHeader(format!("{} must be a 3D image", kind)))?;
+    Ok((header, data))
+}
+
+/// Returns an error if an image of shape `shape` is not on the same grid as the trk `header`.
+pub(crate) fn check_grid(header: &Header, shape: [usize; 3], kind: &str) -> Result<()> {
+    let dim = header.raw_header().dim.map(|d| d as usize);
+    if shape != dim {
+        return Err(Error::InvalidHeader(format!(
+            "{} of shape {:?} doesn't match the trk dimensions {:?}",
+            kind, shape, dim
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{error::path_error, Error, Result};
+
+/// Magic first line of all TCK files.
+const TCK_MAGIC: &str = "mrtrix tracks";
+
+/// Number of digits used to write the `count` field, so that it can be rewritten in-place once the
+/// real number of streamlines is known.
+pub(crate) const COUNT_WIDTH: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TckDataType {
+    Float32LE,
+    Float32BE,
+    Float64LE,
+    Float64BE,
+}
+
+impl fmt::Display for TckDataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            TckDataType::Float32LE => "Float32LE",
+            TckDataType::Float32BE => "Float32BE",
+            TckDataType::Float64LE => "Float64LE",
+            TckDataType::Float64BE => "Float64BE",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for TckDataType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TckDataType> {
+        match s {
+            "Float32LE" => Ok(TckDataType::Float32LE),
+            "Float32BE" => Ok(TckDataType::Float32BE),
+            "Float64LE" => Ok(TckDataType::Float64LE),
+            "Float64BE" => Ok(TckDataType::Float64BE),
+            _ => Err(Error::InvalidHeader(format!("Unsupported TCK datatype {:?}", s))),
+        }
+    }
+}
+
+/// MRtrix TCK header.
+///
+/// The header is a list of `key: value` text lines. `datatype`, `count` and `file` are parsed,
+/// all other fields are kept as-is, in order, so they can be written back.
+#[derive(Clone)]
+pub struct TckHeader {
+    pub nb_streamlines: usize,
+    pub datatype: TckDataType,
+    pub fields: Vec<(String, String)>,
+}
+
+impl TckHeader {
+    /// Retrieve a tck header from a file in the file system.
+    pub fn from_tck<P: AsRef<Path>>(path: P) -> Result<TckHeader> {
+        let f = File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let mut reader = BufReader::new(f);
+        TckHeader::read(&mut reader)
+    }
+
+    /// Retrieve a tck header from the given `BufReader`. The `reader` will be positioned at the
+    /// start of the data when this function returns.
+    pub fn read(reader: &mut BufReader<File>) -> Result<TckHeader> {
+        reader.seek(SeekFrom::Start(0))?;
+
+        // Do not use `read_line` for the signature because a binary file may not be valid utf-8
+        let mut magic = [0u8; TCK_MAGIC.len() + 1];
+        if reader.read_exact(&mut magic).is_err()
+            || &magic[..TCK_MAGIC.len()] != TCK_MAGIC.as_bytes()
+            || magic[TCK_MAGIC.len()] != b'\n'
+        {
+            return Err(Error::BadMagic);
+        }
+        let mut line = String::new();
+
+        let mut nb_streamlines = 0;
+        let mut datatype = None;
+        let mut offset = None;
+        let mut fields = vec![];
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).map_err(invalid_header)? == 0 {
+                return Err(Error::InvalidHeader("Missing END line".to_string()));
+            }
+            let line = line.trim_end();
+            if line == "END" {
+                break;
+            }
+
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| Error::InvalidHeader(format!("Invalid line {:?}", line)))?;
+            let value = value.trim();
+            match key.trim() {
+                "count" => {
+                    nb_streamlines = value
+                        .parse::<usize>()
+                        .map_err(|_| Error::InvalidHeader(format!("Invalid count {:?}", value)))?
+                }
+                "datatype" => datatype = Some(value.parse::<TckDataType>()?),
+                "file" => {
+                    // The only supported form is `. offset`, meaning "this file, at `offset`"
+                    offset = match value.split_whitespace().collect::<Vec<_>>()[..] {
+                        [".", offset] => Some(offset.parse::<u64>().map_err(|_| {
+                            Error::InvalidHeader(format!("Invalid offset {:?}", offset))
+                        })?),
+                        _ => {
+                            return Err(Error::InvalidHeader(format!(
+                                "Unsupported file field {:?}",
+                                value
+                            )))
+                        }
+                    }
+                }
+                key => fields.push((key.to_string(), value.to_string())),
+            }
+        }
+
+        let datatype =
+            datatype.ok_or_else(|| Error::InvalidHeader("Missing 'datatype'".to_string()))?;
+        let offset = offset.ok_or_else(|| Error::InvalidHeader("Missing 'file'".to_string()))?;
+        reader.seek(SeekFrom::Start(offset))?;
+
+        Ok(TckHeader { nb_streamlines, datatype, fields })
+    }
+
+    /// Write the header, with a zero-padded `count` field, and returns the offset of the `count`
+    /// value in bytes.
+    ///
+    /// The `datatype` written is always `Float32LE` because it's the only type that `TckWriter`
+    /// writes.
+    pub(crate) fn write<W: Write>(&self, writer: &mut W) -> Result<u64> {
+        let mut text = format!("{}\ncount: ", TCK_MAGIC);
+        let count_offset = text.len() as u64;
+        text += &format!("{:0width$}\n", 0, width = COUNT_WIDTH);
+        text += &format!("datatype: {}\n", TckDataType::Float32LE);
+        for (key, value) in &self.fields {
+            text += &format!("{}: {}\n", key, value);
+        }
+        text += "file: . ";
+
+        // The offset is part of the header, so its own number of digits must be taken into
+        // account.
+        let end = "\nEND\n";
+        let mut offset = text.len() + end.len();
+        loop {
+            let new_offset = text.len() + offset.to_string().len() + end.len();
+            if new_offset == offset {
+                break;
+            }
+            offset = new_offset;
+        }
+        text += &format!("{}{}", offset, end);
+
+        writer.write_all(text.as_bytes())?;
+        Ok(count_offset)
+    }
+}
+
+impl Default for TckHeader {
+    fn default() -> TckHeader {
+        TckHeader { nb_streamlines: 0, datatype: TckDataType::Float32LE, fields: vec![] }
+    }
+}
+
+impl PartialEq for TckHeader {
+    fn eq(&self, other: &TckHeader) -> bool {
+        self.nb_streamlines == other.nb_streamlines && self.fields == other.fields
+    }
+}
+
+/// A header line which isn't valid utf-8 (or which can't be read at all) makes the header invalid.
+fn invalid_header(e: io::Error) -> Error {
+    if e.kind() == ErrorKind::InvalidData {
+        Error::InvalidHeader("Header is not valid utf-8".to_string())
+    } else {
+        e.into()
+    }
+}
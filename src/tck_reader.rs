@@ -0,0 +1,165 @@
+use std::{
+    fs::File,
+    io::{BufReader, Seek},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{
+    error::path_error,
+    reader::read_up_to,
+    tck_header::{TckDataType, TckHeader},
+    tractogram::{Point, Points, Streamlines, Tractogram, TractogramItem},
+    ArraySequence, Error, Result,
+};
+
+/// Reader for MRtrix TCK files.
+///
+/// TCK points are always in world space (RAS+ mm), so there's no affine to apply. The format
+/// doesn't support scalars nor properties, thus they will always be empty.
+pub struct TckReader {
+    reader: BufReader<File>,
+    pub header: TckHeader,
+
+    /// `true` when the end of the data has been reached (by the Inf terminator or EOF), or when an
+    /// error has been returned.
+    done: bool,
+
+    /// Index and position in the file of the next streamline to read. Only used to report errors.
+    streamline_index: usize,
+    byte_offset: u64,
+}
+
+impl TckReader {
+    /// Create an object to read all points of a TCK file.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<TckReader> {
+        let f = File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let mut reader = BufReader::new(f);
+        let header = TckHeader::read(&mut reader)?;
+        let byte_offset = reader.stream_position()?;
+        Ok(TckReader { reader, header, done: false, streamline_index: 0, byte_offset })
+    }
+
+    /// Iterate only on streamlines (`Vec<Point>`).
+    pub fn into_streamlines_iter(self) -> TckStreamlinesIter {
+        TckStreamlinesIter { reader: self }
+    }
+
+    /// Read the complete tractogram. Scalars and properties will be empty.
+    pub fn tractogram(&mut self) -> Result<Tractogram> {
+        Ok(Tractogram::new(self.streamlines()?, ArraySequence::empty(), ArraySequence::empty()))
+    }
+
+    /// Read all points. Empty streamlines are kept, so there's one streamline per NaN delimiter.
+    pub fn streamlines(&mut self) -> Result<Streamlines> {
+        let mut streamlines = Streamlines::with_capacity(300);
+        while let Some(streamline) = self.read_streamline()? {
+            // Not `extend`, which would skip an empty streamline
+            streamlines.insert(streamlines.len(), &streamline);
+        }
+        Ok(streamlines)
+    }
+
+    /// Read the next streamline, up to its NaN delimiter. Returns `None` when the Inf terminator
+    /// (or the end of the file) is reached.
+    fn read_streamline(&mut self) -> Result<Option<Points>> {
+        let mut points = Points::new();
+        while !self.done {
+            let p = match self.header.datatype {
+                TckDataType::Float32LE => self.read_point::<LittleEndian>(),
+                TckDataType::Float32BE => self.read_point::<BigEndian>(),
+                TckDataType::Float64LE => self.read_point_f64::<LittleEndian>(),
+                TckDataType::Float64BE => self.read_point_f64::<BigEndian>(),
+            };
+            match p {
+                Ok(Some(p)) if p.x.is_nan() => {
+                    self.end_streamline(points.len() + 1);
+                    return Ok(Some(points));
+                }
+                Ok(Some(p)) if p.x.is_infinite() => self.done = true,
+                Ok(Some(p)) => points.push(p),
+                // Some writers do not add the Inf terminator
+                Ok(None) if points.is_empty() => self.done = true,
+                Ok(None) => {
+                    self.done = true;
+                    return Err(self.truncated());
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Err(e);
+                }
+            }
+        }
+
+        // Some writers do not add a NaN delimiter before the Inf terminator
+        if points.is_empty() {
+            Ok(None)
+        } else {
+            self.end_streamline(points.len());
+            Ok(Some(points))
+        }
+    }
+
+    /// Read a single point. Returns `None` if the end of the file is reached before the point.
+    fn read_point<E: ByteOrder>(&mut self) -> Result<Option<Point>> {
+        let mut bytes = [0u8; 12];
+        match read_up_to(&mut self.reader, &mut bytes)? {
+            0 => Ok(None),
+            12 => {
+                let mut xyz = [0.0f32; 3];
+                E::read_f32_into(&bytes, &mut xyz);
+                Ok(Some(Point::new(xyz[0], xyz[1], xyz[2])))
+            }
+            _ => Err(self.truncated()),
+        }
+    }
+
+    fn read_point_f64<E: ByteOrder>(&mut self) -> Result<Option<Point>> {
+        let mut bytes = [0u8; 24];
+        match read_up_to(&mut self.reader, &mut bytes)? {
+            0 => Ok(None),
+            24 => {
+                let mut xyz = [0.0f64; 3];
+                E::read_f64_into(&bytes, &mut xyz);
+                Ok(Some(Point::new(xyz[0] as f32, xyz[1] as f32, xyz[2] as f32)))
+            }
+            _ => Err(self.truncated()),
+        }
+    }
+
+    /// Advance the error-reporting position to the next streamline.
+    fn end_streamline(&mut self, nb_points: usize) {
+        let point_size = match self.header.datatype {
+            TckDataType::Float32LE | TckDataType::Float32BE => 12,
+            TckDataType::Float64LE | TckDataType::Float64BE => 24,
+        };
+        self.byte_offset += (nb_points * point_size) as u64;
+        self.streamline_index += 1;
+    }
+
+    fn truncated(&self) -> Error {
+        Error::Truncated { streamline_index: self.streamline_index, byte_offset: self.byte_offset }
+    }
+}
+
+impl Iterator for TckReader {
+    type Item = Result<TractogramItem>;
+
+    fn next(&mut self) -> Option<Result<TractogramItem>> {
+        let streamline = self.read_streamline().transpose()?;
+        Some(streamline.map(|streamline| (streamline, ArraySequence::empty(), vec![])))
+    }
+}
+
+pub struct TckStreamlinesIter {
+    reader: TckReader,
+}
+
+impl Iterator for TckStreamlinesIter {
+    type Item = Result<Points>;
+
+    fn next(&mut self) -> Option<Result<Points>> {
+        self.reader.read_streamline().transpose()
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use byteorder::{LittleEndian, WriteBytesExt};
+
+use crate::{
+    error::path_error,
+    tck_header::{TckHeader, COUNT_WIDTH},
+    tractogram::{Point, RefTractogramItem, Tractogram, TractogramItem},
+    Result, Stats,
+};
+
+/// Writer for MRtrix TCK files.
+///
+/// Points are expected to be in world space (RAS+ mm) and are always written as `Float32LE`.
+/// Scalars and properties are silently ignored because the format can't hold them.
+pub struct TckWriter {
+    writer: BufWriter<File>,
+    count_offset: u64,
+    real_n_count: usize,
+    real_nb_points: usize,
+    sync_on_finish: bool,
+    finished: bool,
+}
+
+pub trait TckWritable {
+    fn write(self, w: &mut TckWriter) -> Result<()>;
+}
+
+impl TckWritable for Tractogram {
+    fn write(self, w: &mut TckWriter) -> Result<()> {
+        for item in &self {
+            item.write(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl TckWritable for TractogramItem {
+    fn write(self, w: &mut TckWriter) -> Result<()> {
+        w.write_points(&self.0)
+    }
+}
+
+impl<'data> TckWritable for RefTractogramItem<'data> {
+    fn write(self, w: &mut TckWriter) -> Result<()> {
+        w.write_points(self.0)
+    }
+}
+
+impl TckWritable for &[Point] {
+    fn write(self, w: &mut TckWriter) -> Result<()> {
+        w.write_points(self)
+    }
+}
+
+impl TckWriter {
+    /// Create a new TCK file. The fields of the `reference` header, if any, are copied, except
+    /// `count`, `datatype` and `file` which are managed by the `TckWriter`.
+    pub fn new<P: AsRef<Path>>(path: P, reference: Option<&TckHeader>) -> Result<TckWriter> {
+        let f =
+            File::create(path.as_ref()).map_err(|e| path_error(e, "Failed to create", &path))?;
+        let mut writer = BufWriter::new(f);
+
+        let count_offset = match reference {
+            Some(header) => header.write(&mut writer)?,
+            None => TckHeader::default().write(&mut writer)?,
+        };
+
+        Ok(TckWriter {
+            writer,
+            count_offset,
+            real_n_count: 0,
+            real_nb_points: 0,
+            sync_on_finish: false,
+            finished: false,
+        })
+    }
+
+    /// Asks `finish` to also call `fsync`, so that all data is on disk when it returns.
+    pub fn sync_on_finish(mut self) -> Self {
+        self.sync_on_finish = true;
+        self
+    }
+
+    pub fn write<T: TckWritable>(&mut self, data: T) -> Result<()> {
+        data.write(self)
+    }
+
+    pub fn write_from_iter<I>(&mut self, streamline: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Point>,
+    {
+        for p in streamline {
+            self.write_point(&p)?;
+        }
+        self.end_streamline()
+    }
+
+    fn write_points(&mut self, streamline: &[Point]) -> Result<()> {
+        for p in streamline {
+            self.write_point(p)?;
+        }
+        self.end_streamline()
+    }
+
+    fn write_point(&mut self, p: &Point) -> Result<()> {
+        self.write_xyz(p.x, p.y, p.z)?;
+        self.real_nb_points += 1;
+        Ok(())
+    }
+
+    /// Write the NaN delimiter which ends all streamlines.
+    fn end_streamline(&mut self) -> Result<()> {
+        self.write_xyz(f32::NAN, f32::NAN, f32::NAN)?;
+        self.real_n_count += 1;
+        Ok(())
+    }
+
+    fn write_xyz(&mut self, x: f32, y: f32, z: f32) -> Result<()> {
+        self.writer.write_f32::<LittleEndian>(x)?;
+        self.writer.write_f32::<LittleEndian>(y)?;
+        self.writer.write_f32::<LittleEndian>(z)?;
+        Ok(())
+    }
+
+    /// Complete the tck file and returns what has been written.
+    ///
+    /// The Inf terminator and the real `count` can only be written at the end because we don't
+    /// know how many streamlines the user will write. If `finish` is not called, `Drop` will try
+    /// to write them, but any error will be silently ignored.
+    pub fn finish(mut self) -> Result<Stats> {
+        self.finished = true;
+        self.write_end()?;
+        if self.sync_on_finish {
+            self.writer.get_ref().sync_all()?;
+        }
+        Ok(Stats { nb_streamlines: self.real_n_count, nb_points: self.real_nb_points })
+    }
+
+    fn write_end(&mut self) -> Result<()> {
+        self.write_xyz(f32::INFINITY, f32::INFINITY, f32::INFINITY)?;
+        self.writer.seek(SeekFrom::Start(self.count_offset))?;
+        let count = format!("{:0width$}", self.real_n_count, width = COUNT_WIDTH);
+        self.writer.write_all(count.as_bytes())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// Best-effort fallback when `finish` has not been called
+impl Drop for TckWriter {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.write_end();
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Track density imaging (TDI), i.e. rasterization of streamlines on the grid of a reference
+//! image.
+//!
+//! The streamlines must be in world space (RAS+ and mm), which is what a `Reader` returns by
+//! default. Each segment is traversed exactly, voxel by voxel, so a voxel crossed by a long segment
+//! is counted even if no point lies in it. Everything outside of the reference grid is ignored.
+
+use std::path::Path;
+
+use nalgebra::Vector3;
+use ndarray::Array3;
+use nifti::{writer::WriterOptions, NiftiHeader};
+
+use crate::{Error, Point, Result, Streamlines};
+
+/// What is accumulated in each voxel of a track density image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TdiMode {
+    /// Number of streamlines crossing the voxel. A streamline is counted once per voxel, even if
+    /// it crosses it several times.
+    Count,
+    /// Total length, in mm, of all the streamline segments inside the voxel.
+    Length,
+    /// Number of streamline endpoints (first and last points) in the voxel.
+    Endpoints,
+}
+
+/// Rasterize `streamlines` on the grid of `reference`.
+///
+/// Returns an image of the same shape as the first 3 dimensions of `reference`, indexed in voxel
+/// order `[i, j, k]`.
+pub fn track_density_image(
+    streamlines: &Streamlines,
+    reference: &NiftiHeader,
+    mode: TdiMode,
+) -> Result<Array3<f32>> {
+    let shape = [reference.dim[1] as usize, reference.dim[2] as usize, reference.dim[3] as usize];
+    let to_voxel = reference.affine::<f32>().try_inverse().ok_or(Error::AffineNotInvertible)?;
+    let to_voxel = |p: &Point| {
+        // Voxel centers are at integer coordinates. Shift by half a voxel so that `floor` gives
+        // the index of the voxel.
+        to_voxel.transform_point(p).coords + Vector3::repeat(0.5)
+    };
+    let in_grid = |voxel: [isize; 3]| -> Option<[usize; 3]> {
+        let mut index = [0; 3];
+        for axis in 0..3 {
+            if voxel[axis] < 0 || voxel[axis] as usize >= shape[axis] {
+                return None;
+            }
+            index[axis] = voxel[axis] as usize;
+        }
+        Some(index)
+    };
+
+    let mut image = Array3::zeros(shape);
+    let mut visited = vec![];
+    for streamline in streamlines {
+        let voxels = streamline.iter().map(to_voxel).collect::<Vec<_>>();
+        match mode {
+            TdiMode::Count => {
+                visited.clear();
+                visited.extend(voxels.first().map(voxel_of).and_then(in_grid));
+                for w in voxels.windows(2) {
+                    traverse_segment(&w[0], &w[1], |voxel, _| visited.extend(in_grid(voxel)));
+                }
+                visited.sort_unstable();
+                visited.dedup();
+                for &index in &visited {
+                    image[index] += 1.0;
+                }
+            }
+            TdiMode::Length => {
+                for (w, segment) in voxels.windows(2).zip(streamline.windows(2)) {
+                    let length = (segment[1] - segment[0]).norm();
+                    traverse_segment(&w[0], &w[1], |voxel, fraction| {
+                        if let Some(index) = in_grid(voxel) {
+                            image[index] += fraction * length;
+                        }
+                    });
+                }
+            }
+            TdiMode::Endpoints => {
+                // A single point is both endpoints, but it's counted only once
+                let endpoints = match voxels.len() {
+                    0 => vec![],
+                    1 => vec![&voxels[0]],
+                    n => vec![&voxels[0], &voxels[n - 1]],
+                };
+                for index in endpoints.into_iter().map(voxel_of).filter_map(in_grid) {
+                    image[index] += 1.0;
+                }
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Save `image` as a NIfTI file (`.nii` or `.nii.gz`) using the affine of `reference`.
+pub fn write_track_density_image<P: AsRef<Path>>(
+    path: P,
+    image: &Array3<f32>,
+    reference: &NiftiHeader,
+) -> Result<()> {
+    WriterOptions::new(path.as_ref()).reference_header(reference).write_nifti(image)?;
+    Ok(())
+}
+
+pub(crate) fn voxel_of(p: &Vector3<f32>) -> [isize; 3] {
+    [p.x.floor() as isize, p.y.floor() as isize, p.z.floor() as isize]
+}
+
+/// Call `visit` on all voxels crossed by the segment `[start, end]`, in order, with the fraction of
+/// the segment inside each voxel (Amanatides & Woo, 1987).
+pub(crate) fn traverse_segment<F>(start: &Vector3<f32>, end: &Vector3<f32>, mut visit: F)
+where
+    F: FnMut([isize; 3], f32),
+{
+    let direction = end - start;
+    let mut voxel = voxel_of(start);
+    let mut step = [0; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = ((voxel[axis] + 1) as f32 - start[axis]) / direction[axis];
+            t_delta[axis] = 1.0 / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (voxel[axis] as f32 - start[axis]) / direction[axis];
+            t_delta[axis] = -1.0 / direction[axis];
+        }
+    }
+
+    let mut t = 0.0;
+    loop {
+        let axis = (0..3).fold(0, |best, axis| if t_max[axis] < t_max[best] { axis } else { best });
+        if t_max[axis] >= 1.0 {
+            visit(voxel, 1.0 - t);
+            return;
+        }
+        visit(voxel, t_max[axis] - t);
+        t = t_max[axis];
+        voxel[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+    }
+}
@@ -1,6 +1,8 @@
+use std::ops::RangeBounds;
+
 use nalgebra::Point3;
 
-use crate::ArraySequence;
+use crate::{metrics, ArraySequence, ArraySequenceView};
 
 pub type Point = Point3<f32>;
 pub type Points = Vec<Point>;
@@ -9,7 +11,7 @@ pub type Streamlines = ArraySequence<Point>;
 pub type TractogramItem = (Points, ArraySequence<f32>, Vec<f32>);
 pub type RefTractogramItem<'data> = (&'data [Point], &'data [f32], &'data [f32]);
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Tractogram {
     pub streamlines: Streamlines,
     pub scalars: ArraySequence<f32>,
@@ -25,13 +27,287 @@ impl Tractogram {
         Tractogram { streamlines, scalars, properties }
     }
 
-    pub fn item(&self, idx: usize) -> RefTractogramItem {
+    pub fn item(&self, idx: usize) -> RefTractogramItem<'_> {
         // Do not use .get(idx).unwrap_or(). The empty slice is valid only if the ArraySequence are
         // empty. It should crash if the index is invalid.
         let scalars = if self.scalars.is_empty() { &[] } else { &self.scalars[idx] };
         let properties = if self.properties.is_empty() { &[] } else { &self.properties[idx] };
         (&self.streamlines[idx], scalars, properties)
     }
+
+    /// Borrow the streamlines in `range`, with their scalars and properties, without any copy.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn view<R: RangeBounds<usize> + Clone>(&self, range: R) -> TractogramView<'_> {
+        let all = TractogramView {
+            streamlines: self.streamlines.view(..),
+            scalars: self.scalars.view(..),
+            properties: self.properties.view(..),
+        };
+        all.view(range)
+    }
+
+    /// Copy the streamlines in `range`, with their scalars and properties, in a new `Tractogram`.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn slice<R: RangeBounds<usize> + Clone>(&self, range: R) -> Tractogram {
+        self.view(range).to_tractogram()
+    }
+
+    /// Copy the streamlines for which `predicate` returns `true`, with their scalars and
+    /// properties, in a new `Tractogram`.
+    pub fn filter<P>(&self, mut predicate: P) -> Tractogram
+    where
+        P: FnMut(RefTractogramItem) -> bool,
+    {
+        let keep = self.into_iter().map(&mut predicate).collect::<Vec<_>>();
+        let select_or_empty = |arr: &ArraySequence<f32>| {
+            if arr.is_empty() {
+                ArraySequence::empty()
+            } else {
+                select(arr, &keep)
+            }
+        };
+        Tractogram::new(
+            select(&self.streamlines, &keep),
+            select_or_empty(&self.scalars),
+            select_or_empty(&self.properties),
+        )
+    }
+
+    /// Copy the streamlines with a length (in mm, if in world space) in `[min_mm, max_mm]`, with
+    /// their scalars and properties, in a new `Tractogram`.
+    pub fn filter_by_length(&self, min_mm: f32, max_mm: f32) -> Tractogram {
+        self.filter(|(streamline, _, _)| {
+            let length = metrics::length(streamline);
+            min_mm <= length && length <= max_mm
+        })
+    }
+
+    /// Copy the streamlines with a number of points in `[min_points, max_points]`, with their
+    /// scalars and properties, in a new `Tractogram`.
+    pub fn filter_by_nb_points(&self, min_points: usize, max_points: usize) -> Tractogram {
+        self.filter(|(streamline, _, _)| (min_points..=max_points).contains(&streamline.len()))
+    }
+
+    /// Keep only the streamlines for which `f` returns `true`, with their scalars and properties.
+    ///
+    /// `f` is called exactly once per streamline, in order.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(RefTractogramItem) -> bool,
+    {
+        fn retain_by<T>(arr: &mut ArraySequence<T>, keep: &[bool]) {
+            let mut keep = keep.iter();
+            arr.retain(|_| *keep.next().unwrap());
+        }
+
+        let keep = self.into_iter().map(&mut f).collect::<Vec<_>>();
+        retain_by(&mut self.streamlines, &keep);
+        self.apply_to_data(|arr| retain_by(arr, &keep));
+    }
+
+    /// Remove and return streamline `i`, shifting all following streamlines.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn remove(&mut self, i: usize) -> TractogramItem {
+        let streamline = self.streamlines.remove(i);
+        self.removed_item(streamline, |arr| arr.remove(i))
+    }
+
+    /// Remove and return streamline `i`, replacing it by the last streamline.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn swap_remove(&mut self, i: usize) -> TractogramItem {
+        let streamline = self.streamlines.swap_remove(i);
+        self.removed_item(streamline, |arr| arr.swap_remove(i))
+    }
+
+    /// Insert a streamline, with its scalars and properties, at index `i`.
+    ///
+    /// Panics if `i > streamlines.len()`, or if `item` has scalars or properties but `self`
+    /// doesn't (and vice versa), unless `self` is empty.
+    pub fn insert(&mut self, i: usize, item: RefTractogramItem) {
+        let (streamline, scalars, properties) = item;
+        let nb_streamlines = self.streamlines.len();
+        insert_data(&mut self.scalars, nb_streamlines, i, scalars, "scalars");
+        insert_data(&mut self.properties, nb_streamlines, i, properties, "properties");
+        self.streamlines.insert(i, streamline);
+    }
+
+    /// Reverse the order of the points of streamline `i`, with their scalars.
+    ///
+    /// Panics if `i` is out of bounds.
+    pub fn reverse(&mut self, i: usize) {
+        self.streamlines[i].reverse();
+        let nb_points = self.streamlines.length_of_array(i);
+        if !self.scalars.is_empty() && nb_points > 0 {
+            let scalars = &mut self.scalars[i];
+            let nb_scalars = scalars.len() / nb_points;
+            // Reversing all floats also reverses the scalars of each point. Put them back in order.
+            scalars.reverse();
+            for point_scalars in scalars.chunks_mut(nb_scalars) {
+                point_scalars.reverse();
+            }
+        }
+    }
+
+    /// Keep only the first `len` streamlines, with their scalars and properties.
+    pub fn truncate(&mut self, len: usize) {
+        self.streamlines.truncate(len);
+        self.apply_to_data(|arr| arr.truncate(len));
+    }
+
+    /// Move all streamlines of `other`, with their scalars and properties, at the end of `self`,
+    /// leaving `other` empty.
+    ///
+    /// Panics if only one of the tractograms has scalars or properties, unless it's empty.
+    pub fn append(&mut self, other: &mut Tractogram) {
+        let nb_streamlines = (self.streamlines.len(), other.streamlines.len());
+        append_data(&mut self.scalars, &mut other.scalars, nb_streamlines, "scalars");
+        append_data(&mut self.properties, &mut other.properties, nb_streamlines, "properties");
+        self.streamlines.append(&mut other.streamlines);
+    }
+
+    /// Build a new `Tractogram` holding all streamlines of `tractograms`, in order.
+    pub fn concat<I>(tractograms: I) -> Tractogram
+    where
+        I: IntoIterator<Item = Tractogram>,
+    {
+        let mut new =
+            Tractogram::new(Streamlines::empty(), ArraySequence::empty(), ArraySequence::empty());
+        for mut tractogram in tractograms {
+            new.append(&mut tractogram);
+        }
+        new
+    }
+
+    /// Apply `f` to the scalars and to the properties, when there are some.
+    fn apply_to_data<F: FnMut(&mut ArraySequence<f32>)>(&mut self, mut f: F) {
+        if !self.scalars.is_empty() {
+            f(&mut self.scalars);
+        }
+        if !self.properties.is_empty() {
+            f(&mut self.properties);
+        }
+    }
+
+    fn removed_item<F>(&mut self, streamline: Points, mut remove: F) -> TractogramItem
+    where
+        F: FnMut(&mut ArraySequence<f32>) -> Vec<f32>,
+    {
+        let mut scalars = ArraySequence::empty();
+        if !self.scalars.is_empty() {
+            scalars.extend(remove(&mut self.scalars));
+        }
+        let properties =
+            if self.properties.is_empty() { vec![] } else { remove(&mut self.properties) };
+        (streamline, scalars, properties)
+    }
+}
+
+/// Borrowed range of streamlines of a `Tractogram`, with their scalars and properties, as returned
+/// by `Tractogram::view`.
+#[derive(Clone, Copy, Debug)]
+pub struct TractogramView<'data> {
+    pub streamlines: ArraySequenceView<'data, Point>,
+    pub scalars: ArraySequenceView<'data, f32>,
+    pub properties: ArraySequenceView<'data, f32>,
+}
+
+impl<'data> TractogramView<'data> {
+    pub fn len(&self) -> usize {
+        self.streamlines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.streamlines.is_empty()
+    }
+
+    /// Streamline `idx` of the view, with its scalars and properties.
+    pub fn item(&self, idx: usize) -> RefTractogramItem<'data> {
+        let scalars =
+            if self.scalars.is_empty() { &[] } else { self.scalars.view(idx..=idx).data() };
+        let properties =
+            if self.properties.is_empty() { &[] } else { self.properties.view(idx..=idx).data() };
+        (self.streamlines.view(idx..=idx).data(), scalars, properties)
+    }
+
+    /// Restrict the view to the streamlines in `range`, relative to this view.
+    ///
+    /// Panics if `range` is out of bounds.
+    pub fn view<R: RangeBounds<usize> + Clone>(&self, range: R) -> TractogramView<'data> {
+        // Empty scalars and properties stay empty
+        let view_or_empty = |arr: ArraySequenceView<'data, f32>| {
+            if arr.is_empty() {
+                arr
+            } else {
+                arr.view(range.clone())
+            }
+        };
+        TractogramView {
+            streamlines: self.streamlines.view(range.clone()),
+            scalars: view_or_empty(self.scalars),
+            properties: view_or_empty(self.properties),
+        }
+    }
+
+    /// Copy the viewed streamlines, with their scalars and properties, in a new `Tractogram`.
+    pub fn to_tractogram(&self) -> Tractogram {
+        Tractogram::new(
+            self.streamlines.to_array_sequence(),
+            self.scalars.to_array_sequence(),
+            self.properties.to_array_sequence(),
+        )
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = RefTractogramItem<'data>> + '_ {
+        (0..self.len()).map(|i| self.item(i))
+    }
+}
+
+/// Copy the arrays of `arr` for which `keep` is `true`. Contrary to `ArraySequence::filter`, empty
+/// arrays are kept, so that all `ArraySequence` of a `Tractogram` stay aligned.
+fn select<T: Clone>(arr: &ArraySequence<T>, keep: &[bool]) -> ArraySequence<T> {
+    let mut new = ArraySequence::empty();
+    for (array, _) in arr.iter().zip(keep).filter(|(_, &keep)| keep) {
+        new.data.extend_from_slice(array);
+        new.offsets.push(new.data.len());
+    }
+    new
+}
+
+/// Insert the scalars or properties of a new streamline, keeping them aligned with the streamlines.
+fn insert_data(
+    arr: &mut ArraySequence<f32>,
+    nb_streamlines: usize,
+    i: usize,
+    data: &[f32],
+    kind: &str,
+) {
+    if arr.is_empty() && data.is_empty() {
+        return;
+    }
+    if arr.len() != nb_streamlines {
+        panic!("Can't insert a streamline with {} in a tractogram without {}", kind, kind);
+    }
+    arr.insert(i, data);
+}
+
+/// Append the scalars or properties of a tractogram, keeping them aligned with the streamlines.
+fn append_data(
+    arr: &mut ArraySequence<f32>,
+    other: &mut ArraySequence<f32>,
+    (nb_streamlines, other_nb_streamlines): (usize, usize),
+    kind: &str,
+) {
+    if arr.is_empty() && other.is_empty() {
+        return;
+    }
+    if arr.len() != nb_streamlines || other.len() != other_nb_streamlines {
+        panic!("Can't append tractograms when only one of them has {}", kind);
+    }
+    arr.append(other);
 }
 
 impl<'data> IntoIterator for &'data Tractogram {
@@ -0,0 +1,204 @@
+use std::{
+    fs::{File, Metadata},
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    cheader::{Endianness, HEADER_SIZE},
+    error::path_error,
+    reader::read_up_to,
+    Error, Header, Result,
+};
+
+const INDEX_MAGIC: &[u8; 8] = b"TRKIDX\x00\x01";
+/// Magic, file size, modification time and number of positions.
+const INDEX_HEADER_SIZE: u64 = 8 + 8 + 16 + 8;
+
+/// Position of all streamlines in a TrackVis file.
+///
+/// The trk format has no offset table, so finding streamline `i` requires reading all preceding
+/// streamlines. A `TrkIndex` is built in one pass and lets a `Reader` seek directly to any
+/// streamline. It can be saved in a sidecar file, which is only considered valid as long as the
+/// size and modification time of the trk file don't change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrkIndex {
+    /// Byte position of each streamline (of its number of points), and of the end of the data.
+    positions: Vec<u64>,
+
+    file_size: u64,
+    /// Modification time of the trk file, in nanoseconds since the epoch. 0 if unknown.
+    modified: u128,
+}
+
+impl TrkIndex {
+    /// Find the position of all streamlines of a trk file. Only the number of points of each
+    /// streamline is read, everything else is skipped.
+    pub fn build<P: AsRef<Path>>(path: P) -> Result<TrkIndex> {
+        let f = File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let metadata = f.metadata()?;
+        let mut reader = BufReader::new(f);
+        let (header, endianness) = Header::read(&mut reader)?;
+
+        let floats_per_point = 3 + header.scalars_name.len();
+        let nb_properties = header.properties_name.len();
+        // A streamline takes at least 4 bytes, whatever `n_count` says
+        let nb_streamlines_hint = header.nb_streamlines.min(metadata.len() as usize / 4);
+        let mut positions = Vec::with_capacity(nb_streamlines_hint + 1);
+        let mut position = HEADER_SIZE as u64;
+        loop {
+            let mut bytes = [0u8; 4];
+            match read_up_to(&mut reader, &mut bytes)? {
+                0 => break,
+                4 => {}
+                _ => return Err(truncated(positions.len(), position)),
+            }
+
+            let nb_points = match endianness {
+                Endianness::Little => LittleEndian::read_i32(&bytes),
+                Endianness::Big => BigEndian::read_i32(&bytes),
+            };
+            if nb_points < 0 {
+                return Err(Error::InvalidNbPoints {
+                    streamline_index: positions.len(),
+                    nb_points,
+                });
+            }
+
+            let nb_floats = nb_points as usize * floats_per_point + nb_properties;
+            let next_position = position + 4 * (1 + nb_floats as u64);
+            if next_position > metadata.len() {
+                return Err(truncated(positions.len(), position));
+            }
+            reader.seek_relative(4 * nb_floats as i64)?;
+
+            positions.push(position);
+            position = next_position;
+        }
+        positions.push(position);
+
+        Ok(TrkIndex {
+            positions,
+            file_size: metadata.len(),
+            modified: modification_time(&metadata),
+        })
+    }
+
+    /// Load the sidecar index of `trk_path`, or build it if it doesn't exist or is outdated.
+    ///
+    /// A new sidecar is written when the index is built. Failing to write it is not an error,
+    /// because the directory may be read-only.
+    pub fn load_or_build<P: AsRef<Path>>(trk_path: P) -> Result<TrkIndex> {
+        let sidecar = TrkIndex::sidecar_path(&trk_path);
+        match TrkIndex::load(&sidecar, &trk_path) {
+            Ok(index) => Ok(index),
+            Err(_) => {
+                let index = TrkIndex::build(&trk_path)?;
+                let _ = index.save(&sidecar);
+                Ok(index)
+            }
+        }
+    }
+
+    /// Default path of the sidecar index of a trk file, e.g. `bundle.trk.idx`.
+    pub fn sidecar_path<P: AsRef<Path>>(trk_path: P) -> PathBuf {
+        let mut path = trk_path.as_ref().as_os_str().to_owned();
+        path.push(".idx");
+        PathBuf::from(path)
+    }
+
+    /// Save the index in a sidecar file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let f =
+            File::create(path.as_ref()).map_err(|e| path_error(e, "Failed to create", &path))?;
+        let mut writer = BufWriter::new(f);
+        writer.write_all(INDEX_MAGIC)?;
+        writer.write_u64::<LittleEndian>(self.file_size)?;
+        writer.write_u128::<LittleEndian>(self.modified)?;
+        writer.write_u64::<LittleEndian>(self.positions.len() as u64)?;
+        for &position in &self.positions {
+            writer.write_u64::<LittleEndian>(position)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a sidecar index and check that it still describes `trk_path`.
+    ///
+    /// Returns `Error::InvalidIndex` if the file is not an index or if the trk file has been
+    /// modified since the index was built.
+    pub fn load<P: AsRef<Path>, Q: AsRef<Path>>(path: P, trk_path: Q) -> Result<TrkIndex> {
+        let f = File::open(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+        let index_size = f.metadata()?.len();
+        let mut reader = BufReader::new(f);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic).map_err(invalid_index)?;
+        if &magic != INDEX_MAGIC {
+            return Err(Error::InvalidIndex("Not a trk index".to_string()));
+        }
+        let file_size = reader.read_u64::<LittleEndian>().map_err(invalid_index)?;
+        let modified = reader.read_u128::<LittleEndian>().map_err(invalid_index)?;
+        let metadata = std::fs::metadata(trk_path.as_ref())
+            .map_err(|e| path_error(e, "Failed to load", &trk_path))?;
+        if metadata.len() != file_size || modification_time(&metadata) != modified {
+            return Err(Error::InvalidIndex("The trk file has been modified".to_string()));
+        }
+
+        // Check the number of positions before allocating them. Each position is saved in 8
+        // bytes and each streamline takes at least 4 bytes in the trk file.
+        let nb_positions = reader.read_u64::<LittleEndian>().map_err(invalid_index)?;
+        if nb_positions == 0 {
+            return Err(Error::InvalidIndex("No end position".to_string()));
+        }
+        if nb_positions > index_size.saturating_sub(INDEX_HEADER_SIZE) / 8
+            || nb_positions > file_size / 4
+        {
+            return Err(Error::InvalidIndex(format!(
+                "Invalid number of positions {}",
+                nb_positions
+            )));
+        }
+        let mut positions = vec![0; nb_positions as usize];
+        reader.read_u64_into::<LittleEndian>(&mut positions).map_err(invalid_index)?;
+
+        Ok(TrkIndex { positions, file_size, modified })
+    }
+
+    /// Number of streamlines.
+    pub fn len(&self) -> usize {
+        self.positions.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Byte position of streamline `i`. `position(len())` is the end of the data.
+    pub fn position(&self, i: usize) -> u64 {
+        self.positions[i]
+    }
+}
+
+fn modification_time(metadata: &Metadata) -> u128 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos())
+}
+
+fn truncated(streamline_index: usize, byte_offset: u64) -> Error {
+    Error::Truncated { streamline_index, byte_offset }
+}
+
+fn invalid_index(e: std::io::Error) -> Error {
+    if e.kind() == ErrorKind::UnexpectedEof {
+        Error::InvalidIndex("Index file is truncated".to_string())
+    } else {
+        e.into()
+    }
+}
@@ -0,0 +1,553 @@
+//! TRX (tractography exchange) files, zipped or as a directory.
+//!
+//! TRX points are always in world space (RAS+ mm), so there's no affine to apply. The data per
+//! vertex (`dpv`) and the data per streamline (`dps`) are the scalars and the properties of the
+//! `Tractogram`. Unlike in a trk header, their names can be of any length and there can be any
+//! number of them. Like in a trk header, a multi-dimensional `dpv` or `dps`, e.g. a color, is
+//! repeated once per dimension in the names. Groups are kept as lists of streamline indices. The
+//! data per group (`dpg`) is ignored.
+//!
+//! All arrays are little-endian. Zipped files are written without compression (stored), so that
+//! other tools can map them in memory.
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use byteorder::{ByteOrder, LittleEndian};
+use half::f16;
+use serde_json::{json, Value};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    error::path_error,
+    header::{select_columns, split_names},
+    Affine4, ArraySequence, Error, Header, Point, Result, Streamlines, Tractogram,
+};
+
+/// Data type of the positions, i.e. of the coordinates of all points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionsType {
+    Float16,
+    Float32,
+    Float64,
+}
+
+/// Data type of the offsets, i.e. of the index of the first point of each streamline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OffsetsType {
+    UInt32,
+    UInt64,
+}
+
+/// A complete TRX file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trx {
+    /// Voxel to RAS+ mm transformation of the reference image.
+    pub voxel_to_rasmm: Affine4,
+    /// Shape of the reference image.
+    pub dimensions: [u16; 3],
+    pub tractogram: Tractogram,
+    pub scalars_name: Vec<String>,
+    pub properties_name: Vec<String>,
+    /// Name and streamline indices of each group.
+    pub groups: Vec<(String, Vec<u32>)>,
+    /// Data type used to save the positions. Kept from the loaded file.
+    pub positions_type: PositionsType,
+    /// Data type used to save the offsets. Kept from the loaded file. `UInt64` is used anyway if
+    /// the offsets don't fit in a `u32`.
+    pub offsets_type: OffsetsType,
+}
+
+impl Trx {
+    /// Build a TRX without any scalars, properties nor groups, saved as `Float32` and `UInt32`.
+    pub fn new(streamlines: Streamlines, voxel_to_rasmm: Affine4, dimensions: [u16; 3]) -> Trx {
+        Trx {
+            voxel_to_rasmm,
+            dimensions,
+            tractogram: Tractogram::new(
+                streamlines,
+                ArraySequence::empty(),
+                ArraySequence::empty(),
+            ),
+            scalars_name: vec![],
+            properties_name: vec![],
+            groups: vec![],
+            positions_type: PositionsType::Float32,
+            offsets_type: OffsetsType::UInt32,
+        }
+    }
+
+    /// Build a TRX from a trk `header` and a `tractogram` in world space, as read by a `Reader`.
+    /// The scalars and properties names are copied from `header`.
+    pub fn from_trk(header: &Header, tractogram: Tractogram) -> Trx {
+        let c_header = header.raw_header();
+        let voxel_to_rasmm =
+            Affine4::from_iterator(c_header.vox_to_ras.iter().cloned()).transpose();
+        let dimensions = c_header.dim.map(|d| d as u16);
+        Trx {
+            tractogram,
+            scalars_name: header.scalars_name.clone(),
+            properties_name: header.properties_name.clone(),
+            ..Trx::new(ArraySequence::empty(), voxel_to_rasmm, dimensions)
+        }
+    }
+
+    /// Build a trk header with the same reference space, scalars and properties.
+    ///
+    /// The names are kept even if they don't fit in a trk header, e.g. if they are longer than 20
+    /// characters. Writing a trk file with this header then returns an error.
+    pub fn trk_header(&self) -> Header {
+        let mut header = Header::from_affine4(&self.voxel_to_rasmm, self.dimensions);
+        header.nb_streamlines = self.tractogram.streamlines.len();
+        let _ = header.set_names(self.scalars_name.clone(), self.properties_name.clone());
+        header
+    }
+
+    /// Load a zipped TRX file, or a TRX directory.
+    ///
+    /// The files of a directory are read in alphabetical order, so the scalars and properties
+    /// may not be in the order in which they were saved.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Trx> {
+        let path = path.as_ref();
+        let files = if path.is_dir() { read_directory(path)? } else { read_zip(path)? };
+        Trx::from_files(files)
+    }
+
+    /// Save as a zipped TRX file, without compression.
+    pub fn save_zip<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        write_zip(path, self.to_files()?)
+    }
+
+    /// Save `tractogram`, in the reference space and with the names of a trk `header`, as a
+    /// zipped TRX file. Unlike `from_trk`, the tractogram isn't copied.
+    pub(crate) fn save_trk_zip<P: AsRef<Path>>(
+        path: P,
+        header: &Header,
+        tractogram: &Tractogram,
+    ) -> Result<()> {
+        let empty =
+            Tractogram::new(ArraySequence::empty(), ArraySequence::empty(), ArraySequence::empty());
+        write_zip(path, Trx::from_trk(header, empty).files(tractogram)?)
+    }
+
+    /// Save as a TRX directory. Files already in the directory are not removed.
+    pub fn save_directory<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        for (name, bytes) in self.to_files()? {
+            let file_path = path.as_ref().join(name);
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| path_error(e, "Failed to create", parent))?;
+            }
+            fs::write(&file_path, bytes)
+                .map_err(|e| path_error(e, "Failed to write", &file_path))?;
+        }
+        Ok(())
+    }
+
+    fn from_files(files: Vec<(String, Vec<u8>)>) -> Result<Trx> {
+        let header = files
+            .iter()
+            .find(|(name, _)| name == "header.json")
+            .ok_or_else(|| invalid("header.json is missing".to_string()))?;
+        let header: Value = serde_json::from_slice(&header.1)
+            .map_err(|e| invalid(format!("header.json can't be parsed: {}", e)))?;
+        let voxel_to_rasmm = parse_affine(&header["VOXEL_TO_RASMM"])?;
+        let dimensions = parse_dimensions(&header["DIMENSIONS"])?;
+        let nb_streamlines = parse_count(&header, "NB_STREAMLINES")?;
+        let nb_vertices = parse_count(&header, "NB_VERTICES")?;
+
+        let mut positions = None;
+        let mut offsets = None;
+        let (mut scalars, mut properties, mut groups) = (vec![], vec![], vec![]);
+        for (path, bytes) in &files {
+            let (folder, file) = match path.rsplit_once('/') {
+                Some((folder, file)) => (folder, file),
+                None => ("", path.as_str()),
+            };
+            if path == "header.json" || folder == "dpg" || folder.starts_with("dpg/") {
+                continue;
+            }
+            let (name, dim, dtype) = split_file_name(file)?;
+            match (folder, name) {
+                ("", "positions") => {
+                    if dim != 3 {
+                        return Err(invalid(format!(
+                            "positions must have 3 dimensions, not {}",
+                            dim
+                        )));
+                    }
+                    let positions_type = match dtype {
+                        "float16" => PositionsType::Float16,
+                        "float32" => PositionsType::Float32,
+                        "float64" => PositionsType::Float64,
+                        _ => {
+                            return Err(invalid(format!("Unsupported positions type {:?}", dtype)))
+                        }
+                    };
+                    positions = Some((decode_floats(bytes, dtype)?, positions_type));
+                }
+                ("", "offsets") => {
+                    let offsets_type = match dtype {
+                        "uint32" => OffsetsType::UInt32,
+                        "uint64" => OffsetsType::UInt64,
+                        _ => return Err(invalid(format!("Unsupported offsets type {:?}", dtype))),
+                    };
+                    offsets = Some((decode_indices(bytes, dtype)?, offsets_type));
+                }
+                ("dpv", _) => scalars.push((name, dim, decode_floats(bytes, dtype)?)),
+                ("dps", _) => properties.push((name, dim, decode_floats(bytes, dtype)?)),
+                ("groups", _) => groups.push((name.to_string(), decode_indices(bytes, dtype)?)),
+                _ => return Err(invalid(format!("Unknown file {:?}", path))),
+            }
+        }
+
+        let (positions, positions_type) =
+            positions.ok_or_else(|| invalid("positions are missing".to_string()))?;
+        if nb_vertices.checked_mul(3) != Some(positions.len()) {
+            return Err(invalid(format!(
+                "{} vertices are declared, but positions contains {} values",
+                nb_vertices,
+                positions.len()
+            )));
+        }
+        let (offsets, offsets_type) =
+            offsets.ok_or_else(|| invalid("offsets are missing".to_string()))?;
+        let lengths = lengths_from_offsets(&offsets, nb_streamlines, nb_vertices)?;
+        let points = positions.chunks_exact(3).map(|p| Point::new(p[0], p[1], p[2])).collect();
+        let streamlines = ArraySequence::new(lengths.clone(), points);
+
+        let (scalars_name, scalars) = interleave(scalars, nb_vertices, "dpv")?;
+        let (properties_name, properties) = interleave(properties, nb_streamlines, "dps")?;
+        let scalars = if scalars_name.is_empty() {
+            ArraySequence::empty()
+        } else {
+            ArraySequence::new(lengths.iter().map(|l| l * scalars_name.len()).collect(), scalars)
+        };
+        let properties = if properties_name.is_empty() {
+            ArraySequence::empty()
+        } else {
+            ArraySequence::new(vec![properties_name.len(); nb_streamlines], properties)
+        };
+
+        let groups = groups
+            .into_iter()
+            .map(|(name, indices)| {
+                if let Some(&i) = indices.iter().find(|&&i| i >= nb_streamlines as u64) {
+                    return Err(invalid(format!("Group {:?} contains streamline {}", name, i)));
+                }
+                Ok((name, indices.into_iter().map(|i| i as u32).collect()))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Trx {
+            voxel_to_rasmm,
+            dimensions,
+            tractogram: Tractogram::new(streamlines, scalars, properties),
+            scalars_name,
+            properties_name,
+            groups,
+            positions_type,
+            offsets_type,
+        })
+    }
+
+    fn to_files(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        self.files(&self.tractogram)
+    }
+
+    /// The files of `self`, with `tractogram` instead of `self.tractogram`.
+    fn files(&self, tractogram: &Tractogram) -> Result<Vec<(String, Vec<u8>)>> {
+        let Tractogram { streamlines, scalars, properties } = tractogram;
+        let (nb_streamlines, nb_vertices) = (streamlines.len(), streamlines.data.len());
+        check_names(&self.scalars_name, scalars.data.len(), nb_vertices, "scalars")?;
+        check_names(&self.properties_name, properties.data.len(), nb_streamlines, "properties")?;
+
+        let rows = (0..4)
+            .map(|r| (0..4).map(|c| self.voxel_to_rasmm[(r, c)]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let header = json!({
+            "VOXEL_TO_RASMM": rows,
+            "DIMENSIONS": self.dimensions,
+            "NB_STREAMLINES": nb_streamlines,
+            "NB_VERTICES": nb_vertices,
+        });
+        let header = serde_json::to_vec_pretty(&header).expect("A JSON value can be serialized");
+        let mut files = vec![("header.json".to_string(), header)];
+
+        let coordinates = streamlines.data.iter().flat_map(|p| p.coords.iter().cloned());
+        let positions = match self.positions_type {
+            PositionsType::Float16 => {
+                ("float16", encode(coordinates, |v| f16::from_f32(v).to_le_bytes()))
+            }
+            PositionsType::Float32 => ("float32", encode(coordinates, f32::to_le_bytes)),
+            PositionsType::Float64 => {
+                ("float64", encode(coordinates, |v| (v as f64).to_le_bytes()))
+            }
+        };
+        files.push((format!("positions.3.{}", positions.0), positions.1));
+
+        let offsets = streamlines.offsets[..nb_streamlines].iter().cloned();
+        let fits = nb_vertices <= u32::MAX as usize;
+        let offsets = match self.offsets_type {
+            OffsetsType::UInt32 if fits => {
+                ("uint32", encode(offsets, |o| (o as u32).to_le_bytes()))
+            }
+            _ => ("uint64", encode(offsets, |o| (o as u64).to_le_bytes())),
+        };
+        files.push((format!("offsets.{}", offsets.0), offsets.1));
+
+        for (name, start, dim) in split_names(&self.scalars_name)? {
+            let values = select_columns(&scalars.data, self.scalars_name.len(), start, dim);
+            files.push((file_name("dpv", name, dim), encode(values, f32::to_le_bytes)));
+        }
+        for (name, start, dim) in split_names(&self.properties_name)? {
+            let values = select_columns(&properties.data, self.properties_name.len(), start, dim);
+            files.push((file_name("dps", name, dim), encode(values, f32::to_le_bytes)));
+        }
+        for (name, indices) in &self.groups {
+            check_name(name)?;
+            if let Some(&i) = indices.iter().find(|&&i| i as usize >= nb_streamlines) {
+                return Err(invalid(format!("Group {:?} contains streamline {}", name, i)));
+            }
+            let indices = indices.iter().cloned();
+            files.push((format!("groups/{}.uint32", name), encode(indices, u32::to_le_bytes)));
+        }
+        Ok(files)
+    }
+}
+
+fn write_zip<P: AsRef<Path>>(path: P, files: Vec<(String, Vec<u8>)>) -> Result<()> {
+    let f = File::create(path.as_ref()).map_err(|e| path_error(e, "Failed to create", &path))?;
+    let mut zip = ZipWriter::new(BufWriter::new(f));
+    for (name, bytes) in files {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .large_file(bytes.len() as u64 >= u32::MAX as u64);
+        zip.start_file(name, options)?;
+        zip.write_all(&bytes)?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidHeader(msg)
+}
+
+fn parse_affine(value: &Value) -> Result<Affine4> {
+    let error = || invalid("VOXEL_TO_RASMM must be a 4x4 matrix".to_string());
+    let rows = value.as_array().filter(|rows| rows.len() == 4).ok_or_else(error)?;
+    let mut affine = Affine4::zeros();
+    for (r, row) in rows.iter().enumerate() {
+        let row = row.as_array().filter(|row| row.len() == 4).ok_or_else(error)?;
+        for (c, v) in row.iter().enumerate() {
+            affine[(r, c)] = v.as_f64().ok_or_else(error)? as f32;
+        }
+    }
+    Ok(affine)
+}
+
+fn parse_dimensions(value: &Value) -> Result<[u16; 3]> {
+    let error = || invalid("DIMENSIONS must be 3 positive integers".to_string());
+    let dimensions = value.as_array().filter(|dims| dims.len() == 3).ok_or_else(error)?;
+    let mut dims = [0; 3];
+    for (d, v) in dims.iter_mut().zip(dimensions) {
+        *d = v.as_u64().and_then(|v| u16::try_from(v).ok()).ok_or_else(error)?;
+    }
+    Ok(dims)
+}
+
+fn parse_count(header: &Value, key: &str) -> Result<usize> {
+    header[key]
+        .as_u64()
+        .map(|n| n as usize)
+        .ok_or_else(|| invalid(format!("{} must be a positive integer", key)))
+}
+
+fn read_zip(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let f = File::open(path).map_err(|e| path_error(e, "Failed to load", path))?;
+    let mut zip = ZipArchive::new(BufReader::new(f))?;
+    let mut files = Vec::with_capacity(zip.len());
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        // Don't trust the declared size to allocate, it may be corrupted
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        files.push((file.name().to_string(), bytes));
+    }
+    Ok(files)
+}
+
+fn read_directory(path: &Path) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut files = vec![];
+    let mut folders = vec![(path.to_path_buf(), String::new())];
+    while let Some((folder, prefix)) = folders.pop() {
+        let entries =
+            fs::read_dir(&folder).map_err(|e| path_error(e, "Failed to load", &folder))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if entry.file_type()?.is_dir() {
+                folders.push((entry.path(), format!("{}/", name)));
+            } else {
+                let bytes = fs::read(entry.path())
+                    .map_err(|e| path_error(e, "Failed to load", entry.path()))?;
+                files.push((name, bytes));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// Split a file name in its name, its number of dimensions and its data type, e.g. `fa.float32`
+/// or `color.3.uint8`.
+fn split_file_name(file: &str) -> Result<(&str, usize, &str)> {
+    let (rest, dtype) =
+        file.rsplit_once('.').ok_or_else(|| invalid(format!("{:?} has no data type", file)))?;
+    let (name, dim) = match rest.rsplit_once('.').map(|(n, d)| (n, d.parse::<usize>())) {
+        Some((name, Ok(dim))) => (name, dim),
+        _ => (rest, 1),
+    };
+    if dim == 0 {
+        return Err(invalid(format!("{:?} has 0 dimension", file)));
+    }
+    Ok((name, dim, dtype))
+}
+
+fn decode_floats(bytes: &[u8], dtype: &str) -> Result<Vec<f32>> {
+    let values = match dtype {
+        "float16" => decode(bytes, 2, |b| f16::from_le_bytes([b[0], b[1]]).to_f32()),
+        "float32" => decode(bytes, 4, LittleEndian::read_f32),
+        "float64" => decode(bytes, 8, |b| LittleEndian::read_f64(b) as f32),
+        "int8" => decode(bytes, 1, |b| b[0] as i8 as f32),
+        "int16" => decode(bytes, 2, |b| LittleEndian::read_i16(b) as f32),
+        "int32" => decode(bytes, 4, |b| LittleEndian::read_i32(b) as f32),
+        "int64" => decode(bytes, 8, |b| LittleEndian::read_i64(b) as f32),
+        "uint8" => decode(bytes, 1, |b| b[0] as f32),
+        "uint16" => decode(bytes, 2, |b| LittleEndian::read_u16(b) as f32),
+        "uint32" => decode(bytes, 4, |b| LittleEndian::read_u32(b) as f32),
+        "uint64" => decode(bytes, 8, |b| LittleEndian::read_u64(b) as f32),
+        _ => return Err(invalid(format!("Unsupported data type {:?}", dtype))),
+    };
+    values.ok_or_else(|| invalid(format!("Data size isn't a multiple of {:?}", dtype)))
+}
+
+fn decode_indices(bytes: &[u8], dtype: &str) -> Result<Vec<u64>> {
+    let values = match dtype {
+        "uint8" => decode(bytes, 1, |b| b[0] as u64),
+        "uint16" => decode(bytes, 2, |b| LittleEndian::read_u16(b) as u64),
+        "uint32" => decode(bytes, 4, |b| LittleEndian::read_u32(b) as u64),
+        "uint64" => decode(bytes, 8, LittleEndian::read_u64),
+        _ => return Err(invalid(format!("Unsupported index type {:?}", dtype))),
+    };
+    values.ok_or_else(|| invalid(format!("Data size isn't a multiple of {:?}", dtype)))
+}
+
+fn decode<T, F: Fn(&[u8]) -> T>(bytes: &[u8], size: usize, f: F) -> Option<Vec<T>> {
+    if bytes.len() % size != 0 {
+        return None;
+    }
+    Some(bytes.chunks_exact(size).map(f).collect())
+}
+
+fn encode<T, I, F, const N: usize>(values: I, f: F) -> Vec<u8>
+where
+    I: Iterator<Item = T>,
+    F: Fn(T) -> [u8; N],
+{
+    values.flat_map(f).collect()
+}
+
+/// Number of points of each streamline. The offsets may or may not end with `nb_vertices`.
+fn lengths_from_offsets(
+    offsets: &[u64],
+    nb_streamlines: usize,
+    nb_vertices: usize,
+) -> Result<Vec<usize>> {
+    let offsets = match offsets.len() {
+        n if n == nb_streamlines => offsets,
+        n if n.checked_sub(1) == Some(nb_streamlines) && offsets[n - 1] == nb_vertices as u64 => {
+            &offsets[..n - 1]
+        }
+        n => {
+            return Err(invalid(format!(
+                "{} streamlines are declared, but offsets contains {} values",
+                nb_streamlines, n
+            )))
+        }
+    };
+    // `offsets` was read from the file, unlike `nb_streamlines`
+    let mut lengths = Vec::with_capacity(offsets.len());
+    for (i, &start) in offsets.iter().enumerate() {
+        let end = offsets.get(i + 1).cloned().unwrap_or(nb_vertices as u64);
+        if start > end || end > nb_vertices as u64 {
+            return Err(invalid(format!("Offsets of streamline {} are invalid", i)));
+        }
+        lengths.push((end - start) as usize);
+    }
+    Ok(lengths)
+}
+
+/// Expand the multi-dimensional arrays and interleave them, so that all the values of a vertex
+/// (or of a streamline) are contiguous.
+fn interleave(
+    arrays: Vec<(&str, usize, Vec<f32>)>,
+    nb_items: usize,
+    kind: &str,
+) -> Result<(Vec<String>, Vec<f32>)> {
+    let mut names = vec![];
+    for (name, dim, values) in &arrays {
+        if dim.checked_mul(nb_items) != Some(values.len()) {
+            return Err(invalid(format!(
+                "{}/{} contains {} values, but {} values of {} items were expected",
+                kind,
+                name,
+                values.len(),
+                dim,
+                nb_items
+            )));
+        }
+        names.extend(std::iter::repeat_n(name.to_string(), *dim));
+    }
+    let mut data = Vec::with_capacity(names.len() * nb_items);
+    for i in 0..nb_items {
+        for (_, dim, values) in &arrays {
+            data.extend_from_slice(&values[i * dim..(i + 1) * dim]);
+        }
+    }
+    Ok((names, data))
+}
+
+fn file_name(folder: &str, name: &str, dim: usize) -> String {
+    match dim {
+        1 => format!("{}/{}.float32", folder, name),
+        _ => format!("{}/{}.{}.float32", folder, name, dim),
+    }
+}
+
+fn check_names(names: &[String], nb_values: usize, nb_items: usize, kind: &str) -> Result<()> {
+    if names.len() * nb_items != nb_values {
+        return Err(invalid(format!(
+            "{} {} names for {} values per item",
+            names.len(),
+            kind,
+            nb_values.checked_div(nb_items).unwrap_or(0)
+        )));
+    }
+    names.iter().try_for_each(|name| check_name(name))
+}
+
+/// A name is saved as a file name, so it can't be empty or contain a path separator.
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(Error::InvalidName(format!("{:?} can't be used as a TRX file name", name)));
+    }
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::{Header, Points, Reader, Spacing, StreamlinesIter};
+use crate::{Header, Points, Reader, Result, Spacing, StreamlinesIter};
 
 /// Generator to read a TrackVis file, streamline per streamline.
 ///
@@ -13,21 +13,20 @@ impl VoxelSpaceReader {
     /// * `path` - Path to TrackVis file
     /// * `spacing` - Spacing (pixel dimension `pixdim`) obtained from the `Header` or from a
     ///   reference image.
-    pub fn new<P: AsRef<std::path::Path>>(path: P, spacing: Spacing) -> (Header, VoxelSpaceReader) {
-        let reader = Reader::new(path).unwrap().to_voxel_space(spacing);
+    pub fn new<P: AsRef<std::path::Path>>(
+        path: P,
+        spacing: Spacing,
+    ) -> Result<(Header, VoxelSpaceReader)> {
+        let reader = Reader::new(path)?.to_voxel_space(spacing);
         let header = reader.header.clone();
-        (header, VoxelSpaceReader { reader: reader.into_streamlines_iter() })
+        Ok((header, VoxelSpaceReader { reader: reader.into_streamlines_iter() }))
     }
 }
 
 impl Iterator for VoxelSpaceReader {
-    type Item = Points;
+    type Item = Result<Points>;
 
-    fn next(&mut self) -> Option<Points> {
-        if let Some(points) = self.reader.next() {
-            return Some(points);
-        } else {
-            return None;
-        }
+    fn next(&mut self) -> Option<Result<Points>> {
+        self.reader.next()
     }
 }
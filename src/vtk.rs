@@ -0,0 +1,716 @@
+//! Legacy VTK files (`.vtk`), ASCII or binary, holding streamlines as the `LINES` of a
+//! `POLYDATA` dataset.
+//!
+//! VTK points don't have any affine, so they are read and written as-is. A `Tractogram` read by a
+//! `Reader` is in world space (RAS+ mm), which is what most tools expect. The scalars are saved in
+//! `POINT_DATA` and the properties in `CELL_DATA`, one array per name. Like in a trk header, a
+//! multi-component array, e.g. a color, is repeated once per component in the names.
+//!
+//! When reading, the points can be shared or in any order, and both the classic `LINES` layout and
+//! the `OFFSETS`/`CONNECTIVITY` layout of VTK 5.1 are supported. The other cells (vertices,
+//! polygons and strips) are ignored.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{
+    error::path_error,
+    header::{select_columns, split_names},
+    ArraySequence, Error, Header, Point, Result, Tractogram,
+};
+
+/// Encoding of the data of a legacy VTK file. Binary data is big-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VtkFormat {
+    Ascii,
+    Binary,
+}
+
+/// Load the streamlines of a legacy VTK file, with their point and cell data.
+///
+/// The returned header only holds the number of streamlines and the scalars and properties
+/// names. Names that don't fit in a trk header are kept, but writing a trk file with this header
+/// will return an error.
+pub fn read_vtk<P: AsRef<Path>>(path: P) -> Result<(Header, Tractogram)> {
+    let bytes = std::fs::read(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+    LegacyParser::new(&bytes).parse()?.into_tractogram()
+}
+
+/// Save `tractogram` as a legacy VTK file. The scalars and properties names are read from
+/// `header`.
+pub fn write_vtk<P: AsRef<Path>>(
+    path: P,
+    tractogram: &Tractogram,
+    header: &Header,
+    format: VtkFormat,
+) -> Result<()> {
+    let poly_data = PolyData::from_tractogram(tractogram, header)?;
+    if poly_data.connectivity.len() > i32::MAX as usize {
+        return Err(Error::InvalidHeader("Too many points for a legacy VTK file".to_string()));
+    }
+    for array in poly_data.point_data.iter().chain(&poly_data.cell_data) {
+        if array.name.contains(char::is_whitespace) {
+            return Err(Error::InvalidName(format!(
+                "{:?} can't be saved in a legacy VTK file",
+                array.name
+            )));
+        }
+    }
+
+    let f = File::create(path.as_ref()).map_err(|e| path_error(e, "Failed to create", &path))?;
+    let mut writer = BufWriter::new(f);
+    let format_name = match format {
+        VtkFormat::Ascii => "ASCII",
+        VtkFormat::Binary => "BINARY",
+    };
+    write!(writer, "# vtk DataFile Version 3.0\ntrk-io\n{}\nDATASET POLYDATA\n", format_name)?;
+
+    let nb_points = poly_data.points.len();
+    let nb_lines = poly_data.nb_lines();
+    writeln!(writer, "POINTS {} float", nb_points)?;
+    let coordinates = poly_data.points.iter().flat_map(|p| p.coords.iter().cloned());
+    write_floats(&mut writer, format, coordinates, 3)?;
+
+    writeln!(writer, "LINES {} {}", nb_lines, nb_lines + poly_data.connectivity.len())?;
+    match format {
+        VtkFormat::Ascii => {
+            for w in poly_data.offsets.windows(2) {
+                write!(writer, "{}", w[1] - w[0])?;
+                for i in &poly_data.connectivity[w[0]..w[1]] {
+                    write!(writer, " {}", i)?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        VtkFormat::Binary => {
+            let mut buffer = [0; 4];
+            for w in poly_data.offsets.windows(2) {
+                BigEndian::write_i32(&mut buffer, (w[1] - w[0]) as i32);
+                writer.write_all(&buffer)?;
+                for &i in &poly_data.connectivity[w[0]..w[1]] {
+                    BigEndian::write_i32(&mut buffer, i as i32);
+                    writer.write_all(&buffer)?;
+                }
+            }
+            writeln!(writer)?;
+        }
+    }
+
+    for (kind, arrays, nb_items) in [
+        ("POINT_DATA", &poly_data.point_data, nb_points),
+        ("CELL_DATA", &poly_data.cell_data, nb_lines),
+    ] {
+        if arrays.is_empty() {
+            continue;
+        }
+        writeln!(writer, "{} {}", kind, nb_items)?;
+        writeln!(writer, "FIELD FieldData {}", arrays.len())?;
+        for array in arrays {
+            writeln!(writer, "{} {} {} float", array.name, array.nb_components, nb_items)?;
+            write_floats(&mut writer, format, array.values.iter().cloned(), array.nb_components)?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn write_floats<W: Write, I: Iterator<Item = f32>>(
+    writer: &mut W,
+    format: VtkFormat,
+    values: I,
+    per_line: usize,
+) -> Result<()> {
+    match format {
+        VtkFormat::Ascii => {
+            for (i, v) in values.enumerate() {
+                let separator = if (i + 1) % per_line == 0 { '\n' } else { ' ' };
+                write!(writer, "{}{}", v, separator)?;
+            }
+        }
+        VtkFormat::Binary => {
+            for v in values {
+                writer.write_all(&v.to_be_bytes())?;
+            }
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// A named array of `nb_components` values per point or per cell.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DataArray {
+    pub name: String,
+    pub nb_components: usize,
+    pub values: Vec<f32>,
+}
+
+impl DataArray {
+    /// Keep only the rows `[start, start + count)`. Returns an error if they don't exist.
+    pub(crate) fn rows(mut self, start: usize, count: usize) -> Result<DataArray> {
+        let start = product(start, self.nb_components)?;
+        let end = product(count, self.nb_components)?.saturating_add(start);
+        if self.values.len() < end {
+            return Err(Error::InvalidHeader(format!(
+                "Array {:?} has {} values, but at least {} were expected",
+                self.name,
+                self.values.len(),
+                end
+            )));
+        }
+        self.values.truncate(end);
+        self.values.drain(..start);
+        Ok(self)
+    }
+}
+
+/// The lines of a VTK dataset, with their point and cell data.
+///
+/// `offsets` always starts with 0 and ends with the length of `connectivity`, like the offsets of
+/// an `ArraySequence`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PolyData {
+    pub points: Vec<Point>,
+    pub connectivity: Vec<usize>,
+    pub offsets: Vec<usize>,
+    pub point_data: Vec<DataArray>,
+    pub cell_data: Vec<DataArray>,
+}
+
+impl PolyData {
+    pub(crate) fn nb_lines(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub(crate) fn from_tractogram(tractogram: &Tractogram, header: &Header) -> Result<PolyData> {
+        let Tractogram { streamlines, scalars, properties } = tractogram;
+        let (nb_lines, nb_points) = (streamlines.len(), streamlines.data.len());
+        let point_data = data_arrays(&header.scalars_name, &scalars.data, nb_points, "scalars")?;
+        let cell_data =
+            data_arrays(&header.properties_name, &properties.data, nb_lines, "properties")?;
+        Ok(PolyData {
+            points: streamlines.data.clone(),
+            connectivity: (0..nb_points).collect(),
+            offsets: streamlines.offsets.clone(),
+            point_data,
+            cell_data,
+        })
+    }
+
+    /// Build the streamlines by following the connectivity of each line, and a header with the
+    /// names of the point and cell data.
+    pub(crate) fn into_tractogram(self) -> Result<(Header, Tractogram)> {
+        let nb_points = self.points.len();
+        let nb_lines = self.nb_lines();
+        if let Some(&i) = self.connectivity.iter().find(|&&i| i >= nb_points) {
+            return Err(Error::InvalidHeader(format!(
+                "A line uses point {}, but there are only {} points",
+                i, nb_points
+            )));
+        }
+        if self.offsets[0] != 0
+            || self.offsets.windows(2).any(|w| w[0] > w[1])
+            || self.offsets[nb_lines] != self.connectivity.len()
+        {
+            return Err(Error::InvalidHeader("The lines offsets are invalid".to_string()));
+        }
+        for (arrays, nb_items) in [(&self.point_data, nb_points), (&self.cell_data, nb_lines)] {
+            for array in arrays {
+                let nb_values = product(array.nb_components, nb_items)?;
+                if array.values.len() != nb_values {
+                    return Err(Error::InvalidHeader(format!(
+                        "Array {:?} has {} values, but {} were expected",
+                        array.name,
+                        array.values.len(),
+                        nb_values
+                    )));
+                }
+            }
+        }
+
+        // The names may not fit in a trk header, which is only a problem when saving a trk file
+        let names = |arrays: &[DataArray]| -> Vec<String> {
+            arrays
+                .iter()
+                .flat_map(|array| std::iter::repeat_n(array.name.clone(), array.nb_components))
+                .collect()
+        };
+        let mut header = Header::default();
+        header.nb_streamlines = nb_lines;
+        let _ = header.set_names(names(&self.point_data), names(&self.cell_data));
+
+        let lengths = self.offsets.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        let points = self.connectivity.iter().map(|&i| self.points[i]).collect();
+        let streamlines = ArraySequence::new(lengths.clone(), points);
+        let scalars = if self.point_data.is_empty() {
+            ArraySequence::empty()
+        } else {
+            let nb_scalars = header.scalars_name.len();
+            let mut data = Vec::with_capacity(self.connectivity.len() * nb_scalars);
+            for &i in &self.connectivity {
+                for array in &self.point_data {
+                    let n = array.nb_components;
+                    data.extend_from_slice(&array.values[i * n..(i + 1) * n]);
+                }
+            }
+            ArraySequence::new(lengths.iter().map(|l| l * nb_scalars).collect(), data)
+        };
+        let properties = if self.cell_data.is_empty() {
+            ArraySequence::empty()
+        } else {
+            let nb_properties = header.properties_name.len();
+            let mut data = Vec::with_capacity(nb_lines * nb_properties);
+            for i in 0..nb_lines {
+                for array in &self.cell_data {
+                    let n = array.nb_components;
+                    data.extend_from_slice(&array.values[i * n..(i + 1) * n]);
+                }
+            }
+            ArraySequence::new(vec![nb_properties; nb_lines], data)
+        };
+        Ok((header, Tractogram::new(streamlines, scalars, properties)))
+    }
+}
+
+fn data_arrays(
+    names: &[String],
+    data: &[f32],
+    nb_items: usize,
+    kind: &str,
+) -> Result<Vec<DataArray>> {
+    if names.len() * nb_items != data.len() {
+        return Err(Error::InvalidHeader(format!(
+            "The header has {} {} names, but the tractogram has {} values per item",
+            names.len(),
+            kind,
+            data.len().checked_div(nb_items).unwrap_or(0)
+        )));
+    }
+    let arrays = split_names(names)?
+        .into_iter()
+        .map(|(name, start, dim)| DataArray {
+            name: name.to_string(),
+            nb_components: dim,
+            values: select_columns(data, names.len(), start, dim).collect(),
+        })
+        .collect();
+    Ok(arrays)
+}
+
+/// Numeric types of VTK arrays.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DataType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float32,
+    Float64,
+}
+
+impl DataType {
+    /// Parse a type name of a legacy VTK file.
+    fn from_legacy(name: &str) -> Result<DataType> {
+        match name {
+            "char" | "vtktypeint8" => Ok(DataType::Int8),
+            "unsigned_char" | "vtktypeuint8" => Ok(DataType::UInt8),
+            "short" | "vtktypeint16" => Ok(DataType::Int16),
+            "unsigned_short" | "vtktypeuint16" => Ok(DataType::UInt16),
+            "int" | "vtktypeint32" => Ok(DataType::Int32),
+            "unsigned_int" | "vtktypeuint32" => Ok(DataType::UInt32),
+            "long" | "vtkIdType" | "vtktypeint64" => Ok(DataType::Int64),
+            "unsigned_long" | "vtktypeuint64" => Ok(DataType::UInt64),
+            "float" | "vtktypefloat32" => Ok(DataType::Float32),
+            "double" | "vtktypefloat64" => Ok(DataType::Float64),
+            _ => Err(Error::InvalidHeader(format!("Unsupported VTK data type {:?}", name))),
+        }
+    }
+
+    /// Parse a type name of a VTK XML file.
+    pub(crate) fn from_xml(name: &str) -> Result<DataType> {
+        match name {
+            "Int8" | "Char" => Ok(DataType::Int8),
+            "UInt8" | "UChar" => Ok(DataType::UInt8),
+            "Int16" => Ok(DataType::Int16),
+            "UInt16" => Ok(DataType::UInt16),
+            "Int32" => Ok(DataType::Int32),
+            "UInt32" => Ok(DataType::UInt32),
+            "Int64" => Ok(DataType::Int64),
+            "UInt64" => Ok(DataType::UInt64),
+            "Float32" => Ok(DataType::Float32),
+            "Float64" => Ok(DataType::Float64),
+            _ => Err(Error::InvalidHeader(format!("Unsupported VTK data type {:?}", name))),
+        }
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            DataType::Int8 | DataType::UInt8 => 1,
+            DataType::Int16 | DataType::UInt16 => 2,
+            DataType::Int32 | DataType::UInt32 | DataType::Float32 => 4,
+            DataType::Int64 | DataType::UInt64 | DataType::Float64 => 8,
+        }
+    }
+}
+
+/// Decode `bytes`, an array of `dtype`, to `f32`.
+pub(crate) fn decode_floats(bytes: &[u8], dtype: DataType, big_endian: bool) -> Vec<f32> {
+    if big_endian {
+        decode::<BigEndian, _, _>(bytes, dtype, |v| v as f32, |v| v as f32)
+    } else {
+        decode::<LittleEndian, _, _>(bytes, dtype, |v| v as f32, |v| v as f32)
+    }
+}
+
+/// Decode `bytes`, an array of `dtype`, to indices. Returns an error for negative or fractional
+/// values.
+pub(crate) fn decode_indices(
+    bytes: &[u8],
+    dtype: DataType,
+    big_endian: bool,
+) -> Result<Vec<usize>> {
+    let values = if big_endian {
+        decode::<BigEndian, _, _>(bytes, dtype, |v| usize::try_from(v).ok(), float_to_index)
+    } else {
+        decode::<LittleEndian, _, _>(bytes, dtype, |v| usize::try_from(v).ok(), float_to_index)
+    };
+    values
+        .into_iter()
+        .collect::<Option<_>>()
+        .ok_or_else(|| Error::InvalidHeader("Invalid index in a VTK array".to_string()))
+}
+
+fn float_to_index(v: f64) -> Option<usize> {
+    if v >= 0.0 && v.fract() == 0.0 {
+        Some(v as usize)
+    } else {
+        None
+    }
+}
+
+fn decode<E, T, F>(
+    bytes: &[u8],
+    dtype: DataType,
+    from_integer: F,
+    from_float: fn(f64) -> T,
+) -> Vec<T>
+where
+    E: ByteOrder,
+    F: Fn(i128) -> T,
+{
+    let chunks = bytes.chunks_exact(dtype.size());
+    match dtype {
+        DataType::Int8 => chunks.map(|b| from_integer(b[0] as i8 as i128)).collect(),
+        DataType::UInt8 => chunks.map(|b| from_integer(b[0] as i128)).collect(),
+        DataType::Int16 => chunks.map(|b| from_integer(E::read_i16(b) as i128)).collect(),
+        DataType::UInt16 => chunks.map(|b| from_integer(E::read_u16(b) as i128)).collect(),
+        DataType::Int32 => chunks.map(|b| from_integer(E::read_i32(b) as i128)).collect(),
+        DataType::UInt32 => chunks.map(|b| from_integer(E::read_u32(b) as i128)).collect(),
+        DataType::Int64 => chunks.map(|b| from_integer(E::read_i64(b) as i128)).collect(),
+        DataType::UInt64 => chunks.map(|b| from_integer(E::read_u64(b) as i128)).collect(),
+        DataType::Float32 => chunks.map(|b| from_float(E::read_f32(b) as f64)).collect(),
+        DataType::Float64 => chunks.map(|b| from_float(E::read_f64(b))).collect(),
+    }
+}
+
+/// Parse `tokens`, one number each, to `f32`.
+pub(crate) fn parse_floats<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Result<Vec<f32>> {
+    tokens.map(parse_number::<f32>).collect()
+}
+
+/// Parse `tokens`, one number each, to indices.
+pub(crate) fn parse_indices<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Result<Vec<usize>> {
+    tokens.map(parse_number::<usize>).collect()
+}
+
+fn parse_number<T: FromStr>(token: &str) -> Result<T> {
+    token.parse().map_err(|_| Error::InvalidHeader(format!("{:?} is not a valid number", token)))
+}
+
+/// Reads a legacy VTK file, keyword by keyword.
+struct LegacyParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    binary: bool,
+    /// Number of points or cells declared by the last `POINT_DATA` or `CELL_DATA`.
+    nb_items: usize,
+}
+
+impl<'a> LegacyParser<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        LegacyParser { bytes, pos: 0, binary: false, nb_items: 0 }
+    }
+
+    fn parse(mut self) -> Result<PolyData> {
+        if !self.line()?.starts_with("# vtk DataFile") {
+            return Err(Error::BadMagic);
+        }
+        let _title = self.line()?;
+        self.binary = match self.line()?.trim() {
+            "ASCII" => false,
+            "BINARY" => true,
+            format => return Err(invalid(format!("Unknown VTK format {:?}", format))),
+        };
+        match (self.token()?, self.token()?) {
+            ("DATASET", "POLYDATA") => {}
+            (_, dataset) => return Err(invalid(format!("Unsupported VTK dataset {:?}", dataset))),
+        }
+
+        let mut points = vec![];
+        let mut lines = None;
+        let mut nb_vertices = 0;
+        let (mut point_data, mut cell_data) = (vec![], vec![]);
+        let mut in_cell_data = false;
+        while let Some(keyword) = self.next_token()? {
+            match keyword {
+                "POINTS" => {
+                    let nb_points: usize = self.number()?;
+                    let dtype = DataType::from_legacy(self.token()?)?;
+                    let coordinates = self.floats(product(3, nb_points)?, dtype)?;
+                    points =
+                        coordinates.chunks_exact(3).map(|p| Point::new(p[0], p[1], p[2])).collect();
+                }
+                "LINES" => lines = Some(self.cells()?),
+                "VERTICES" => nb_vertices = self.cells()?.1.len() - 1,
+                "POLYGONS" | "TRIANGLE_STRIPS" => {
+                    self.cells()?;
+                }
+                "POINT_DATA" | "CELL_DATA" => {
+                    in_cell_data = keyword == "CELL_DATA";
+                    self.nb_items = self.number()?;
+                }
+                "SCALARS" | "VECTORS" | "NORMALS" | "TENSORS" | "COLOR_SCALARS" | "FIELD" => {
+                    let arrays = self.attribute(keyword)?;
+                    if in_cell_data {
+                        cell_data.extend(arrays);
+                    } else {
+                        point_data.extend(arrays);
+                    }
+                }
+                "LOOKUP_TABLE" => {
+                    let _name = self.token()?;
+                    let size: usize = self.number()?;
+                    if self.binary {
+                        self.binary_bytes(product(4, size)?)?;
+                    } else {
+                        self.floats(product(4, size)?, DataType::Float32)?;
+                    }
+                }
+                "METADATA" => self.skip_metadata()?,
+                _ => return Err(invalid(format!("Unsupported VTK keyword {:?}", keyword))),
+            }
+        }
+
+        let (connectivity, offsets) = lines.unwrap_or((vec![], vec![0]));
+        let nb_lines = offsets.len() - 1;
+        let cell_data = cell_data
+            .into_iter()
+            .map(|array: DataArray| array.rows(nb_vertices, nb_lines))
+            .collect::<Result<_>>()?;
+        Ok(PolyData { points, connectivity, offsets, point_data, cell_data })
+    }
+
+    /// Read the cells after `LINES`, `VERTICES`, etc. as connectivity and offsets.
+    fn cells(&mut self) -> Result<(Vec<usize>, Vec<usize>)> {
+        let (first, second): (usize, usize) = (self.number()?, self.number()?);
+        if self.peek_token()? == Some("OFFSETS") {
+            // VTK 5.1: `first` offsets, starting with 0, then `second` indices
+            self.token()?;
+            let dtype = DataType::from_legacy(self.token()?)?;
+            let offsets = self.indices(first, dtype)?;
+            if self.token()? != "CONNECTIVITY" {
+                return Err(invalid("CONNECTIVITY must follow OFFSETS".to_string()));
+            }
+            let dtype = DataType::from_legacy(self.token()?)?;
+            let connectivity = self.indices(second, dtype)?;
+            if offsets.is_empty() {
+                return Err(invalid("OFFSETS must not be empty".to_string()));
+            }
+            return Ok((connectivity, offsets));
+        }
+
+        // Classic layout: `first` cells, saved as `second` numbers, each cell being its number of
+        // points followed by their indices.
+        let numbers = self.indices(second, DataType::Int32)?;
+        if first > second {
+            return Err(invalid("Truncated cells".to_string()));
+        }
+        let mut connectivity = Vec::with_capacity(second.saturating_sub(first));
+        let mut offsets = Vec::with_capacity(first + 1);
+        offsets.push(0);
+        let mut rest = &numbers[..];
+        for _ in 0..first {
+            let (&n, tail) = rest.split_first().ok_or_else(|| invalid("Truncated cells".into()))?;
+            if tail.len() < n {
+                return Err(invalid("Truncated cells".to_string()));
+            }
+            connectivity.extend_from_slice(&tail[..n]);
+            offsets.push(connectivity.len());
+            rest = &tail[n..];
+        }
+        Ok((connectivity, offsets))
+    }
+
+    /// Read the arrays of a dataset attribute.
+    fn attribute(&mut self, keyword: &str) -> Result<Vec<DataArray>> {
+        let name = self.token()?.to_string();
+        let (nb_components, values) = match keyword {
+            "FIELD" => {
+                let nb_arrays: usize = self.number()?;
+                let mut arrays = vec![];
+                for _ in 0..nb_arrays {
+                    let name = self.token()?.to_string();
+                    let nb_components = self.number()?;
+                    let nb_tuples: usize = self.number()?;
+                    let dtype = DataType::from_legacy(self.token()?)?;
+                    let values = self.floats(product(nb_components, nb_tuples)?, dtype)?;
+                    arrays.push(DataArray { name, nb_components, values });
+                }
+                return Ok(arrays);
+            }
+            "COLOR_SCALARS" => {
+                let nb_components = self.number()?;
+                let nb_values = product(self.nb_items, nb_components)?;
+                let values = if self.binary {
+                    let bytes = self.binary_bytes(nb_values)?;
+                    bytes.iter().map(|&b| b as f32 / 255.0).collect()
+                } else {
+                    self.floats(nb_values, DataType::Float32)?
+                };
+                (nb_components, values)
+            }
+            _ => {
+                let dtype = DataType::from_legacy(self.token()?)?;
+                let nb_components = match keyword {
+                    "SCALARS" => match self.peek_token()?.map(|t| t.parse::<usize>()) {
+                        Some(Ok(n)) => {
+                            self.token()?;
+                            n
+                        }
+                        _ => 1,
+                    },
+                    "TENSORS" => 9,
+                    _ => 3,
+                };
+                if keyword == "SCALARS" && self.peek_token()? == Some("LOOKUP_TABLE") {
+                    self.token()?;
+                    self.token()?;
+                }
+                let nb_values = product(self.nb_items, nb_components)?;
+                (nb_components, self.floats(nb_values, dtype)?)
+            }
+        };
+        Ok(vec![DataArray { name, nb_components, values }])
+    }
+
+    fn skip_metadata(&mut self) -> Result<()> {
+        // The metadata ends with an empty line
+        self.line()?;
+        while self.pos < self.bytes.len() {
+            if self.line()?.trim().is_empty() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn floats(&mut self, n: usize, dtype: DataType) -> Result<Vec<f32>> {
+        if self.binary {
+            let bytes = self.binary_bytes(product(n, dtype.size())?)?;
+            Ok(decode_floats(bytes, dtype, true))
+        } else {
+            parse_floats(self.tokens(n)?.into_iter())
+        }
+    }
+
+    fn indices(&mut self, n: usize, dtype: DataType) -> Result<Vec<usize>> {
+        if self.binary {
+            let bytes = self.binary_bytes(product(n, dtype.size())?)?;
+            decode_indices(bytes, dtype, true)
+        } else {
+            parse_indices(self.tokens(n)?.into_iter())
+        }
+    }
+
+    /// Binary data starts on the line following its keyword.
+    fn binary_bytes(&mut self, n: usize) -> Result<&'a [u8]> {
+        self.line()?;
+        if n > self.bytes.len() - self.pos {
+            return Err(invalid("The binary data is truncated".to_string()));
+        }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// The next `n` tokens. `n` comes from the file, so it's checked against the bytes left
+    /// before allocating anything: each token takes at least 1 byte and 1 separator.
+    fn tokens(&mut self, n: usize) -> Result<Vec<&'a str>> {
+        if n > (self.bytes.len() - self.pos).div_ceil(2) {
+            return Err(invalid("The VTK file is truncated".to_string()));
+        }
+        (0..n).map(|_| self.token()).collect()
+    }
+
+    /// Rest of the current line, without the line feed.
+    fn line(&mut self) -> Result<&'a str> {
+        let rest = &self.bytes[self.pos..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        self.pos += (end + 1).min(rest.len());
+        std::str::from_utf8(&rest[..end])
+            .map(|line| line.trim_end_matches('\r'))
+            .map_err(|_| invalid("The VTK header isn't valid text".to_string()))
+    }
+
+    /// The next whitespace-separated token, or `None` at the end of the file.
+    fn next_token(&mut self) -> Result<Option<&'a str>> {
+        let rest = &self.bytes[self.pos..];
+        let start = match rest.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let len = rest[start..]
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .unwrap_or(rest.len() - start);
+        self.pos += start + len;
+        match std::str::from_utf8(&rest[start..start + len]) {
+            Ok(token) => Ok(Some(token)),
+            Err(_) => Err(invalid(format!("Invalid text at byte {}", self.pos - len))),
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<Option<&'a str>> {
+        let pos = self.pos;
+        let token = self.next_token();
+        self.pos = pos;
+        token
+    }
+
+    fn token(&mut self) -> Result<&'a str> {
+        self.next_token()?.ok_or_else(|| invalid("The VTK file is truncated".to_string()))
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T> {
+        parse_number(self.token()?)
+    }
+}
+
+/// `a * b`, or an error if the counts read in the file are too large.
+pub(crate) fn product(a: usize, b: usize) -> Result<usize> {
+    a.checked_mul(b).ok_or_else(|| invalid(format!("{} * {} values is too large", a, b)))
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidHeader(msg)
+}
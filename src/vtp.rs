@@ -0,0 +1,535 @@
+//! VTK XML PolyData files (`.vtp`), holding streamlines as the `Lines` of a single `Piece`.
+//!
+//! The points are read and written as-is and the names are mapped like in `vtk`: the scalars are
+//! saved in `PointData` and the properties in `CellData`, a multi-component array being repeated
+//! once per component in the names.
+//!
+//! The arrays can be saved as ASCII, as inline base64, or in the appended data section, either raw
+//! or base64. Both byte orders and both `UInt32` and `UInt64` block headers are supported when
+//! reading. Compressed arrays are not.
+
+use std::{
+    borrow::Cow,
+    fmt::{Display, Write as _},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+
+use crate::{
+    error::path_error,
+    vtk::{
+        decode_floats, decode_indices, parse_floats, parse_indices, product, DataArray, DataType,
+        PolyData,
+    },
+    Error, Header, Point, Result, Tractogram,
+};
+
+/// How the arrays of a `.vtp` file are saved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VtpEncoding {
+    /// Human-readable numbers, inside each `DataArray`.
+    Ascii,
+    /// Base64, inside each `DataArray`.
+    Binary,
+    /// Raw little-endian bytes, in the appended data section.
+    AppendedRaw,
+    /// Base64, in the appended data section.
+    AppendedBase64,
+}
+
+/// Load the streamlines of a `.vtp` file, with their point and cell data.
+///
+/// The returned header only holds the number of streamlines and the scalars and properties
+/// names. Names that don't fit in a trk header are kept, but writing a trk file with this header
+/// will return an error.
+pub fn read_vtp<P: AsRef<Path>>(path: P) -> Result<(Header, Tractogram)> {
+    let bytes = std::fs::read(path.as_ref()).map_err(|e| path_error(e, "Failed to load", &path))?;
+    parse(&bytes)?.into_tractogram()
+}
+
+/// Save `tractogram` as a `.vtp` file. The scalars and properties names are read from `header`.
+pub fn write_vtp<P: AsRef<Path>>(
+    path: P,
+    tractogram: &Tractogram,
+    header: &Header,
+    encoding: VtpEncoding,
+) -> Result<()> {
+    let poly_data = PolyData::from_tractogram(tractogram, header)?;
+    let mut writer = XmlWriter { encoding, xml: String::new(), appended: vec![] };
+    writer.xml.push_str(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<VTKFile type=\"PolyData\" version=\"1.0\" byte_order=\"LittleEndian\" ",
+        "header_type=\"UInt64\">\n",
+        "  <PolyData>\n",
+    ));
+    let _ = writeln!(
+        writer.xml,
+        "    <Piece NumberOfPoints=\"{}\" NumberOfVerts=\"0\" NumberOfLines=\"{}\" \
+         NumberOfStrips=\"0\" NumberOfPolys=\"0\">",
+        poly_data.points.len(),
+        poly_data.nb_lines()
+    );
+
+    for (section, arrays) in
+        [("PointData", &poly_data.point_data), ("CellData", &poly_data.cell_data)]
+    {
+        let _ = writeln!(writer.xml, "      <{}>", section);
+        for array in arrays {
+            writer.array(Some(&array.name), array.nb_components, &array.values);
+        }
+        let _ = writeln!(writer.xml, "      </{}>", section);
+    }
+
+    writer.xml.push_str("      <Points>\n");
+    let coordinates =
+        poly_data.points.iter().flat_map(|p| p.coords.iter().cloned()).collect::<Vec<_>>();
+    writer.array(None, 3, &coordinates);
+    writer.xml.push_str("      </Points>\n      <Lines>\n");
+    let connectivity = poly_data.connectivity.iter().map(|&i| i as i64).collect::<Vec<_>>();
+    writer.array(Some("connectivity"), 1, &connectivity);
+    let offsets = poly_data.offsets[1..].iter().map(|&o| o as i64).collect::<Vec<_>>();
+    writer.array(Some("offsets"), 1, &offsets);
+    writer.xml.push_str("      </Lines>\n    </Piece>\n  </PolyData>\n");
+
+    let f = File::create(path.as_ref()).map_err(|e| path_error(e, "Failed to create", &path))?;
+    let mut file = BufWriter::new(f);
+    file.write_all(writer.xml.as_bytes())?;
+    if let Some(encoding) = match encoding {
+        VtpEncoding::AppendedRaw => Some("raw"),
+        VtpEncoding::AppendedBase64 => Some("base64"),
+        _ => None,
+    } {
+        write!(file, "  <AppendedData encoding=\"{}\">\n   _", encoding)?;
+        file.write_all(&writer.appended)?;
+        file.write_all(b"\n  </AppendedData>\n")?;
+    }
+    file.write_all(b"</VTKFile>\n")?;
+    file.flush()?;
+    Ok(())
+}
+
+/// A type that can be saved in a `DataArray`.
+trait VtpValue: Copy + Display {
+    const TYPE: &'static str;
+    fn write_le(self, bytes: &mut Vec<u8>);
+}
+
+impl VtpValue for f32 {
+    const TYPE: &'static str = "Float32";
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl VtpValue for i64 {
+    const TYPE: &'static str = "Int64";
+    fn write_le(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_le_bytes());
+    }
+}
+
+struct XmlWriter {
+    encoding: VtpEncoding,
+    xml: String,
+    appended: Vec<u8>,
+}
+
+impl XmlWriter {
+    fn array<T: VtpValue>(&mut self, name: Option<&str>, nb_components: usize, values: &[T]) {
+        let indent = "        ";
+        let _ = write!(self.xml, "{}<DataArray type=\"{}\"", indent, T::TYPE);
+        if let Some(name) = name {
+            let _ = write!(self.xml, " Name=\"{}\"", escape(name));
+        }
+        let _ = write!(self.xml, " NumberOfComponents=\"{}\"", nb_components);
+
+        // All binary blocks start with their size in bytes
+        let block = || {
+            let size = std::mem::size_of_val(values);
+            let mut block = Vec::with_capacity(8 + size);
+            block.extend_from_slice(&(size as u64).to_le_bytes());
+            values.iter().for_each(|v| v.write_le(&mut block));
+            block
+        };
+        match self.encoding {
+            VtpEncoding::Ascii => {
+                self.xml.push_str(" format=\"ascii\">\n");
+                for tuple in values.chunks(nb_components) {
+                    self.xml.push_str(indent);
+                    for v in tuple {
+                        let _ = write!(self.xml, "  {}", v);
+                    }
+                    self.xml.push('\n');
+                }
+                let _ = writeln!(self.xml, "{}</DataArray>", indent);
+            }
+            VtpEncoding::Binary => {
+                let _ = write!(self.xml, " format=\"binary\">\n{}  ", indent);
+                self.xml.push_str(&encode_base64(&block()));
+                let _ = write!(self.xml, "\n{}</DataArray>\n", indent);
+            }
+            VtpEncoding::AppendedRaw | VtpEncoding::AppendedBase64 => {
+                let _ =
+                    writeln!(self.xml, " format=\"appended\" offset=\"{}\"/>", self.appended.len());
+                if self.encoding == VtpEncoding::AppendedRaw {
+                    self.appended.extend(block());
+                } else {
+                    self.appended.extend(encode_base64(&block()).into_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Everything needed to decode the binary arrays.
+struct Context<'a> {
+    big_endian: bool,
+    header_type: DataType,
+    /// Encoding and data of the appended section, starting just after the `_`.
+    appended: Option<(&'a str, &'a [u8])>,
+}
+
+fn parse(bytes: &[u8]) -> Result<PolyData> {
+    // The appended data can be raw bytes, so only the part before it is parsed as text
+    let (xml_end, appended) = match find(bytes, b"<AppendedData") {
+        Some(start) => {
+            let tag_end = find(&bytes[start..], b">").map(|end| start + end);
+            let data_start = tag_end.and_then(|end| find(&bytes[end..], b"_").map(|u| end + u));
+            let (tag_end, data_start) = tag_end
+                .zip(data_start)
+                .ok_or_else(|| invalid("The AppendedData tag is malformed".to_string()))?;
+            let tag = text(&bytes[start + 1..tag_end])?;
+            let (_, attributes) = parse_tag(tag)?;
+            let encoding = attribute(&attributes, "encoding").unwrap_or("raw");
+            (start, Some((encoding.to_string(), &bytes[data_start + 1..])))
+        }
+        None => (bytes.len(), None),
+    };
+    let xml = text(&bytes[..xml_end])?;
+
+    let mut context = Context { big_endian: false, header_type: DataType::UInt32, appended: None };
+    let mut nb_pieces = 0;
+    let (mut nb_points, mut nb_verts, mut nb_lines) = (0, 0, 0);
+    let mut arrays = vec![];
+    let mut stack = vec![];
+    let mut pos = 0;
+    while let Some(start) = xml[pos..].find('<').map(|start| pos + start) {
+        let rest = &xml[start..];
+        if rest.starts_with("<?") || rest.starts_with("<!--") {
+            let close = if rest.starts_with("<?") { "?>" } else { "-->" };
+            let end = rest.find(close).ok_or_else(|| invalid("Unclosed XML tag".to_string()))?;
+            pos = start + end + close.len();
+            continue;
+        }
+        let end = rest.find('>').ok_or_else(|| invalid("Unclosed XML tag".to_string()))?;
+        let content = &rest[1..end];
+        pos = start + end + 1;
+        if content.starts_with('/') {
+            stack.pop();
+            continue;
+        }
+        let self_closing = content.ends_with('/');
+        let (name, attributes) = parse_tag(content.trim_end_matches('/'))?;
+        match name {
+            "VTKFile" => {
+                if attribute(&attributes, "type") != Some("PolyData") {
+                    return Err(invalid("The VTK file doesn't hold a PolyData".to_string()));
+                }
+                if attribute(&attributes, "compressor").is_some_and(|c| !c.is_empty()) {
+                    return Err(invalid("Compressed VTK files are not supported".to_string()));
+                }
+                context.big_endian = attribute(&attributes, "byte_order") == Some("BigEndian");
+                context.header_type = match attribute(&attributes, "header_type") {
+                    None | Some("UInt32") => DataType::UInt32,
+                    Some("UInt64") => DataType::UInt64,
+                    Some(t) => return Err(invalid(format!("Unsupported header_type {:?}", t))),
+                };
+            }
+            "Piece" => {
+                nb_pieces += 1;
+                nb_points = count(&attributes, "NumberOfPoints")?;
+                nb_verts = count(&attributes, "NumberOfVerts")?;
+                nb_lines = count(&attributes, "NumberOfLines")?;
+            }
+            "DataArray" => {
+                let body = if self_closing {
+                    ""
+                } else {
+                    let close = xml[pos..]
+                        .find("</DataArray>")
+                        .ok_or_else(|| invalid("Unclosed DataArray".to_string()))?;
+                    let body = &xml[pos..pos + close];
+                    pos += close + "</DataArray>".len();
+                    body
+                };
+                let parent = stack.last().cloned().unwrap_or("");
+                arrays.push((parent, attributes, body));
+                continue;
+            }
+            _ => {}
+        }
+        if !self_closing {
+            stack.push(name);
+        }
+    }
+    if nb_pieces != 1 {
+        return Err(invalid(format!("Only one Piece is supported, not {}", nb_pieces)));
+    }
+    context.appended = appended.as_ref().map(|(encoding, data)| (encoding.as_str(), *data));
+
+    let mut poly_data = PolyData {
+        points: vec![],
+        connectivity: vec![],
+        offsets: vec![0],
+        point_data: vec![],
+        cell_data: vec![],
+    };
+    for (parent, attributes, body) in arrays {
+        let name = attribute(&attributes, "Name");
+        let dtype = DataType::from_xml(attribute(&attributes, "type").unwrap_or(""))?;
+        let content = array_content(&attributes, body, dtype, &context)?;
+        match (parent, name) {
+            ("Points", _) => {
+                let coordinates = content.floats(dtype, context.big_endian)?;
+                if coordinates.len() != product(3, nb_points)? {
+                    return Err(invalid(format!(
+                        "{} points are declared, but {} coordinates were found",
+                        nb_points,
+                        coordinates.len()
+                    )));
+                }
+                poly_data.points =
+                    coordinates.chunks_exact(3).map(|p| Point::new(p[0], p[1], p[2])).collect();
+            }
+            ("Lines", Some("connectivity")) => {
+                poly_data.connectivity = content.indices(dtype, context.big_endian)?;
+            }
+            ("Lines", Some("offsets")) => {
+                poly_data.offsets.truncate(1);
+                poly_data.offsets.extend(content.indices(dtype, context.big_endian)?);
+                if poly_data.offsets.len() != nb_lines + 1 {
+                    return Err(invalid(format!(
+                        "{} lines are declared, but {} offsets were found",
+                        nb_lines,
+                        poly_data.offsets.len() - 1
+                    )));
+                }
+            }
+            ("PointData" | "CellData", _) => {
+                let name = name.ok_or_else(|| invalid("A DataArray has no Name".to_string()))?;
+                let nb_components = match attribute(&attributes, "NumberOfComponents") {
+                    Some(n) => n.parse().map_err(|_| invalid(format!("{:?} components", n)))?,
+                    None => 1,
+                };
+                let array = DataArray {
+                    name: name.to_string(),
+                    nb_components,
+                    values: content.floats(dtype, context.big_endian)?,
+                };
+                if parent == "PointData" {
+                    poly_data.point_data.push(array);
+                } else {
+                    poly_data.cell_data.push(array.rows(nb_verts, nb_lines)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(poly_data)
+}
+
+/// The values of a `DataArray`, either as text or as bytes.
+enum Content<'a> {
+    Text(&'a str),
+    Binary(Cow<'a, [u8]>),
+}
+
+impl Content<'_> {
+    fn floats(&self, dtype: DataType, big_endian: bool) -> Result<Vec<f32>> {
+        match self {
+            Content::Text(text) => parse_floats(text.split_whitespace()),
+            Content::Binary(bytes) => Ok(decode_floats(bytes, dtype, big_endian)),
+        }
+    }
+
+    fn indices(&self, dtype: DataType, big_endian: bool) -> Result<Vec<usize>> {
+        match self {
+            Content::Text(text) => parse_indices(text.split_whitespace()),
+            Content::Binary(bytes) => decode_indices(bytes, dtype, big_endian),
+        }
+    }
+}
+
+fn array_content<'a>(
+    attributes: &[(&str, String)],
+    body: &'a str,
+    dtype: DataType,
+    context: &Context<'a>,
+) -> Result<Content<'a>> {
+    let content = match attribute(attributes, "format") {
+        Some("ascii") | None => Content::Text(body),
+        Some("binary") => {
+            let text = body.bytes().filter(|b| !b.is_ascii_whitespace()).collect::<Vec<_>>();
+            Content::Binary(Cow::Owned(base64_block(&text, context)?))
+        }
+        Some("appended") => {
+            let (encoding, data) =
+                context.appended.ok_or_else(|| invalid("No AppendedData".to_string()))?;
+            let offset = count(attributes, "offset")?;
+            let data = data.get(offset..).ok_or_else(truncated)?;
+            match encoding {
+                "raw" => Content::Binary(Cow::Borrowed(raw_block(data, context)?)),
+                "base64" => Content::Binary(Cow::Owned(base64_block(data, context)?)),
+                _ => return Err(invalid(format!("Unsupported encoding {:?}", encoding))),
+            }
+        }
+        Some(format) => return Err(invalid(format!("Unsupported format {:?}", format))),
+    };
+    if let Content::Binary(bytes) = &content {
+        if bytes.len() % dtype.size() != 0 {
+            return Err(invalid("A binary array isn't a multiple of its type".to_string()));
+        }
+    }
+    Ok(content)
+}
+
+/// Size, in bytes, of the data following a block header.
+fn block_size(header: &[u8], context: &Context) -> usize {
+    match (context.header_type, context.big_endian) {
+        (DataType::UInt64, false) => LittleEndian::read_u64(header) as usize,
+        (DataType::UInt64, true) => BigEndian::read_u64(header) as usize,
+        (_, false) => LittleEndian::read_u32(header) as usize,
+        (_, true) => BigEndian::read_u32(header) as usize,
+    }
+}
+
+fn raw_block<'a>(data: &'a [u8], context: &Context) -> Result<&'a [u8]> {
+    let header_size = context.header_type.size();
+    let size = block_size(data.get(..header_size).ok_or_else(truncated)?, context);
+    data.get(header_size..).and_then(|data| data.get(..size)).ok_or_else(truncated)
+}
+
+/// Decode a base64 block. The header and the data can be encoded together or separately.
+fn base64_block(text: &[u8], context: &Context) -> Result<Vec<u8>> {
+    let header_size = context.header_type.size();
+    let header_chars = header_size.div_ceil(3) * 4;
+    let header = decode_base64(text.get(..header_chars).ok_or_else(truncated)?)?;
+    let size = block_size(&header, context);
+    // `size` comes from the file, so it can't be larger than what the text could hold
+    if size > text.len() {
+        return Err(truncated());
+    }
+    if text[header_chars - 1] == b'=' {
+        let data_chars = size.div_ceil(3) * 4;
+        let data = text.get(header_chars..).and_then(|data| data.get(..data_chars));
+        let mut data = decode_base64(data.ok_or_else(truncated)?)?;
+        data.truncate(size);
+        Ok(data)
+    } else {
+        let chars = (header_size + size).div_ceil(3) * 4;
+        let mut data = decode_base64(text.get(..chars).ok_or_else(truncated)?)?;
+        data.truncate(header_size + size);
+        data.drain(..header_size);
+        Ok(data)
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Decode base64 by groups of 4 characters, so that padding is accepted in the middle.
+fn decode_base64(text: &[u8]) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    for chunk in text.chunks(4) {
+        let (mut n, mut nb_chars) = (0u32, 0);
+        for &c in chunk.iter().take_while(|&&c| c != b'=') {
+            let value = BASE64
+                .iter()
+                .position(|&b| b == c)
+                .ok_or_else(|| invalid(format!("Invalid base64 character {:?}", c as char)))?;
+            n |= (value as u32) << (18 - 6 * nb_chars);
+            nb_chars += 1;
+        }
+        bytes.extend_from_slice(&n.to_be_bytes()[1..1 + nb_chars * 6 / 8]);
+    }
+    Ok(bytes)
+}
+
+/// Split the content of a tag, without the brackets, in its name and its attributes.
+fn parse_tag(content: &str) -> Result<(&str, Vec<(&str, String)>)> {
+    let content = content.trim();
+    let name_end = content.find(char::is_whitespace).unwrap_or(content.len());
+    let (name, mut rest) = content.split_at(name_end);
+    let error = || invalid(format!("Malformed XML tag {:?}", name));
+    let mut attributes = vec![];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return Ok((name, attributes));
+        }
+        let equal = rest.find('=').ok_or_else(error)?;
+        let key = rest[..equal].trim();
+        let value = rest[equal + 1..].trim_start();
+        let quote = value.chars().next().filter(|&c| c == '"' || c == '\'').ok_or_else(error)?;
+        let close = value[1..].find(quote).ok_or_else(error)?;
+        attributes.push((key, unescape(&value[1..1 + close])));
+        rest = &value[close + 2..];
+    }
+}
+
+fn attribute<'a>(attributes: &'a [(&str, String)], key: &str) -> Option<&'a str> {
+    attributes.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+}
+
+/// A non-negative integer attribute. 0 if it's missing.
+fn count(attributes: &[(&str, String)], key: &str) -> Result<usize> {
+    match attribute(attributes, key) {
+        Some(v) => v.parse().map_err(|_| invalid(format!("{} can't be {:?}", key, v))),
+        None => Ok(0),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn text(bytes: &[u8]) -> Result<&str> {
+    std::str::from_utf8(bytes).map_err(|_| invalid("The XML isn't valid UTF-8".to_string()))
+}
+
+fn truncated() -> Error {
+    invalid("The binary data is truncated".to_string())
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidHeader(msg)
+}
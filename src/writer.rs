@@ -1,102 +1,180 @@
-use std::{fs::File, io::BufWriter, path::Path};
+use std::{
+    io::{self, Seek, SeekFrom, Write},
+    path::Path,
+};
 
-use anyhow::Result;
 use byteorder::WriteBytesExt;
 use nalgebra::Vector4;
 
 use crate::{
     affine::get_affine_and_translation,
     tractogram::{Point, RefTractogramItem, Tractogram, TractogramItem},
-    Affine, Affine4, CHeader, Header, Spacing, Translation, TrkEndianness,
+    Affine, Affine4, CHeader, Error, Header, Result, Spacing, Translation, TrkEndianness,
+    TrkOutput,
 };
 
 macro_rules! write_streamline {
     ($writer:ident, $streamline:expr, $scalars:expr, $properties:expr) => {
         if $writer.nb_scalars == 0 {
-            $streamline.write($writer);
+            $streamline.write($writer)?;
         } else {
-            $writer.writer.write_i32::<TrkEndianness>($streamline.len() as i32).unwrap();
+            $writer.writer.write_i32::<TrkEndianness>($streamline.len() as i32)?;
             $writer.real_n_count += 1;
 
             let scalars = $scalars.chunks($writer.nb_scalars);
             for (p, scalars) in $streamline.into_iter().zip(scalars) {
-                $writer.write_point(&p);
-                $writer.write_f32s(scalars);
+                $writer.write_point(&p)?;
+                $writer.write_f32s(scalars)?;
             }
         }
 
-        $writer.write_f32s($properties);
+        $writer.write_f32s($properties)?;
     };
     // Fast method, without scalars and properties
     ($writer:ident, $streamline:expr, $nb_points:expr) => {
-        $writer.writer.write_i32::<TrkEndianness>($nb_points as i32).unwrap();
+        $writer.writer.write_i32::<TrkEndianness>($nb_points as i32)?;
         for p in $streamline {
-            $writer.write_point(&p);
+            $writer.write_point(&p)?;
         }
         $writer.real_n_count += 1;
     };
 }
 
-pub struct Writer {
-    writer: BufWriter<File>,
+type PatchNCount<W> = fn(&mut W, u64, i32) -> io::Result<()>;
+
+/// Writer for TrackVis files.
+///
+/// By default, a `Writer` creates a file on disk, but any `Write + Seek` sink (`from_writer`) or
+/// any non-seekable `Write` sink (`from_stream`) can be used.
+pub struct Writer<W: Write = TrkOutput> {
+    writer: W,
     pub affine4: Affine4,
     affine: Affine,
     translation: Translation,
     nb_scalars: usize,
 
     real_n_count: i32,
+    real_nb_points: usize,
     raw: bool,
     voxel_space: bool,
+    finished: bool,
+
+    /// Position of the header in `writer` and how to update its `n_count` field. `None` when the
+    /// sink can't seek.
+    header_start: u64,
+    patch_n_count: Option<PatchNCount<W>>,
+
+    /// `n_count` already written in the header of a non-seekable sink, if known.
+    declared_n_count: Option<usize>,
+
+    sync: Option<fn(&mut W) -> io::Result<()>>,
+}
+
+/// What has been written by a `Writer`, as returned by `finish`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stats {
+    pub nb_streamlines: usize,
+    pub nb_points: usize,
 }
 
 pub trait Writable {
-    fn write(self, w: &mut Writer);
+    fn write<W: Write>(self, w: &mut Writer<W>) -> Result<()>;
 }
 
 impl Writable for Tractogram {
-    fn write(self, w: &mut Writer) {
+    fn write<W: Write>(self, w: &mut Writer<W>) -> Result<()> {
         for item in &self {
-            item.write(w);
+            item.write(w)?;
         }
+        Ok(())
     }
 }
 
 impl Writable for TractogramItem {
-    fn write(self, writer: &mut Writer) {
+    fn write<W: Write>(self, writer: &mut Writer<W>) -> Result<()> {
         let (streamline, scalars, properties) = self;
         write_streamline!(writer, streamline, scalars.data.as_slice(), &properties);
+        Ok(())
     }
 }
 
 impl<'data> Writable for RefTractogramItem<'data> {
-    fn write(self, writer: &mut Writer) {
+    fn write<W: Write>(self, writer: &mut Writer<W>) -> Result<()> {
         let (streamline, scalars, properties) = self;
         write_streamline!(writer, streamline, scalars, properties);
+        Ok(())
     }
 }
 
-impl<'data> Writable for &'data [Point] {
-    fn write(self, writer: &mut Writer) {
+impl Writable for &[Point] {
+    fn write<W: Write>(self, writer: &mut Writer<W>) -> Result<()> {
         write_streamline!(writer, self, self.len());
+        Ok(())
     }
 }
 
 impl Writer {
+    /// Create the trk file `path`.
+    ///
+    /// It is compressed with gzip or zstd if its extension is `.gz` or `.zst` (`gzip` and `zstd`
+    /// features). The header is then stored uncompressed, in its own gzip member or zstd frame,
+    /// so that its `n_count` field can still be updated by `finish`.
     pub fn new<P: AsRef<Path>>(path: P, reference: Option<&Header>) -> Result<Writer> {
-        let f = File::create(path).expect("Can't create new trk file.");
-        let mut writer = BufWriter::new(f);
+        let mut writer = Writer::build(TrkOutput::create(path)?, reference, 0)?;
+        writer.patch_n_count = Some(|w, _, n_count| w.patch_n_count(n_count));
+        Ok(writer)
+    }
+
+    /// Asks `finish` to also call `fsync`, so that all data is on disk when it returns.
+    pub fn sync_on_finish(mut self) -> Self {
+        self.sync = Some(|w| w.sync_all());
+        self
+    }
+}
+
+impl<W: Write + Seek> Writer<W> {
+    /// Write a tractogram in a seekable sink, like a `Cursor<Vec<u8>>`.
+    ///
+    /// The header is written at the current position of `writer`. Its `n_count` field is updated
+    /// by `finish`. Pass a `&mut` sink to get it back once the `Writer` is finished.
+    pub fn from_writer(mut writer: W, reference: Option<&Header>) -> Result<Writer<W>> {
+        let header_start = writer.stream_position()?;
+        let mut writer = Writer::build(writer, reference, 0)?;
+        writer.header_start = header_start;
+        writer.patch_n_count = Some(patch_n_count::<W>);
+        Ok(writer)
+    }
+}
 
+impl<W: Write> Writer<W> {
+    /// Write a tractogram in a sink that can't seek, like stdout.
+    ///
+    /// Because the header can't be updated at the end, its `n_count` field is written right away:
+    /// `nb_streamlines` if the caller knows it, otherwise 0, which means "unknown" for TrackVis.
+    /// `finish` returns an error if the number of written streamlines doesn't match
+    /// `nb_streamlines`.
+    ///
+    /// No buffering is done, so wrap `writer` in a `BufWriter` if needed.
+    pub fn from_stream(
+        writer: W,
+        reference: Option<&Header>,
+        nb_streamlines: Option<usize>,
+    ) -> Result<Writer<W>> {
+        let mut writer = Writer::build(writer, reference, nb_streamlines.unwrap_or(0) as i32)?;
+        writer.declared_n_count = nb_streamlines;
+        Ok(writer)
+    }
+
+    fn build(mut writer: W, reference: Option<&Header>, n_count: i32) -> Result<Writer<W>> {
         let (affine4, nb_scalars) = match reference {
             Some(header) => {
-                header.write(&mut writer)?;
-                let affine4 = header
-                    .affine4_to_rasmm
-                    .try_inverse()
-                    .expect("Unable to inverse 4x4 affine matrix");
+                header.write_with_n_count(&mut writer, n_count)?;
+                let affine4 =
+                    header.affine4_to_rasmm.try_inverse().ok_or(Error::AffineNotInvertible)?;
                 (affine4, header.scalars_name.len())
             }
             None => {
-                Header::default().write(&mut writer)?;
+                Header::default().write_with_n_count(&mut writer, n_count)?;
                 (Affine4::identity(), 0)
             }
         };
@@ -108,9 +186,15 @@ impl Writer {
             affine,
             translation,
             real_n_count: 0,
+            real_nb_points: 0,
             nb_scalars,
             raw: false,
             voxel_space: false,
+            finished: false,
+            header_start: 0,
+            patch_n_count: None,
+            declared_n_count: None,
+            sync: None,
         })
     }
 
@@ -163,44 +247,83 @@ impl Writer {
     ///
     /// The TrackVis header (on disk) will **not** be modified.
     pub fn apply_affine(&mut self, affine: &Affine4) {
-        self.affine4 = self.affine4 * affine;
+        self.affine4 *= affine;
         let (affine, translation) = get_affine_and_translation(&self.affine4);
         self.affine = affine;
         self.translation = translation;
     }
 
-    pub fn write<T: Writable>(&mut self, data: T) {
-        data.write(self);
+    pub fn write<T: Writable>(&mut self, data: T) -> Result<()> {
+        data.write(self)
     }
 
-    pub fn write_from_iter<I>(&mut self, streamline: I, len: usize)
+    pub fn write_from_iter<I>(&mut self, streamline: I, len: usize) -> Result<()>
     where
         I: IntoIterator<Item = Point>,
     {
         write_streamline!(self, streamline, len);
+        Ok(())
     }
 
-    fn write_point(&mut self, p: &Point) {
+    fn write_point(&mut self, p: &Point) -> Result<()> {
         let p = if self.raw { *p } else { self.affine * p + self.translation };
-        self.writer.write_f32::<TrkEndianness>(p.x).unwrap();
-        self.writer.write_f32::<TrkEndianness>(p.y).unwrap();
-        self.writer.write_f32::<TrkEndianness>(p.z).unwrap();
+        self.writer.write_f32::<TrkEndianness>(p.x)?;
+        self.writer.write_f32::<TrkEndianness>(p.y)?;
+        self.writer.write_f32::<TrkEndianness>(p.z)?;
+        self.real_nb_points += 1;
+        Ok(())
     }
 
-    fn write_f32s(&mut self, data: &[f32]) {
+    fn write_f32s(&mut self, data: &[f32]) -> Result<()> {
         for &d in data {
-            self.writer.write_f32::<TrkEndianness>(d).unwrap();
+            self.writer.write_f32::<TrkEndianness>(d)?;
         }
+        Ok(())
     }
+
+    /// Complete the trk file and returns what has been written.
+    ///
+    /// The `n_count` field can only be written at the end because we don't know how many
+    /// streamlines the user will write. If `finish` is not called, `Drop` will try to write it,
+    /// but any error will be silently ignored.
+    pub fn finish(mut self) -> Result<Stats> {
+        self.finished = true;
+        self.write_n_count()?;
+        if let Some(sync) = self.sync {
+            sync(&mut self.writer)?;
+        }
+
+        let nb_streamlines = self.real_n_count as usize;
+        if let Some(declared) = self.declared_n_count {
+            if declared != nb_streamlines {
+                return Err(Error::CountMismatch { declared, written: nb_streamlines });
+            }
+        }
+        Ok(Stats { nb_streamlines, nb_points: self.real_nb_points })
+    }
+
+    fn write_n_count(&mut self) -> Result<()> {
+        if let Some(patch_n_count) = self.patch_n_count {
+            patch_n_count(&mut self.writer, self.header_start, self.real_n_count)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Overwrite the `n_count` field of the header, then go back to the end of the data.
+fn patch_n_count<W: Write + Seek>(w: &mut W, header_start: u64, n_count: i32) -> io::Result<()> {
+    CHeader::seek_n_count_field(w, header_start)?;
+    w.write_i32::<TrkEndianness>(n_count)?;
+    w.seek(SeekFrom::End(0))?;
+    Ok(())
 }
 
-// Finally write `n_count`
-impl Drop for Writer {
+// Best-effort fallback when `finish` has not been called
+impl<W: Write> Drop for Writer<W> {
     fn drop(&mut self) {
-        CHeader::seek_n_count_field(&mut self.writer)
-            .expect("Unable to seek to 'n_count' field before closing trk file.");
-        self.writer
-            .write_i32::<TrkEndianness>(self.real_n_count)
-            .expect("Unable to write 'n_count' field before closing trk file.");
+        if !self.finished {
+            let _ = self.write_n_count();
+        }
     }
 }
@@ -24,7 +24,7 @@ mod nifti_tests {
                     Point::new(14.00, 27.95, 51.98),
                     Point::new(14.35, 28.05, 52.33),
                 ][..],
-            );
+            )?;
         }
 
         // Loading them back without the right transformation is not supposed to give back the same
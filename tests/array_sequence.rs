@@ -1,4 +1,4 @@
-use trk_io::{ArraySequence, Point};
+use trk_io::{ArraySequence, Point, Tractogram};
 
 fn get_toy_streamlines() -> ArraySequence<Point> {
     ArraySequence::new(
@@ -180,3 +180,309 @@ fn test_filter() {
     // Ensure that arr is still usable
     assert_eq!(arr.len(), 4);
 }
+
+#[test]
+fn test_view() {
+    let streamlines = get_toy_streamlines();
+
+    let view = streamlines.view(1..3);
+    assert_eq!(view.len(), 2);
+    assert_eq!(view.length_of_array(0), 3);
+    assert_eq!(view[0], streamlines[1]);
+    assert_eq!(view[1], streamlines[2]);
+    assert_eq!(view.data(), &streamlines.data[2..]);
+    assert_eq!(view.iter().collect::<Vec<_>>(), streamlines.iter().skip(1).collect::<Vec<_>>());
+
+    // All kinds of ranges
+    assert_eq!(streamlines.view(..).len(), 3);
+    assert_eq!(streamlines.view(1..).len(), 2);
+    assert_eq!(streamlines.view(..1).len(), 1);
+    assert_eq!(streamlines.view(..=1).len(), 2);
+    assert_eq!(streamlines.view(0..=2).len(), 3);
+
+    // View of a view
+    let sub_view = view.view(1..);
+    assert_eq!(sub_view.len(), 1);
+    assert_eq!(sub_view[0], streamlines[2]);
+
+    let empty = streamlines.view(2..2);
+    assert!(empty.is_empty());
+    assert!(empty.data().is_empty());
+    assert_eq!(empty.iter().count(), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_view_out_of_bounds() {
+    get_toy_streamlines().view(2..4);
+}
+
+#[test]
+fn test_slice() {
+    let streamlines = get_toy_streamlines();
+
+    let slice = streamlines.slice(1..);
+    assert_eq!(slice.offsets, vec![0, 3, 6]);
+    assert_eq!(slice.data, streamlines.data[2..].to_vec());
+
+    assert_eq!(streamlines.slice(..), streamlines);
+    assert_eq!(streamlines.slice(1..1), ArraySequence::empty());
+    assert_eq!(
+        streamlines.view(..=1).to_array_sequence(),
+        ArraySequence::new(vec![2, 3], streamlines.data[..5].to_vec())
+    );
+}
+
+#[test]
+fn test_tractogram_slice() {
+    let scalars = ArraySequence::new(vec![2, 3, 3], (0..8).map(|i| i as f32).collect());
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![10.0, 20.0, 30.0]);
+    let tractogram = Tractogram::new(get_toy_streamlines(), scalars, properties);
+
+    let slice = tractogram.slice(1..3);
+    assert_eq!(slice.streamlines.len(), 2);
+    assert!(slice.item(0) == tractogram.item(1));
+    assert!(slice.item(1) == tractogram.item(2));
+
+    // Empty scalars and properties stay empty
+    let tractogram =
+        Tractogram::new(get_toy_streamlines(), ArraySequence::empty(), ArraySequence::empty());
+    let slice = tractogram.slice(..2);
+    assert_eq!(slice.streamlines, get_toy_streamlines().slice(..2));
+    assert!(slice.scalars.is_empty());
+    assert!(slice.properties.is_empty());
+}
+
+#[test]
+fn test_tractogram_view() {
+    let scalars = ArraySequence::new(vec![2, 3, 3], (0..8).map(|i| i as f32).collect());
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![10.0, 20.0, 30.0]);
+    let tractogram = Tractogram::new(get_toy_streamlines(), scalars, properties);
+
+    let view = tractogram.view(1..);
+    assert_eq!(view.len(), 2);
+    assert!(view.item(0) == tractogram.item(1));
+    assert!(view.item(1) == tractogram.item(2));
+    assert_eq!(view.iter().count(), 2);
+    assert_eq!(view.to_tractogram(), tractogram.slice(1..));
+
+    // View of a view
+    let sub_view = view.view(1..2);
+    assert_eq!(sub_view.len(), 1);
+    assert!(sub_view.item(0) == tractogram.item(2));
+    assert_eq!(sub_view.properties.data(), &[30.0]);
+    assert!(tractogram.view(3..).is_empty());
+
+    // Empty scalars and properties stay empty
+    let tractogram =
+        Tractogram::new(get_toy_streamlines(), ArraySequence::empty(), ArraySequence::empty());
+    let view = tractogram.view(..=1);
+    assert_eq!(view.item(1), (&tractogram.streamlines[1], &[][..], &[][..]));
+    assert!(view.scalars.is_empty());
+    assert!(view.to_tractogram().properties.is_empty());
+}
+
+#[test]
+fn test_retain() {
+    let mut arr = ArraySequence::new(vec![2, 0, 3, 1], vec![1, 2, 3, 4, 5, 6]);
+    let mut nb_calls = 0;
+    arr.retain(|a| {
+        nb_calls += 1;
+        a.len() != 3
+    });
+    assert_eq!(nb_calls, 4);
+    assert_eq!(arr, ArraySequence::new(vec![2, 0, 1], vec![1, 2, 6]));
+
+    arr.retain(|_| false);
+    assert_eq!(arr, ArraySequence::empty());
+}
+
+#[test]
+fn test_remove() {
+    let mut arr = ArraySequence::new(vec![2, 3, 1], vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(arr.remove(1), vec![3, 4, 5]);
+    assert_eq!(arr, ArraySequence::new(vec![2, 1], vec![1, 2, 6]));
+    assert_eq!(arr.remove(1), vec![6]);
+    assert_eq!(arr.remove(0), vec![1, 2]);
+    assert_eq!(arr, ArraySequence::empty());
+}
+
+#[test]
+#[should_panic]
+fn test_remove_out_of_bounds() {
+    ArraySequence::new(vec![2], vec![1, 2]).remove(1);
+}
+
+#[test]
+fn test_swap_remove() {
+    let mut arr = ArraySequence::new(vec![2, 3, 1], vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(arr.swap_remove(0), vec![1, 2]);
+    assert_eq!(arr, ArraySequence::new(vec![1, 3], vec![6, 3, 4, 5]));
+    assert_eq!(arr.swap_remove(1), vec![3, 4, 5]);
+    assert_eq!(arr, ArraySequence::new(vec![1], vec![6]));
+    assert_eq!(arr.swap_remove(0), vec![6]);
+    assert_eq!(arr, ArraySequence::empty());
+}
+
+#[test]
+#[should_panic]
+fn test_swap_remove_empty() {
+    ArraySequence::<i32>::empty().swap_remove(0);
+}
+
+#[test]
+fn test_insert() {
+    let mut arr = ArraySequence::new(vec![2, 1], vec![1, 2, 6]);
+    arr.insert(1, &[3, 4, 5]);
+    assert_eq!(arr, ArraySequence::new(vec![2, 3, 1], vec![1, 2, 3, 4, 5, 6]));
+    arr.insert(3, &[7]);
+    arr.insert(0, &[]);
+    assert_eq!(arr, ArraySequence::new(vec![0, 2, 3, 1, 1], vec![1, 2, 3, 4, 5, 6, 7]));
+}
+
+#[test]
+fn test_truncate() {
+    let mut arr = ArraySequence::new(vec![2, 3, 1], vec![1, 2, 3, 4, 5, 6]);
+    arr.truncate(5);
+    assert_eq!(arr.len(), 3);
+    arr.truncate(2);
+    assert_eq!(arr, ArraySequence::new(vec![2, 3], vec![1, 2, 3, 4, 5]));
+    arr.truncate(0);
+    assert_eq!(arr, ArraySequence::empty());
+}
+
+#[test]
+fn test_append_and_concat() {
+    let mut arr = ArraySequence::new(vec![2], vec![1, 2]);
+    let mut other = ArraySequence::new(vec![1, 3], vec![3, 4, 5, 6]);
+    arr.append(&mut other);
+    assert_eq!(arr, ArraySequence::new(vec![2, 1, 3], vec![1, 2, 3, 4, 5, 6]));
+    assert_eq!(other, ArraySequence::empty());
+
+    let concat = ArraySequence::concat(vec![
+        ArraySequence::new(vec![2], vec![1, 2]),
+        ArraySequence::empty(),
+        ArraySequence::new(vec![1, 3], vec![3, 4, 5, 6]),
+    ]);
+    assert_eq!(concat, arr);
+}
+
+fn get_toy_tractogram() -> Tractogram {
+    let scalars = ArraySequence::new(vec![2, 3, 3], (0..8).map(|i| i as f32).collect());
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![10.0, 20.0, 30.0]);
+    Tractogram::new(get_toy_streamlines(), scalars, properties)
+}
+
+#[test]
+fn test_tractogram_mutations() {
+    let original = get_toy_tractogram();
+
+    let mut tractogram = original.clone();
+    tractogram.retain(|(streamline, _, properties)| streamline.len() == 3 && properties[0] > 25.0);
+    assert_eq!(tractogram, original.slice(2..));
+
+    let mut tractogram = original.clone();
+    let (streamline, scalars, properties) = tractogram.remove(1);
+    assert!((&streamline[..], &scalars.data[..], &properties[..]) == original.item(1));
+    assert_eq!(scalars.len(), 1);
+    tractogram.insert(1, original.item(1));
+    assert_eq!(tractogram, original);
+
+    let (streamline, scalars, properties) = tractogram.swap_remove(0);
+    assert!((&streamline[..], &scalars.data[..], &properties[..]) == original.item(0));
+    assert!(tractogram.item(0) == original.item(2));
+    assert!(tractogram.item(1) == original.item(1));
+
+    let mut tractogram = original.clone();
+    tractogram.truncate(1);
+    assert_eq!(tractogram, original.slice(..1));
+
+    let mut other = original.slice(1..);
+    tractogram.append(&mut other);
+    assert_eq!(tractogram, original);
+    assert_eq!(other.streamlines.len(), 0);
+
+    let concat =
+        Tractogram::concat(vec![original.slice(..1), original.slice(1..1), original.slice(1..)]);
+    assert_eq!(concat, original);
+}
+
+#[test]
+fn test_tractogram_mutations_without_data() {
+    let empty = || ArraySequence::empty();
+    let mut tractogram = Tractogram::new(get_toy_streamlines(), empty(), empty());
+
+    let (_, scalars, properties) = tractogram.remove(0);
+    assert!(scalars.is_empty() && properties.is_empty());
+    tractogram.insert(0, (&[Point::origin()], &[], &[]));
+    tractogram.retain(|(streamline, _, _)| streamline.len() > 1);
+    assert_eq!(tractogram.streamlines, get_toy_streamlines().slice(1..));
+    assert!(tractogram.scalars.is_empty() && tractogram.properties.is_empty());
+
+    // An empty tractogram can receive anything
+    let mut new = Tractogram::new(ArraySequence::empty(), empty(), empty());
+    new.append(&mut get_toy_tractogram());
+    assert_eq!(new, get_toy_tractogram());
+}
+
+#[test]
+#[should_panic]
+fn test_tractogram_append_misaligned() {
+    let empty = || ArraySequence::empty();
+    let mut tractogram = Tractogram::new(get_toy_streamlines(), empty(), empty());
+    tractogram.append(&mut get_toy_tractogram());
+}
+
+#[test]
+fn test_tractogram_filter() {
+    // Lengths are 1, 2 and 2 mm, and the points are 2, 3 and 3
+    let tractogram = get_toy_tractogram();
+
+    let filtered = tractogram.filter(|(_, _, properties)| properties[0] != 20.0);
+    assert!(filtered.item(0) == tractogram.item(0));
+    assert!(filtered.item(1) == tractogram.item(2));
+    assert_eq!(filtered.streamlines.len(), 2);
+
+    assert_eq!(tractogram.filter_by_length(1.5, 2.5), tractogram.slice(1..));
+    assert_eq!(tractogram.filter_by_length(0.0, 1.0), tractogram.slice(..1));
+    assert_eq!(tractogram.filter_by_length(3.0, f32::INFINITY), tractogram.slice(0..0));
+    assert_eq!(tractogram.filter_by_nb_points(3, 3), tractogram.slice(1..));
+    assert_eq!(tractogram.filter_by_nb_points(0, usize::MAX), tractogram);
+
+    // Empty streamlines are kept aligned with their properties
+    let streamlines =
+        ArraySequence::new(vec![0, 2], vec![Point::origin(), Point::new(1.0, 0.0, 0.0)]);
+    let properties = ArraySequence::new(vec![1, 1], vec![1.0, 2.0]);
+    let tractogram = Tractogram::new(streamlines, ArraySequence::empty(), properties);
+    let filtered = tractogram.filter_by_nb_points(0, 1);
+    assert_eq!(filtered.streamlines.offsets, vec![0, 0]);
+    assert_eq!(filtered.properties, ArraySequence::new(vec![1], vec![1.0]));
+    assert!(filtered.scalars.is_empty());
+}
+
+#[test]
+fn test_index_mut() {
+    let mut arr = ArraySequence::new(vec![2, 3], (0..5).collect::<Vec<i32>>());
+    arr[1][0] = 10;
+    arr[0].reverse();
+    assert_eq!(arr.data, vec![1, 0, 10, 3, 4]);
+}
+
+#[test]
+fn test_tractogram_reverse() {
+    let mut tractogram = get_toy_tractogram();
+    // Two scalars per point
+    tractogram.scalars = ArraySequence::new(vec![4, 6, 6], (0..16).map(|i| i as f32).collect());
+    let original = tractogram.clone();
+
+    tractogram.reverse(1);
+    let (streamline, scalars, properties) = tractogram.item(1);
+    let expected = original.streamlines[1].iter().rev().cloned().collect::<Vec<_>>();
+    assert_eq!(streamline, &expected[..]);
+    assert_eq!(scalars, &[8.0, 9.0, 6.0, 7.0, 4.0, 5.0]);
+    assert_eq!(properties, &original.properties[1]);
+    assert!(tractogram.item(0) == original.item(0));
+
+    tractogram.reverse(1);
+    assert_eq!(tractogram, original);
+}
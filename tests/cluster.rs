@@ -0,0 +1,86 @@
+mod test;
+
+use test::{line, load_trk, streamlines};
+use trk_io::{cluster::QuickBundles, Error, Point, Streamlines};
+
+/// The x of a straight line from 0 to 10. Reversed if `reversed`.
+fn xs(reversed: bool) -> Vec<f32> {
+    let mut xs = (0..=10).map(|x| x as f32).collect::<Vec<_>>();
+    if reversed {
+        xs.reverse();
+    }
+    xs
+}
+
+#[test]
+fn test_quickbundles() {
+    let streamlines = streamlines(&[
+        line(0.0, &xs(false)),
+        line(1.0, &xs(false)),
+        line(20.0, &xs(false)),
+        line(21.0, &xs(true)),
+        line(0.5, &xs(true)),
+    ]);
+    let clusters = QuickBundles::new(5.0).cluster(&streamlines).unwrap();
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters.assignments, vec![0, 0, 1, 1, 0]);
+    assert_eq!(clusters.sizes, vec![3, 2]);
+    assert_eq!(clusters.members(0), vec![0, 1, 4]);
+    assert_eq!(clusters.members(1), vec![2, 3]);
+
+    // The reversed streamlines are flipped before being averaged
+    assert_eq!(clusters.centroids.len(), 2);
+    assert_eq!(clusters.centroids[0].len(), 12);
+    let first = &clusters.centroids[0];
+    assert!((first[0] - Point::new(0.0, 0.5, 0.0)).norm() < 1e-5);
+    assert!((first[11] - Point::new(10.0, 0.5, 0.0)).norm() < 1e-5);
+    let second = &clusters.centroids[1];
+    assert!((second[0] - Point::new(0.0, 20.5, 0.0)).norm() < 1e-5);
+}
+
+#[test]
+fn test_quickbundles_threshold() {
+    let streamlines =
+        streamlines(&[line(0.0, &xs(false)), line(1.0, &xs(false)), line(2.0, &xs(false))]);
+    assert_eq!(QuickBundles::new(0.5).cluster(&streamlines).unwrap().len(), 3);
+    // The centroid moves to y = 0.5 after the second line
+    assert_eq!(QuickBundles::new(1.5).cluster(&streamlines).unwrap().assignments, vec![0, 0, 1]);
+    assert_eq!(QuickBundles::new(1.6).cluster(&streamlines).unwrap().assignments, vec![0, 0, 0]);
+
+    let clusters = QuickBundles::new(1.6).nb_points(3).cluster(&streamlines).unwrap();
+    assert_eq!(clusters.centroids.offsets, vec![0, 3]);
+}
+
+#[test]
+fn test_quickbundles_empty() {
+    let clusters = QuickBundles::new(5.0).cluster(&Streamlines::empty()).unwrap();
+    assert!(clusters.is_empty());
+    assert!(clusters.centroids.is_empty());
+    assert!(clusters.assignments.is_empty());
+}
+
+#[test]
+fn test_quickbundles_empty_streamline() {
+    let points = [line(0.0, &xs(false)), line(1.0, &xs(false))].concat();
+    let streamlines = Streamlines::new(vec![11, 0, 11], points);
+    match QuickBundles::new(5.0).cluster(&streamlines) {
+        Err(Error::InvalidInput(msg)) => assert_eq!(msg, "Streamline 1 is empty"),
+        r => panic!("Expected an InvalidInput error, got {:?}", r),
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_quickbundles_no_points() {
+    QuickBundles::new(5.0).nb_points(0);
+}
+
+#[test]
+fn test_quickbundles_standard() {
+    let streamlines = load_trk("data/standard.trk").1.streamlines;
+    let clusters = QuickBundles::new(2.0).cluster(&streamlines).unwrap();
+    assert_eq!(clusters.assignments.len(), streamlines.len());
+    assert_eq!(clusters.sizes.iter().sum::<usize>(), streamlines.len());
+    assert_eq!(clusters.centroids.len(), clusters.len());
+    assert!(1 < clusters.len() && clusters.len() < streamlines.len());
+}
@@ -0,0 +1,126 @@
+mod test;
+
+use test::{load_trk, x_line};
+use trk_io::{
+    compress::{
+        compress_indices, compress_item, compress_streamline, compress_streamlines,
+        compress_tractogram,
+    },
+    ArraySequence, Point, Streamlines, Tractogram,
+};
+
+#[test]
+fn test_compress_straight_line() {
+    let streamline = x_line(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+    assert_eq!(compress_indices(&streamline, 0.01, 10.0), vec![0, 5]);
+    assert_eq!(compress_streamline(&streamline, 0.01, 10.0), x_line(&[0.0, 5.0]));
+
+    // The segments can't be longer than 2 mm
+    assert_eq!(compress_indices(&streamline, 0.01, 2.0), vec![0, 2, 4, 5]);
+}
+
+#[test]
+fn test_compress_keeps_corners() {
+    let streamline = vec![
+        Point::new(0.0, 0.0, 0.0),
+        Point::new(1.0, 0.0, 0.0),
+        Point::new(2.0, 0.0, 0.0),
+        Point::new(2.0, 1.0, 0.0),
+        Point::new(2.0, 2.0, 0.0),
+    ];
+    assert_eq!(compress_indices(&streamline, 0.01, 10.0), vec![0, 2, 4]);
+
+    // A high tolerance accepts to cut the corner
+    assert_eq!(compress_indices(&streamline, 1.5, 10.0), vec![0, 4]);
+}
+
+#[test]
+fn test_compress_tolerance() {
+    // A small wiggle of 0.005 mm is removed with the default tolerance, but not a bigger one
+    let streamline =
+        vec![Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.005, 0.0), Point::new(2.0, 0.0, 0.0)];
+    assert_eq!(compress_indices(&streamline, 0.01, 10.0), vec![0, 2]);
+    let streamline =
+        vec![Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.05, 0.0), Point::new(2.0, 0.0, 0.0)];
+    assert_eq!(compress_indices(&streamline, 0.01, 10.0), vec![0, 1, 2]);
+}
+
+#[test]
+fn test_compress_degenerate() {
+    assert!(compress_indices(&[], 0.01, 10.0).is_empty());
+    assert_eq!(compress_indices(&x_line(&[4.0]), 0.01, 10.0), vec![0]);
+    assert_eq!(compress_indices(&x_line(&[4.0, 4.0]), 0.01, 10.0), vec![0, 1]);
+    assert_eq!(compress_indices(&x_line(&[4.0, 4.0, 4.0]), 0.01, 10.0), vec![0, 2]);
+}
+
+#[test]
+fn test_compress_streamlines() {
+    let streamlines =
+        Streamlines::new(vec![2, 4], [x_line(&[0.0, 4.0]), x_line(&[0.0, 1.0, 2.0, 3.0])].concat());
+    let compressed = compress_streamlines(&streamlines, 0.01, 10.0);
+    assert_eq!(compressed.offsets, vec![0, 2, 4]);
+    assert_eq!(compressed[1], x_line(&[0.0, 3.0]));
+}
+
+#[test]
+fn test_compress_tractogram_scalars() {
+    // Two scalars per point: x and 2 * x
+    let streamlines = Streamlines::new(vec![3], x_line(&[0.0, 1.0, 10.0]));
+    let scalars = ArraySequence::new(vec![6], vec![0.0, 0.0, 1.0, 2.0, 10.0, 20.0]);
+    let properties = ArraySequence::new(vec![1], vec![42.0]);
+    let tractogram = Tractogram::new(streamlines, scalars, properties.clone());
+
+    let compressed = compress_tractogram(&tractogram, 0.01, 10.0);
+    assert_eq!(compressed.streamlines[0], x_line(&[0.0, 10.0]));
+    assert_eq!(compressed.scalars[0], [0.0, 0.0, 10.0, 20.0]);
+    assert_eq!(compressed.properties, properties);
+
+    let item = (x_line(&[0.0, 1.0, 10.0]), tractogram.scalars.clone(), vec![42.0]);
+    let (streamline, scalars, properties) = compress_item(item, 0.01, 10.0);
+    assert_eq!(streamline, x_line(&[0.0, 10.0]));
+    assert_eq!(scalars.offsets, vec![0, 4]);
+    assert_eq!(scalars[0], [0.0, 0.0, 10.0, 20.0]);
+    assert_eq!(properties, vec![42.0]);
+}
+
+#[test]
+fn test_compress_complex() {
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let nb_scalars = header.scalars_name.len();
+    let compressed = compress_tractogram(&tractogram, 0.01, 10.0);
+    assert_eq!(compressed.streamlines.len(), 3);
+    assert_eq!(compressed.scalars.len(), 3);
+    assert_eq!(compressed.properties, tractogram.properties);
+    for (original, (streamline, scalars, _)) in tractogram.streamlines.iter().zip(&compressed) {
+        assert!(streamline.len() <= original.len());
+        assert_eq!(scalars.len(), streamline.len() * nb_scalars);
+        assert_eq!(streamline[0], original[0]);
+        assert_eq!(streamline[streamline.len() - 1], original[original.len() - 1]);
+    }
+}
+
+#[test]
+fn test_compress_empty_streamline() {
+    let streamlines =
+        Streamlines::new(vec![3, 0, 2], [x_line(&[0.0, 1.0, 2.0]), x_line(&[1.0, 3.0])].concat());
+    assert_eq!(compress_streamlines(&streamlines, 0.01, 10.0).offsets, vec![0, 2, 2, 4]);
+
+    let scalars = ArraySequence::new(vec![3, 0, 2], vec![0.0, 1.0, 2.0, 1.0, 3.0]);
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![1.0, 2.0, 3.0]);
+    let tractogram = Tractogram::new(streamlines, scalars, properties.clone());
+    let compressed = compress_tractogram(&tractogram, 0.01, 10.0);
+    assert_eq!(compressed.streamlines.len(), 3);
+    assert_eq!(compressed.scalars.offsets, vec![0, 2, 2, 4]);
+    assert_eq!(compressed.properties, properties);
+
+    // Same result as the streaming path
+    for (i, (streamline, scalars, properties)) in tractogram.into_iter().enumerate() {
+        let item = (
+            streamline.to_vec(),
+            ArraySequence::new(vec![scalars.len()], scalars.to_vec()),
+            properties.to_vec(),
+        );
+        let (streamline, scalars, properties) = compress_item(item, 0.01, 10.0);
+        assert_eq!(compressed.item(i), (&streamline[..], &scalars.data[..], &properties[..]));
+    }
+}
@@ -0,0 +1,131 @@
+#![cfg(any(feature = "gzip", feature = "zstd"))]
+
+mod test;
+
+#[cfg(feature = "gzip")]
+use std::io::Write;
+use std::{
+    fs::{self, File},
+    io::Read,
+};
+
+use anyhow::Result;
+use tempfile::TempDir;
+
+use test::{get_random_trk_path, load_trk, temp_path};
+#[cfg(feature = "gzip")]
+use trk_io::Header;
+use trk_io::{Compression, TrkInput, Writer};
+#[cfg(feature = "zstd")]
+use trk_io::{Reader, TrkIndex};
+
+/// Write `data/complex.trk` in `path` and check that it's read back identically.
+fn check_round_trip(path: &str, compression: Compression) -> Result<()> {
+    let (header, tractogram) = load_trk("data/complex.trk");
+    {
+        let mut writer = Writer::new(path, Some(&header))?;
+        writer.write(tractogram.clone())?;
+        let stats = writer.finish()?;
+        assert_eq!(stats.nb_streamlines, 3);
+    }
+
+    let mut magic = [0u8; 4];
+    File::open(path)?.read_exact(&mut magic)?;
+    assert_eq!(Compression::detect(&magic), compression);
+    assert_eq!(TrkInput::open(path)?.compression(), compression);
+
+    let (read_header, read_tractogram) = load_trk(path);
+    assert_eq!(read_header.nb_streamlines, 3);
+    assert!((read_header, read_tractogram) == (header, tractogram));
+    Ok(())
+}
+
+/// The decompressed bytes must be exactly those of an uncompressed file.
+fn check_decompressed(path: &str, mut decompressed: impl Read) -> Result<()> {
+    let plain_path = get_random_trk_path();
+    let (header, tractogram) = load_trk(path);
+    let mut writer = Writer::new(&plain_path, Some(&header))?;
+    writer.write(tractogram)?;
+    writer.finish()?;
+
+    let mut bytes = vec![];
+    decompressed.read_to_end(&mut bytes)?;
+    assert_eq!(bytes, fs::read(&plain_path)?);
+    Ok(())
+}
+
+#[test]
+fn test_compression_from_extension() {
+    assert_eq!(Compression::from_extension("a/b.trk.gz"), Compression::Gzip);
+    assert_eq!(Compression::from_extension("b.trk.ZST"), Compression::Zstd);
+    assert_eq!(Compression::from_extension("b.trk"), Compression::None);
+    assert_eq!(Compression::from_extension("gz"), Compression::None);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_gzip_round_trip() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "complex.trk.gz");
+    check_round_trip(&path, Compression::Gzip)?;
+    check_decompressed(&path, flate2::read::MultiGzDecoder::new(File::open(&path)?))
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_zstd_round_trip() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "complex.trk.zst");
+    check_round_trip(&path, Compression::Zstd)?;
+    check_decompressed(&path, zstd::Decoder::new(File::open(&path)?)?)
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_read_gzip_archive() -> Result<()> {
+    // Compressed by a standard encoder, header included
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "archive.trk.gz");
+    let mut encoder = flate2::write::GzEncoder::new(File::create(&path)?, Default::default());
+    encoder.write_all(&fs::read("data/complex_big_endian.trk")?)?;
+    encoder.finish()?;
+
+    assert!(load_trk(&path) == load_trk("data/complex_big_endian.trk"));
+    assert!(Header::from_trk(&path)? == Header::from_trk("data/complex_big_endian.trk")?);
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn test_read_zstd_archive() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "archive.trk.zst");
+    fs::write(&path, zstd::encode_all(&fs::read("data/standard.trk")?[..], 3)?)?;
+
+    assert!(load_trk(&path) == load_trk("data/standard.trk"));
+
+    // Streamlines can be read one by one, but a compressed file can't seek
+    let index = TrkIndex::build("data/standard.trk")?;
+    let mut reader = Reader::new(&path)?;
+    assert_eq!(reader.by_ref().count(), index.len());
+    assert!(reader.item_at(&index, 0).is_err());
+    Ok(())
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_write_gzip_without_finish() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "standard.trk.gz");
+    let (header, tractogram) = load_trk("data/standard.trk");
+    {
+        // `n_count` is patched by `Drop`
+        let mut writer = Writer::new(&path, Some(&header))?;
+        writer.write(tractogram.clone())?;
+    }
+
+    let (read_header, read_tractogram) = load_trk(&path);
+    assert_eq!(read_header.nb_streamlines, tractogram.streamlines.len());
+    assert!(read_tractogram == tractogram);
+    Ok(())
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "nifti_images")]
+
+use ndarray::{arr2, Array2, Array3};
+use nifti::writer::WriterOptions;
+use tempfile::TempDir;
+use trk_io::{
+    connectivity::{Atlas, ConnectivityWeight},
+    ArraySequence, Error, Header, Point, Reader, Spacing, Streamlines, Tractogram,
+};
+
+/// A 4x4x4 atlas with 2x1x1 mm voxels. Label 1 at [0, 0, 0], 2 at [3, 0, 0] and 5 at [0, 3, 0].
+fn atlas() -> Atlas {
+    let mut data = Array3::zeros([4, 4, 4]);
+    data[[0, 0, 0]] = 1;
+    data[[3, 0, 0]] = 2;
+    data[[0, 3, 0]] = 5;
+    Atlas::new(data, Spacing::new(2.0, 1.0, 1.0))
+}
+
+fn tractogram() -> Tractogram {
+    let x_line = |xs: &[f32]| xs.iter().map(|&x| Point::new(x, 0.5, 0.5)).collect::<Vec<_>>();
+    let streamlines = [
+        x_line(&[0.5, 3.5]),
+        x_line(&[0.5, 1.5, 3.5]),
+        x_line(&[3.5, 0.5]),
+        vec![Point::new(0.5, 0.5, 0.5), Point::new(0.5, 3.5, 0.5)],
+        x_line(&[1.5, 2.5]),
+    ];
+    let mut arr = Streamlines::empty();
+    for streamline in streamlines {
+        arr.extend(streamline);
+    }
+    let properties = ArraySequence::new(vec![1; 5], vec![1.0, 2.0, 6.0, 4.0, 5.0]);
+    Tractogram::new(arr, ArraySequence::empty(), properties)
+}
+
+#[test]
+fn test_atlas_labels() {
+    let atlas = atlas();
+    assert_eq!(atlas.shape(), [4, 4, 4]);
+    assert_eq!(atlas.labels(), &[1, 2, 5]);
+    assert_eq!(atlas.label(&Point::new(0.5, 0.5, 0.5)), 1);
+    assert_eq!(atlas.label(&Point::new(3.9, 0.2, 0.7)), 2);
+    assert_eq!(atlas.label(&Point::new(1.5, 0.5, 0.5)), 0);
+    assert_eq!(atlas.label(&Point::new(-0.5, 0.5, 0.5)), 0);
+    assert_eq!(atlas.label(&Point::new(4.5, 0.5, 0.5)), 0);
+
+    let streamline = [Point::new(3.5, 0.5, 0.5), Point::new(0.5, 0.5, 0.5)];
+    assert_eq!(atlas.endpoint_labels(&streamline), Some((2, 1)));
+    assert_eq!(atlas.endpoint_labels(&streamline[..1]), Some((2, 2)));
+    assert_eq!(atlas.endpoint_labels(&[]), None);
+}
+
+#[test]
+fn test_connectivity_count() {
+    let connectivity =
+        atlas().connectivity(&tractogram(), ConnectivityWeight::Count, false).unwrap();
+    assert_eq!(connectivity.labels, vec![1, 2, 5]);
+    assert_eq!(connectivity.matrix, arr2(&[[0.0, 3.0, 1.0], [3.0, 0.0, 0.0], [1.0, 0.0, 0.0]]));
+    assert!(connectivity.assignments.is_none());
+}
+
+#[test]
+fn test_connectivity_means() {
+    let atlas = atlas();
+    let connectivity =
+        atlas.connectivity(&tractogram(), ConnectivityWeight::MeanLength, false).unwrap();
+    // 3 voxels of 2 mm and 3 voxels of 1 mm
+    assert_eq!(connectivity.matrix, arr2(&[[0.0, 6.0, 3.0], [6.0, 0.0, 0.0], [3.0, 0.0, 0.0]]));
+
+    let connectivity =
+        atlas.connectivity(&tractogram(), ConnectivityWeight::MeanProperty(0), false).unwrap();
+    assert_eq!(connectivity.matrix, arr2(&[[0.0, 3.0, 4.0], [3.0, 0.0, 0.0], [4.0, 0.0, 0.0]]));
+
+    let result = atlas.connectivity(&tractogram(), ConnectivityWeight::MeanProperty(1), false);
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+
+    // Nothing to average in an empty tractogram
+    let empty =
+        Tractogram::new(Streamlines::empty(), ArraySequence::empty(), ArraySequence::empty());
+    let connectivity = atlas.connectivity(&empty, ConnectivityWeight::MeanProperty(0), false);
+    assert_eq!(connectivity.unwrap().matrix, Array2::<f32>::zeros([3, 3]));
+
+    // The last streamline doesn't have a second property
+    let mut tractogram = tractogram();
+    tractogram.properties = ArraySequence::new(vec![2, 2, 2, 2, 1], vec![1.0; 9]);
+    let result = atlas.connectivity(&tractogram, ConnectivityWeight::MeanProperty(1), false);
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+}
+
+#[test]
+fn test_connectivity_assignments() {
+    let connectivity =
+        atlas().connectivity(&tractogram(), ConnectivityWeight::Count, true).unwrap();
+    assert_eq!(
+        connectivity.assignments,
+        Some(vec![Some((1, 2)), Some((1, 2)), Some((2, 1)), Some((1, 5)), None])
+    );
+}
+
+#[test]
+fn test_connectivity_property_name() {
+    let mut header = Header::default();
+    header.add_property("fa").unwrap();
+    header.add_property("md").unwrap();
+    assert_eq!(
+        ConnectivityWeight::mean_property(&header, "md").unwrap(),
+        ConnectivityWeight::MeanProperty(1)
+    );
+    match ConnectivityWeight::mean_property(&header, "rd") {
+        Err(Error::InvalidHeader(_)) => {}
+        _ => panic!("An unknown property should be refused"),
+    }
+}
+
+#[test]
+fn test_atlas_from_nifti() {
+    let mut data = Array3::<f32>::zeros([4, 4, 4]);
+    data[[1, 2, 3]] = 7.0;
+    data[[0, 0, 0]] = 2.0;
+    let path = TempDir::new().unwrap().keep().join("atlas.nii.gz");
+    WriterOptions::new(&path).write_nifti(&data).unwrap();
+
+    let atlas = Atlas::from_nifti(&path).unwrap();
+    assert_eq!(atlas.shape(), [4, 4, 4]);
+    assert_eq!(atlas.labels(), &[2, 7]);
+    assert_eq!(atlas.label(&Point::new(1.5, 2.5, 3.5)), 7);
+}
+
+#[test]
+fn test_connectivity_trk() {
+    // One label per slice along x
+    let spacing = Spacing::new(1.0, 3.0, 2.0);
+    let data = Array3::from_shape_fn([4, 5, 7], |(i, _, _)| i as i32 + 1);
+    let atlas = Atlas::new(data, spacing);
+
+    let tractogram =
+        Reader::new("data/standard.trk").unwrap().to_voxel_space(spacing).tractogram().unwrap();
+    let expected = atlas.connectivity(&tractogram, ConnectivityWeight::MeanLength, true).unwrap();
+    let connectivity =
+        atlas.connectivity_trk("data/standard.trk", ConnectivityWeight::MeanLength, true).unwrap();
+    assert_eq!(connectivity, expected);
+    assert_eq!(connectivity.assignments.unwrap().len(), 120);
+    assert!(connectivity.matrix.sum() > 0.0);
+
+    // standard.trk doesn't have any property
+    let result =
+        atlas.connectivity_trk("data/standard.trk", ConnectivityWeight::MeanProperty(0), false);
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+
+    let wrong_grid = Atlas::new(Array3::zeros([4, 4, 4]), spacing);
+    match wrong_grid.connectivity_trk("data/standard.trk", ConnectivityWeight::Count, false) {
+        Err(Error::InvalidHeader(_)) => {}
+        _ => panic!("An atlas on another grid should be refused"),
+    }
+}
@@ -0,0 +1,200 @@
+mod test;
+
+use std::io::Cursor;
+
+use anyhow::Result;
+
+use test::{get_random_trk_path, load_trk};
+use trk_io::{
+    ArraySequence, DataPerPoint, DataPerStreamline, Error, Header, Point, Reader, Streamlines,
+    Tractogram, Writer,
+};
+
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_read_data_per_point_and_per_streamline() -> Result<()> {
+    let (header, tractogram) = load_trk("data/complex.trk");
+
+    let data_per_point = tractogram.data_per_point(&header)?;
+    assert_eq!(data_per_point.len(), 2);
+    assert_eq!(data_per_point.nb_rows(), tractogram.streamlines.data.len());
+    assert_eq!(data_per_point.nb_components(), 4);
+    let colors = data_per_point.get("colors").unwrap();
+    assert_eq!(colors.nb_components, 3);
+    assert_eq!(colors.row(0), &tractogram.scalars[0][..3]);
+    assert_eq!(data_per_point.get("fa").unwrap().row(1), &tractogram.scalars.data[7..8]);
+    assert!(!data_per_point.contains("color"));
+
+    let data_per_streamline = tractogram.data_per_streamline(&header)?;
+    let names = data_per_streamline.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["mean_colors", "mean_curvature", "mean_torsion"]);
+    let mean_colors = data_per_streamline.get("mean_colors").unwrap();
+    assert_eq!(mean_colors.rows().count(), 3);
+    assert_eq!(mean_colors.row(2), &tractogram.properties[2][..3]);
+    Ok(())
+}
+
+#[test]
+fn test_columns_round_trip() -> Result<()> {
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let data = tractogram.data_per_point(&header)?;
+    let (names, columns) = data.to_columns();
+    assert_eq!(names, header.scalars_name);
+    assert_eq!(columns, tractogram.scalars.data);
+    assert_eq!(DataPerPoint::from_columns(&names, &columns)?, data);
+    Ok(())
+}
+
+#[test]
+fn test_from_columns_errors() {
+    let data = [0.0; 6];
+    let result = DataPerPoint::from_columns(&names(&["a", "b", "a"]), &data);
+    assert!(matches!(result, Err(Error::InvalidName(_))));
+    let result = DataPerPoint::from_columns(&names(&["a", "b", "c", "d"]), &data);
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+    let result = DataPerStreamline::from_columns(&[], &data);
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+    assert!(DataPerStreamline::from_columns(&[], &[]).unwrap().is_empty());
+}
+
+#[test]
+fn test_insert_and_remove() {
+    let mut data = DataPerStreamline::new();
+    assert_eq!(data.insert("color", 3, vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]), None);
+    assert_eq!(data.insert("length", 1, vec![10.0, 20.0]), None);
+    assert_eq!(data.nb_rows(), 2);
+
+    // Replacing an array keeps its position
+    let old = data.insert("color", 1, vec![7.0, 8.0]).unwrap();
+    assert_eq!(old.nb_components, 3);
+    assert_eq!(data.to_columns(), (names(&["color", "length"]), vec![7.0, 10.0, 8.0, 20.0]));
+
+    data.get_mut("length").unwrap().data[1] = 30.0;
+    assert_eq!(data.remove("length").unwrap().data, [10.0, 30.0]);
+    assert_eq!(data.remove("length"), None);
+    assert_eq!(data.len(), 1);
+}
+
+#[test]
+#[should_panic]
+fn test_insert_wrong_nb_rows() {
+    let mut data = DataPerPoint::new();
+    data.insert("color", 3, vec![0.0; 6]);
+    data.insert("fa", 1, vec![0.0; 3]);
+}
+
+#[test]
+fn test_write_data_per_point_and_per_streamline() -> Result<()> {
+    let (mut header, mut tractogram) = load_trk("data/simple.trk");
+    let nb_points = tractogram.streamlines.data.len();
+    let nb_streamlines = tractogram.streamlines.len();
+
+    let mut data_per_point = DataPerPoint::new();
+    data_per_point.insert("color", 3, (0..nb_points * 3).map(|i| i as f32).collect());
+    data_per_point.insert("fa", 1, (0..nb_points).map(|i| i as f32 / 10.0).collect());
+    let mut data_per_streamline = DataPerStreamline::new();
+    data_per_streamline.insert("mean_rgba", 4, vec![0.5; nb_streamlines * 4]);
+    tractogram.set_data_per_point(&mut header, &data_per_point)?;
+    tractogram.set_data_per_streamline(&mut header, &data_per_streamline)?;
+    assert_eq!(header.scalars_name, ["color", "color", "color", "fa"]);
+    assert_eq!(tractogram.scalars[1].len(), 4 * tractogram.streamlines[1].len());
+
+    // Multi-component names are saved once, with a repeat count
+    let raw = header.raw_header();
+    assert_eq!(raw.n_scalars, 4);
+    assert_eq!(&raw.scalar_name[..8], b"color\x003\x00");
+    assert_eq!(&raw.scalar_name[20..23], b"fa\x00");
+    assert_eq!(raw.n_properties, 4);
+    assert_eq!(&raw.property_name[..12], b"mean_rgba\x004\x00");
+    assert_eq!(raw.property_name[20], 0);
+
+    let write_to = get_random_trk_path();
+    {
+        let mut writer = Writer::new(&write_to, Some(&header))?;
+        writer.write(tractogram.clone())?;
+    }
+    let (read_header, read_tractogram) = load_trk(&write_to);
+    assert_eq!(read_header.raw_header().scalar_name, raw.scalar_name);
+    assert_eq!(read_tractogram.data_per_point(&read_header)?, data_per_point);
+    assert_eq!(read_tractogram.data_per_streamline(&read_header)?, data_per_streamline);
+
+    // Removing all arrays removes the scalars
+    tractogram.set_data_per_point(&mut header, &DataPerPoint::new())?;
+    assert!(tractogram.scalars.is_empty());
+    assert!(header.scalars_name.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_write_more_than_10_components() -> Result<()> {
+    let (mut header, mut tractogram) = load_trk("data/simple.trk");
+    let nb_points = tractogram.streamlines.data.len();
+
+    let mut data_per_point = DataPerPoint::new();
+    data_per_point.insert("tensor", 12, (0..nb_points * 12).map(|i| i as f32).collect());
+    data_per_point.insert("fa", 1, vec![0.5; nb_points]);
+    tractogram.set_data_per_point(&mut header, &data_per_point)?;
+    let raw = header.raw_header();
+    assert_eq!(raw.n_scalars, 13);
+    assert_eq!(&raw.scalar_name[..10], b"tensor\x0012\x00");
+
+    let mut cursor = Cursor::new(vec![]);
+    let mut writer = Writer::from_writer(&mut cursor, Some(&header))?;
+    writer.write(tractogram.clone())?;
+    writer.finish()?;
+
+    cursor.set_position(0);
+    let mut reader = Reader::from_reader(cursor)?;
+    let read_header = reader.header.clone();
+    let read_tractogram = reader.tractogram()?;
+    assert_eq!(read_header.scalars_name, header.scalars_name);
+    assert_eq!(read_tractogram, tractogram);
+    assert_eq!(read_tractogram.data_per_point(&read_header)?, data_per_point);
+    Ok(())
+}
+
+#[test]
+fn test_copy_keeps_repeat_count() -> Result<()> {
+    let (header, _) = load_trk("data/complex.trk");
+    let mut copy = Header::default();
+    copy.copy_scalars_and_properties(&header);
+    assert!(copy.scalars_name == header.scalars_name);
+    assert_eq!(copy.raw_header().scalar_name, header.raw_header().scalar_name);
+    assert_eq!(copy.raw_header().property_name, header.raw_header().property_name);
+    Ok(())
+}
+
+#[test]
+fn test_set_too_many_names() -> Result<()> {
+    let (mut header, mut tractogram) = load_trk("data/simple.trk");
+    let nb_streamlines = tractogram.streamlines.len();
+    let mut data = DataPerStreamline::new();
+    for i in 0..11 {
+        data.insert(&format!("property_{}", i), 1, vec![0.0; nb_streamlines]);
+    }
+
+    let (old_header, old_tractogram) = (header.clone(), tractogram.clone());
+    let result = tractogram.set_data_per_streamline(&mut header, &data);
+    assert!(matches!(result, Err(Error::TooManyNames { .. })));
+    assert!(header == old_header);
+    assert_eq!(tractogram, old_tractogram);
+    Ok(())
+}
+
+#[test]
+fn test_set_data_per_point_empty_streamline() -> Result<()> {
+    let streamlines = Streamlines::new(vec![2, 0, 1], vec![Point::origin(); 3]);
+    let mut tractogram =
+        Tractogram::new(streamlines, ArraySequence::empty(), ArraySequence::empty());
+    let mut header = Header::default();
+    let mut data = DataPerPoint::new();
+    data.insert("fa", 1, vec![1.0, 2.0, 3.0]);
+    tractogram.set_data_per_point(&mut header, &data)?;
+    assert_eq!(tractogram.scalars.offsets, vec![0, 2, 2, 3]);
+    let scalars = tractogram.into_iter().map(|(_, scalars, _)| scalars).collect::<Vec<_>>();
+    assert_eq!(scalars, [&[1.0, 2.0][..], &[], &[3.0]]);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+mod test;
+
+use test::{line, load_trk, streamlines};
+use trk_io::{
+    distance::{bundle_distance, mam, mam_matrix, mdf, mdf_matrix, Mam},
+    resample::resample_streamlines,
+    Streamlines,
+};
+
+#[test]
+fn test_mdf() {
+    let a = line(0.0, &[0.0, 1.0, 2.0]);
+    assert_eq!(mdf(&a, &a), 0.0);
+    assert_eq!(mdf(&a, &line(2.0, &[0.0, 1.0, 2.0])), 2.0);
+    // Flipped
+    assert_eq!(mdf(&a, &line(0.0, &[2.0, 1.0, 0.0])), 0.0);
+    assert_eq!(mdf(&a, &line(1.0, &[2.0, 1.0, 0.0])), 1.0);
+    // Symmetric
+    let b = line(3.0, &[5.0, 1.0, 0.0]);
+    assert_eq!(mdf(&a, &b), mdf(&b, &a));
+    // Nothing to compare
+    assert_eq!(mdf(&[], &[]), f32::INFINITY);
+}
+
+#[test]
+#[should_panic]
+fn test_mdf_different_lengths() {
+    mdf(&line(0.0, &[0.0, 1.0]), &line(0.0, &[0.0, 1.0, 2.0]));
+}
+
+#[test]
+fn test_mam() {
+    let a = line(0.0, &[0.0, 1.0, 2.0, 3.0]);
+    assert_eq!(mam(&a, &a, Mam::Mean), 0.0);
+
+    // All points of b are on a, but not vice versa: 0 from b to a, 0.75 from a to b
+    let b = line(0.0, &[0.0, 1.0]);
+    assert_eq!(mam(&a, &b, Mam::Min), 0.0);
+    assert_eq!(mam(&a, &b, Mam::Max), 0.75);
+    assert_eq!(mam(&a, &b, Mam::Mean), 0.375);
+    assert_eq!(mam(&b, &a, Mam::Mean), 0.375);
+
+    assert_eq!(mam(&a, &line(1.0, &[3.0, 2.0, 1.0, 0.0]), Mam::Mean), 1.0);
+    assert_eq!(mam(&a, &[], Mam::Mean), f32::INFINITY);
+}
+
+#[test]
+fn test_distance_matrices() {
+    let a = streamlines(&[line(0.0, &[0.0, 1.0]), line(1.0, &[0.0, 1.0])]);
+    let b = streamlines(&[line(0.0, &[1.0, 0.0]), line(3.0, &[0.0, 1.0]), line(0.0, &[0.0, 1.0])]);
+    assert_eq!(mdf_matrix(&a, &b), vec![0.0, 3.0, 0.0, 1.0, 2.0, 1.0]);
+    assert_eq!(mam_matrix(&a, &b, Mam::Mean), vec![0.0, 3.0, 0.0, 1.0, 2.0, 1.0]);
+    assert!(mdf_matrix(&a, &Streamlines::empty()).is_empty());
+}
+
+#[test]
+fn test_bundle_distance() {
+    let a = streamlines(&[line(0.0, &[0.0, 1.0]), line(1.0, &[0.0, 1.0])]);
+    assert_eq!(bundle_distance(&a, &a), 0.0);
+
+    // Rows: 0 and 1. Columns: 0, 2 and 0.
+    let b = streamlines(&[line(0.0, &[1.0, 0.0]), line(3.0, &[0.0, 1.0]), line(0.0, &[0.0, 1.0])]);
+    assert_eq!(bundle_distance(&a, &b), (0.5 + 2.0 / 3.0) / 2.0);
+    assert_eq!(bundle_distance(&a, &b), bundle_distance(&b, &a));
+    assert_eq!(bundle_distance(&a, &Streamlines::empty()), f32::INFINITY);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_distance_parallel() {
+    use trk_io::distance::{bundle_distance_parallel, mam_matrix_parallel, mdf_matrix_parallel};
+
+    let streamlines = load_trk("data/complex.trk").1.streamlines;
+    let resampled = resample_streamlines(&streamlines, 5);
+    assert_eq!(mdf_matrix_parallel(&resampled, &resampled), mdf_matrix(&resampled, &resampled));
+    assert_eq!(
+        mam_matrix_parallel(&streamlines, &resampled, Mam::Max),
+        mam_matrix(&streamlines, &resampled, Mam::Max)
+    );
+    assert_eq!(
+        bundle_distance_parallel(&resampled, &resampled),
+        bundle_distance(&resampled, &resampled)
+    );
+    assert!(mdf_matrix_parallel(&resampled, &Streamlines::empty()).is_empty());
+}
+
+#[test]
+fn test_distance_standard() {
+    let streamlines = resample_streamlines(&load_trk("data/standard.trk").1.streamlines, 10);
+    let matrix = mdf_matrix(&streamlines, &streamlines);
+    let n = streamlines.len();
+    assert_eq!(matrix.len(), n * n);
+    for i in 0..n {
+        assert_eq!(matrix[i * n + i], 0.0);
+        for j in 0..n {
+            assert!((matrix[i * n + j] - matrix[j * n + i]).abs() < 1e-5);
+        }
+    }
+}
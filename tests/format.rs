@@ -0,0 +1,198 @@
+mod test;
+
+use std::fs;
+
+use anyhow::Result;
+use tempfile::TempDir;
+
+use test::{load_trk, temp_path};
+use trk_io::{
+    format::{create, load, open, save, TractogramFormat},
+    ArraySequence, Error,
+};
+
+#[test]
+fn test_from_extension() {
+    assert_eq!(TractogramFormat::from_extension("a/b.trk"), Some(TractogramFormat::Trk));
+    assert_eq!(TractogramFormat::from_extension("b.TCK"), Some(TractogramFormat::Tck));
+    assert_eq!(TractogramFormat::from_extension("b.vtk"), Some(TractogramFormat::Vtk));
+    assert_eq!(TractogramFormat::from_extension("b.vtp"), Some(TractogramFormat::Vtp));
+    assert_eq!(TractogramFormat::from_extension("b.nii.gz"), None);
+    assert_eq!(TractogramFormat::from_extension("b.trk.gz"), Some(TractogramFormat::Trk));
+    assert_eq!(TractogramFormat::from_extension("b.trk.zst"), Some(TractogramFormat::Trk));
+    assert_eq!(TractogramFormat::from_extension("b.tck.gz"), None);
+    assert_eq!(TractogramFormat::from_extension("trk"), None);
+}
+
+#[test]
+fn test_detect_from_content() -> Result<()> {
+    assert_eq!(TractogramFormat::detect("data/simple.trk")?, TractogramFormat::Trk);
+    assert_eq!(TractogramFormat::detect("data/simple.tck")?, TractogramFormat::Tck);
+
+    // The content wins over the extension
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "misnamed.tck");
+    fs::copy("data/simple.trk", &path)?;
+    assert_eq!(TractogramFormat::detect(&path)?, TractogramFormat::Trk);
+    assert_eq!(load(&path)?.1, load_trk("data/simple.trk").1);
+
+    let path = temp_path(&dir, "unknown.dat");
+    fs::write(&path, "Not a tractogram")?;
+    assert!(matches!(TractogramFormat::detect(&path), Err(Error::UnknownFormat(_))));
+    assert!(TractogramFormat::detect(temp_path(&dir, "missing.trk")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_save_and_load_all_formats() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let mut names = vec!["out.trk", "out.vtk", "out.vtp"];
+    if cfg!(feature = "trx") {
+        names.push("out.trx");
+    }
+    for name in names {
+        let path = temp_path(&dir, name);
+        save(&path, &tractogram, &header)?;
+        let (loaded_header, loaded) = load(&path)?;
+        assert_eq!(loaded.streamlines.len(), tractogram.streamlines.len());
+        for (a, b) in loaded.streamlines.data.iter().zip(&tractogram.streamlines.data) {
+            assert!((a - b).norm() < 1e-4, "{}: {} != {}", name, a, b);
+        }
+        assert_eq!(loaded.scalars, tractogram.scalars);
+        assert_eq!(loaded.properties, tractogram.properties);
+        assert_eq!(loaded_header.scalars_name, header.scalars_name);
+        assert_eq!(loaded_header.properties_name, header.properties_name);
+    }
+
+    // A tck file can't hold the scalars nor the properties
+    let path = temp_path(&dir, "out.tck");
+    save(&path, &tractogram, &header)?;
+    let (tck_header, tck) = load(&path)?;
+    assert_eq!(tck_header.nb_streamlines, 3);
+    assert_eq!(tck.streamlines, tractogram.streamlines);
+    assert!(tck.scalars.is_empty());
+    assert!(tck.properties.is_empty());
+
+    assert!(matches!(
+        save(temp_path(&dir, "out.txt"), &tractogram, &header),
+        Err(Error::UnknownFormat(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_stream_between_formats() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (header, tractogram) = load_trk("data/complex.trk");
+    for (input, output) in [("data/complex.trk", "a.vtp"), ("a.vtp", "b.vtk"), ("b.vtk", "c.trk")] {
+        let input =
+            if input.starts_with("data") { input.to_string() } else { temp_path(&dir, input) };
+        let reader = open(&input)?;
+        assert_eq!(reader.header().nb_streamlines, 3);
+        assert_eq!(reader.header().scalars_name, header.scalars_name);
+
+        let mut writer = create(temp_path(&dir, output), &reader.header().clone())?;
+        for item in reader {
+            writer.write_item(item?)?;
+        }
+        let stats = writer.finish()?;
+        assert_eq!(stats.nb_streamlines, 3);
+        assert_eq!(stats.nb_points, tractogram.streamlines.data.len());
+    }
+
+    let (c_header, c) = load_trk(&temp_path(&dir, "c.trk"));
+    assert_eq!(c.scalars, tractogram.scalars);
+    assert_eq!(c.properties, tractogram.properties);
+    assert_eq!(c_header.properties_name, header.properties_name);
+    Ok(())
+}
+
+#[test]
+fn test_names_longer_than_trk_limits() -> Result<()> {
+    // 11 point data arrays, one of them with a name longer than 20 characters
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "long_names.vtk");
+    let mut vtk = "# vtk DataFile Version 4.2\nlong names\nASCII\nDATASET POLYDATA\n\
+                   POINTS 2 float\n0 0 0 1 1 1\nLINES 1 3\n2 0 1\n\
+                   POINT_DATA 2\nFIELD FieldData 11\n"
+        .to_string();
+    vtk.push_str("fractional_anisotropy_of_the_tensor 1 2 float\n0.5 0.6\n");
+    for i in 0..10 {
+        vtk.push_str(&format!("scalar_{} 1 2 float\n{} {}\n", i, i, i));
+    }
+    fs::write(&path, vtk)?;
+
+    let (header, tractogram) = load(&path)?;
+    assert_eq!(header.scalars_name.len(), 11);
+    assert_eq!(header.scalars_name[0], "fractional_anisotropy_of_the_tensor");
+    let reader = open(&path)?;
+    assert!(reader.header().scalars_name == header.scalars_name);
+
+    // Other formats can hold these names, but not trk
+    let vtp_path = temp_path(&dir, "long_names.vtp");
+    save(&vtp_path, &tractogram, &header)?;
+    assert!(load(&vtp_path)?.0.scalars_name == header.scalars_name);
+    let result = save(temp_path(&dir, "long_names.trk"), &tractogram, &header);
+    assert!(matches!(result, Err(Error::InvalidName(_))));
+    Ok(())
+}
+
+#[test]
+fn test_memory_writer_checks_items() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let path = temp_path(&dir, "with_empty.vtp");
+    let mut writer = create(&path, &header)?;
+
+    // An empty streamline, without scalars, followed by the other streamlines
+    writer.write_item((vec![], ArraySequence::empty(), vec![0.0; 5]))?;
+    for item in &tractogram {
+        let (streamline, scalars, properties) = item;
+        let scalars = ArraySequence::new(vec![4; streamline.len()], scalars.to_vec());
+        writer.write_item((streamline.to_vec(), scalars, properties.to_vec()))?;
+    }
+
+    // The scalars and properties must match the header
+    let (streamline, scalars, _) = tractogram.item(0);
+    let scalars = ArraySequence::new(vec![4; streamline.len()], scalars.to_vec());
+    let result = writer.write_item((streamline.to_vec(), scalars, vec![]));
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+    let result = writer.write_item((streamline.to_vec(), ArraySequence::empty(), vec![0.0; 5]));
+    assert!(matches!(result, Err(Error::InvalidHeader(_))));
+
+    assert_eq!(writer.finish()?.nb_streamlines, 4);
+    let (_, loaded) = load(&path)?;
+    assert_eq!(loaded.streamlines.len(), 4);
+    assert!(loaded.streamlines[0].is_empty());
+    assert_eq!(loaded.scalars.data, tractogram.scalars.data);
+    assert_eq!(loaded.properties[1], tractogram.properties[0]);
+    Ok(())
+}
+
+#[test]
+fn test_memory_writer_empty_streamline_with_scalars() -> Result<()> {
+    let dir = TempDir::new()?;
+    let (header, tractogram) = load_trk("data/complex.trk");
+    for name in ["middle_empty.vtk", "middle_empty.vtp"] {
+        let path = temp_path(&dir, name);
+        let mut writer = create(&path, &header)?;
+        for (i, (streamline, scalars, properties)) in tractogram.into_iter().enumerate() {
+            if i == 1 {
+                writer.write_item((vec![], ArraySequence::empty(), vec![0.0; 5]))?;
+            }
+            let scalars = ArraySequence::new(vec![4; streamline.len()], scalars.to_vec());
+            writer.write_item((streamline.to_vec(), scalars, properties.to_vec()))?;
+        }
+        writer.finish()?;
+
+        let (_, loaded) = load(&path)?;
+        assert_eq!(loaded.scalars.len(), 4);
+        let items = loaded.into_iter().collect::<Vec<_>>();
+        assert!(items[1].0.is_empty() && items[1].1.is_empty());
+        assert_eq!(items[0].1, tractogram.item(0).1);
+        assert_eq!(items[2].1, tractogram.item(1).1);
+        assert_eq!(items[3].1, tractogram.item(2).1);
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
+use std::io::Cursor;
+
 use anyhow::Result;
 
-use trk_io::Header;
+use trk_io::{Affine4, Error, Header, Writer};
 
 #[test]
 fn test_copy_scalars_and_properties() -> Result<()> {
@@ -26,6 +28,31 @@ fn test_copy_scalars_and_properties() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_copy_names_too_long_for_trk() {
+    // Other formats can hold more names than a trk header
+    let mut other = Header::default();
+    other.scalars_name = (0..11).map(|i| format!("s{}", i)).collect();
+    other.properties_name = vec!["01234567890123456789a".to_string()];
+
+    let mut header = Header::default();
+    header.copy_scalars_and_properties(&other);
+    assert_eq!(header.scalars_name, other.scalars_name);
+    assert_eq!(header.properties_name, other.properties_name);
+    assert!(matches!(
+        Writer::from_writer(Cursor::new(vec![]), Some(&header)),
+        Err(Error::TooManyNames { max: 10, .. })
+    ));
+
+    header.copy_scalars(&Header::default());
+    assert!(matches!(
+        Writer::from_writer(Cursor::new(vec![]), Some(&header)),
+        Err(Error::InvalidName(_))
+    ));
+    header.copy_properties(&Header::default());
+    assert!(Writer::from_writer(Cursor::new(vec![]), Some(&header)).is_ok());
+}
+
 #[test]
 fn test_add_scalar() -> Result<()> {
     let torsion = "torsion".to_string();
@@ -46,6 +73,18 @@ fn test_too_much_scalars() {
     }
 }
 
+#[test]
+fn test_too_much_names_error() {
+    let mut header = Header::default();
+    for _ in 0..10 {
+        header.add_scalar("test").unwrap();
+        header.add_property("test").unwrap();
+    }
+    assert!(matches!(header.add_scalar("test"), Err(Error::TooManyNames { max: 10, .. })));
+    assert!(matches!(header.add_property("test"), Err(Error::TooManyNames { max: 10, .. })));
+    assert!(matches!(Header::default().add_scalar("平仮名"), Err(Error::InvalidName(_))));
+}
+
 #[test]
 #[should_panic]
 fn test_scalar_name_too_long() {
@@ -93,3 +132,20 @@ fn test_unicode_property() {
     let mut header = Header::default();
     header.add_property("平仮名, ひらがな").unwrap();
 }
+
+#[test]
+fn test_from_affine4() {
+    #[rustfmt::skip]
+    let affine = Affine4::new(
+        -2.0, 0.0, 0.0, 90.0,
+        0.0, 3.0, 0.0, -126.0,
+        0.0, 0.0, 4.0, -72.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    let header = Header::from_affine4(&affine, [91, 109, 91]);
+    let c_header = header.raw_header();
+    assert_eq!(c_header.dim, [91, 109, 91]);
+    assert_eq!(c_header.voxel_size, [2.0, 3.0, 4.0]);
+    assert_eq!(&c_header.voxel_order[..3], b"LAS");
+    assert_eq!(Affine4::from_iterator(c_header.vox_to_ras.iter().cloned()).transpose(), affine);
+}
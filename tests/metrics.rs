@@ -0,0 +1,148 @@
+mod test;
+
+use std::f32::consts::PI;
+
+use test::load_trk;
+use trk_io::{
+    metrics::{
+        add_metrics_as_properties, curvatures, endpoint_distance, length, torsions, tortuosity,
+        Metric,
+    },
+    ArraySequence, Error, Header, Point, Streamlines, Tractogram,
+};
+
+fn helix(radius: f32, pitch: f32, nb_points: usize) -> Vec<Point> {
+    (0..nb_points)
+        .map(|i| {
+            let t = i as f32 * 0.05;
+            Point::new(radius * t.cos(), radius * t.sin(), pitch * t)
+        })
+        .collect()
+}
+
+#[test]
+fn test_straight_line() {
+    let line =
+        vec![Point::new(0.0, 0.0, 0.0), Point::new(1.0, 1.0, 1.0), Point::new(3.0, 3.0, 3.0)];
+    let sqrt_27 = 27.0f32.sqrt();
+    assert!((length(&line) - sqrt_27).abs() < 1e-5);
+    assert!((endpoint_distance(&line) - sqrt_27).abs() < 1e-5);
+    assert!((tortuosity(&line) - 1.0).abs() < 1e-5);
+    assert_eq!(curvatures(&line), vec![0.0]);
+    assert!(torsions(&line).is_empty());
+}
+
+#[test]
+fn test_degenerate() {
+    assert_eq!(length(&[]), 0.0);
+    assert_eq!(endpoint_distance(&[]), 0.0);
+    assert_eq!(tortuosity(&[]), 1.0);
+    assert_eq!(tortuosity(&[Point::origin(), Point::origin()]), 1.0);
+    assert!(curvatures(&[Point::origin(), Point::origin()]).is_empty());
+    assert_eq!(Metric::MeanCurvature.compute(&[Point::origin()]), 0.0);
+    assert_eq!(Metric::MaxCurvature.compute(&[]), 0.0);
+    assert_eq!(Metric::MeanTorsion.compute(&[]), 0.0);
+
+    // A loop
+    let loop_ = vec![Point::origin(), Point::new(1.0, 0.0, 0.0), Point::origin()];
+    assert_eq!(tortuosity(&loop_), f32::INFINITY);
+}
+
+#[test]
+fn test_curvature() {
+    // Right angle. The circumscribed circle has a radius of sqrt(2) / 2
+    let corner =
+        vec![Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(1.0, 1.0, 0.0)];
+    assert!((curvatures(&corner)[0] - 2.0f32.sqrt()).abs() < 1e-5);
+
+    // Points on a circle of radius 2
+    let circle = (0..10)
+        .map(|i| {
+            let t = i as f32 * PI / 10.0;
+            Point::new(2.0 * t.cos(), 2.0 * t.sin(), 0.0)
+        })
+        .collect::<Vec<_>>();
+    for curvature in curvatures(&circle) {
+        assert!((curvature - 0.5).abs() < 1e-4);
+    }
+    assert!((Metric::MeanCurvature.compute(&circle) - 0.5).abs() < 1e-4);
+    for torsion in torsions(&circle) {
+        assert!(torsion.abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_helix() {
+    // Curvature is r / (r² + c²) and torsion is c / (r² + c²)
+    let streamline = helix(1.0, 0.5, 100);
+    assert!((Metric::MeanCurvature.compute(&streamline) - 0.8).abs() < 1e-2);
+    assert!((Metric::MaxCurvature.compute(&streamline) - 0.8).abs() < 1e-2);
+    assert!((Metric::MeanTorsion.compute(&streamline) - 0.4).abs() < 1e-2);
+
+    // The opposite rotation gives a negative torsion
+    let mirrored = streamline.iter().map(|p| Point::new(p.x, -p.y, p.z)).collect::<Vec<_>>();
+    assert!((Metric::MeanTorsion.compute(&mirrored) + 0.4).abs() < 1e-2);
+}
+
+#[test]
+fn test_compute_all() {
+    let streamlines = Streamlines::new(
+        vec![2, 3],
+        vec![
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(2.0, 0.0, 0.0),
+            Point::new(0.0, 0.0, 0.0),
+            Point::new(1.0, 0.0, 0.0),
+            Point::new(1.0, 1.0, 0.0),
+        ],
+    );
+    assert_eq!(Metric::Length.compute_all(&streamlines), vec![2.0, 2.0]);
+    assert_eq!(Metric::EndpointDistance.compute_all(&streamlines), vec![2.0, 2.0f32.sqrt()]);
+}
+
+#[test]
+fn test_add_metrics_as_properties() {
+    let (mut header, mut tractogram) = load_trk("data/complex.trk");
+    let original = tractogram.clone();
+    let nb_properties = header.properties_name.len();
+
+    let metrics = [Metric::Length, Metric::Tortuosity];
+    add_metrics_as_properties(&mut header, &mut tractogram, &metrics).unwrap();
+    assert_eq!(header.properties_name[nb_properties..], ["length", "tortuosity"]);
+    assert_eq!(tractogram.streamlines, original.streamlines);
+    assert_eq!(tractogram.scalars, original.scalars);
+    for (i, (streamline, _, properties)) in tractogram.into_iter().enumerate() {
+        assert_eq!(properties.len(), nb_properties + 2);
+        assert_eq!(properties[..nb_properties], original.properties[i]);
+        assert_eq!(properties[nb_properties], length(streamline));
+        assert_eq!(properties[nb_properties + 1], tortuosity(streamline));
+    }
+
+    // Too many properties, nothing is modified
+    let (old_header, old_tractogram) = (header.clone(), tractogram.clone());
+    let metrics = [Metric::Length; 10];
+    let result = add_metrics_as_properties(&mut header, &mut tractogram, &metrics);
+    assert!(matches!(result, Err(Error::TooManyNames { .. })));
+    assert!(header == old_header);
+    assert_eq!(tractogram, old_tractogram);
+}
+
+#[test]
+fn test_add_metrics_mismatched_properties() {
+    // The header names more properties than the tractogram has
+    let (mut header, tractogram) = load_trk("data/complex.trk");
+    let mut tractogram =
+        Tractogram::new(tractogram.streamlines, tractogram.scalars, ArraySequence::empty());
+    let (old_header, old_tractogram) = (header.clone(), tractogram.clone());
+    let result = add_metrics_as_properties(&mut header, &mut tractogram, &[Metric::Length]);
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+    assert!(header == old_header);
+    assert_eq!(tractogram, old_tractogram);
+
+    // The tractogram has properties that the header doesn't name
+    let (_, mut tractogram) = load_trk("data/complex.trk");
+    let mut header = Header::default();
+    let result = add_metrics_as_properties(&mut header, &mut tractogram, &[Metric::Length]);
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+    assert!(header.properties_name.is_empty());
+}
@@ -0,0 +1,81 @@
+#![cfg(feature = "mmap")]
+
+mod test;
+
+use std::{borrow::Cow, fs};
+
+use anyhow::Result;
+
+use test::{get_random_trk_path, load_trk};
+use trk_io::{Error, MmapTractogram, Reader};
+
+#[test]
+fn test_mmap_same_as_reader() -> Result<()> {
+    for path in
+        ["data/empty.trk", "data/simple.trk", "data/complex.trk", "data/complex_big_endian.trk"]
+    {
+        let (header, tractogram) = load_trk(path);
+        let mmap = MmapTractogram::open(path)?;
+        assert!(mmap.header == header);
+        assert_eq!(mmap.len(), tractogram.streamlines.len());
+        assert_eq!(mmap.offsets(), &tractogram.streamlines.offsets[..]);
+        assert!(mmap.tractogram() == tractogram);
+
+        for (i, item) in Reader::new(path)?.enumerate() {
+            assert!(mmap.item(i) == item?);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_mmap_zero_copy() -> Result<()> {
+    let mmap = MmapTractogram::open("data/simple.trk")?;
+    assert!(mmap.is_zero_copy());
+    assert_eq!(mmap.nb_points(), 8);
+
+    let mut reader = Reader::new("data/simple.trk")?.raw();
+    let streamlines = reader.streamlines()?;
+    for (i, streamline) in streamlines.into_iter().enumerate() {
+        assert_eq!(mmap.raw_points(i), Some(streamline));
+        assert!(matches!(mmap.raw_streamline(i), Cow::Borrowed(_)));
+    }
+
+    // Scalars are interleaved with the points
+    let mmap = MmapTractogram::open("data/complex.trk")?;
+    assert!(!mmap.is_zero_copy());
+    assert_eq!(mmap.raw_points(0), None);
+    assert!(matches!(mmap.raw_streamline(0), Cow::Owned(_)));
+
+    let mmap = MmapTractogram::open("data/complex_big_endian.trk")?;
+    assert!(!mmap.is_zero_copy());
+    Ok(())
+}
+
+#[test]
+fn test_mmap_truncated() -> Result<()> {
+    let path = get_random_trk_path();
+    let bytes = fs::read("data/complex.trk")?;
+    fs::write(&path, &bytes[..bytes.len() - 4])?;
+
+    match MmapTractogram::open(&path) {
+        Err(Error::Truncated { streamline_index: 2, byte_offset: 1132 }) => Ok(()),
+        Err(e) => panic!("Expected a Truncated error, got {:?}", e),
+        Ok(_) => panic!("Expected a Truncated error"),
+    }
+}
+
+#[test]
+fn test_mmap_corrupted_n_count() -> Result<()> {
+    // A wrong `n_count` must not be used to allocate anything
+    let path = get_random_trk_path();
+    let mut bytes = fs::read("data/complex.trk")?;
+    for n_count in [-1i32, i32::MAX] {
+        bytes[988..992].copy_from_slice(&n_count.to_le_bytes());
+        fs::write(&path, &bytes)?;
+        let mmap = MmapTractogram::open(&path)?;
+        assert_eq!(mmap.len(), 3);
+        assert!(mmap.tractogram() == load_trk("data/complex.trk").1);
+    }
+    Ok(())
+}
@@ -0,0 +1,79 @@
+mod test;
+
+use test::x_line;
+use trk_io::{
+    reorient::{orient_to_point, orient_to_streamline, orient_with},
+    ArraySequence, Point, Streamlines, Tractogram,
+};
+
+/// Three lines along x, the second one reversed. The scalar of each point is its x.
+fn tractogram() -> Tractogram {
+    let lines = [x_line(&[0.0, 5.0, 10.0]), x_line(&[9.0, 1.0]), x_line(&[1.0, 4.0, 8.0, 9.0])];
+    let mut streamlines = Streamlines::empty();
+    let mut scalars = ArraySequence::empty();
+    for line in &lines {
+        streamlines.extend(line.iter().cloned());
+        scalars.extend(line.iter().map(|p| p.x));
+    }
+    Tractogram::new(streamlines, scalars, ArraySequence::empty())
+}
+
+fn first_points(tractogram: &Tractogram) -> Vec<f32> {
+    tractogram.streamlines.iter().map(|s| s[0].x).collect()
+}
+
+#[test]
+fn test_orient_to_point() {
+    let mut tractogram = tractogram();
+    assert_eq!(orient_to_point(&mut tractogram, &Point::new(-5.0, 0.0, 0.0)), 1);
+    assert_eq!(first_points(&tractogram), vec![0.0, 1.0, 1.0]);
+    // The scalars follow the points
+    assert_eq!(tractogram.scalars[1], [1.0, 9.0]);
+
+    // Already oriented
+    assert_eq!(orient_to_point(&mut tractogram, &Point::new(-5.0, 0.0, 0.0)), 0);
+
+    assert_eq!(orient_to_point(&mut tractogram, &Point::new(20.0, 0.0, 0.0)), 3);
+    assert_eq!(first_points(&tractogram), vec![10.0, 9.0, 9.0]);
+    assert_eq!(tractogram.scalars[2], [9.0, 8.0, 4.0, 1.0]);
+}
+
+#[test]
+fn test_orient_to_streamline() {
+    let mut tractogram = tractogram();
+    let reference = x_line(&[0.0, 3.0, 6.0, 10.0]);
+    assert_eq!(orient_to_streamline(&mut tractogram, &reference), 1);
+    assert_eq!(first_points(&tractogram), vec![0.0, 1.0, 1.0]);
+
+    let reversed = reference.iter().rev().cloned().collect::<Vec<_>>();
+    assert_eq!(orient_to_streamline(&mut tractogram, &reversed), 3);
+    assert_eq!(first_points(&tractogram), vec![10.0, 9.0, 9.0]);
+
+    assert_eq!(orient_to_streamline(&mut tractogram, &[]), 0);
+}
+
+#[test]
+fn test_orient_with() {
+    let mut tractogram = tractogram();
+    let original = tractogram.clone();
+    assert_eq!(orient_with(&mut tractogram, |streamline| streamline.len() > 2), 2);
+    assert_eq!(first_points(&tractogram), vec![10.0, 9.0, 9.0]);
+    assert_eq!(orient_with(&mut tractogram, |streamline| streamline.len() > 2), 2);
+    assert_eq!(tractogram, original);
+}
+
+#[cfg(feature = "nifti_images")]
+#[test]
+fn test_orient_to_mask() {
+    use ndarray::Array3;
+    use trk_io::{reorient::orient_to_mask, roi::Mask};
+
+    // Voxel [9, 0, 0] only
+    let mut data = Array3::from_elem([12, 1, 1], false);
+    data[[9, 0, 0]] = true;
+    let mask = Mask::new(data);
+
+    let mut tractogram = tractogram();
+    assert_eq!(orient_to_mask(&mut tractogram, &mask), 1);
+    assert_eq!(first_points(&tractogram), vec![0.0, 9.0, 9.0]);
+}
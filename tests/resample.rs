@@ -0,0 +1,182 @@
+mod test;
+
+use test::{get_random_trk_path, load_trk, x_line};
+use trk_io::{
+    resample::{
+        resample_streamline, resample_streamline_step, resample_streamlines,
+        resample_streamlines_step, resample_tractogram, resample_tractogram_step,
+    },
+    ArraySequence, Header, Point, Streamlines, Tractogram, Writer,
+};
+
+fn assert_points_eq(a: &[Point], b: &[Point]) {
+    assert_eq!(a.len(), b.len());
+    for (p1, p2) in a.iter().zip(b) {
+        assert!((p1 - p2).norm() < 1e-5, "{} != {}", p1, p2);
+    }
+}
+
+#[test]
+fn test_resample_nb_points() {
+    let streamline = x_line(&[0.0, 1.0, 10.0]);
+    let expected = x_line(&[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0]);
+    assert_points_eq(&resample_streamline(&streamline, 11), &expected);
+    assert_points_eq(&resample_streamline(&streamline, 2), &x_line(&[0.0, 10.0]));
+    assert_points_eq(&resample_streamline(&streamline, 1), &x_line(&[0.0]));
+
+    // Not a straight line. Each segment is 2 mm
+    let streamline =
+        vec![Point::new(0.0, 0.0, 0.0), Point::new(2.0, 0.0, 0.0), Point::new(2.0, 2.0, 0.0)];
+    let expected = vec![
+        Point::new(0.0, 0.0, 0.0),
+        Point::new(1.0, 0.0, 0.0),
+        Point::new(2.0, 0.0, 0.0),
+        Point::new(2.0, 1.0, 0.0),
+        Point::new(2.0, 2.0, 0.0),
+    ];
+    assert_points_eq(&resample_streamline(&streamline, 5), &expected);
+}
+
+#[test]
+fn test_resample_degenerate() {
+    assert!(resample_streamline(&[], 10).is_empty());
+    assert!(resample_streamline(&x_line(&[0.0, 1.0]), 0).is_empty());
+    assert_points_eq(&resample_streamline(&x_line(&[4.0]), 3), &x_line(&[4.0, 4.0, 4.0]));
+    assert_points_eq(&resample_streamline(&x_line(&[4.0, 4.0]), 2), &x_line(&[4.0, 4.0]));
+    assert_points_eq(&resample_streamline_step(&x_line(&[4.0]), 1.0), &x_line(&[4.0]));
+}
+
+#[test]
+fn test_resample_step() {
+    let streamline = x_line(&[0.0, 10.0]);
+    assert_points_eq(
+        &resample_streamline_step(&streamline, 2.5),
+        &x_line(&[0.0, 2.5, 5.0, 7.5, 10.0]),
+    );
+    // The step is reduced to keep the points equidistant
+    assert_points_eq(
+        &resample_streamline_step(&streamline, 3.0),
+        &x_line(&[0.0, 2.5, 5.0, 7.5, 10.0]),
+    );
+    assert_points_eq(&resample_streamline_step(&streamline, 20.0), &x_line(&[0.0, 10.0]));
+}
+
+#[test]
+#[should_panic]
+fn test_resample_invalid_step() {
+    resample_streamline_step(&x_line(&[0.0, 10.0]), 0.0);
+}
+
+#[test]
+fn test_resample_streamlines() {
+    let streamlines =
+        Streamlines::new(vec![2, 3], [x_line(&[0.0, 4.0]), x_line(&[0.0, 1.0, 2.0])].concat());
+
+    let resampled = resample_streamlines(&streamlines, 3);
+    assert_eq!(resampled.offsets, vec![0, 3, 6]);
+    assert_points_eq(&resampled[0], &x_line(&[0.0, 2.0, 4.0]));
+    assert_points_eq(&resampled[1], &x_line(&[0.0, 1.0, 2.0]));
+
+    let resampled = resample_streamlines_step(&streamlines, 0.5);
+    assert_eq!(resampled.offsets, vec![0, 9, 14]);
+}
+
+#[test]
+fn test_resample_tractogram_scalars() {
+    // Two scalars per point: x and 2 * x
+    let streamlines = Streamlines::new(vec![3], x_line(&[0.0, 1.0, 10.0]));
+    let scalars = ArraySequence::new(vec![6], vec![0.0, 0.0, 1.0, 2.0, 10.0, 20.0]);
+    let properties = ArraySequence::new(vec![1], vec![42.0]);
+    let tractogram = Tractogram::new(streamlines, scalars, properties.clone());
+
+    let resampled = resample_tractogram(&tractogram, 6);
+    assert_points_eq(&resampled.streamlines[0], &x_line(&[0.0, 2.0, 4.0, 6.0, 8.0, 10.0]));
+    let expected = [0.0, 0.0, 2.0, 4.0, 4.0, 8.0, 6.0, 12.0, 8.0, 16.0, 10.0, 20.0];
+    for (a, b) in resampled.scalars[0].iter().zip(&expected) {
+        assert!((a - b).abs() < 1e-5);
+    }
+    assert_eq!(resampled.scalars.offsets, vec![0, 12]);
+    assert_eq!(resampled.properties, properties);
+
+    let resampled = resample_tractogram_step(&tractogram, 5.0);
+    assert_points_eq(&resampled.streamlines[0], &x_line(&[0.0, 5.0, 10.0]));
+    assert_eq!(resampled.scalars[0].len(), 6);
+}
+
+#[test]
+fn test_resample_complex() {
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let nb_scalars = header.scalars_name.len();
+    let resampled = resample_tractogram(&tractogram, 4);
+    assert_eq!(resampled.streamlines.len(), 3);
+    assert_eq!(resampled.scalars.len(), 3);
+    assert_eq!(resampled.properties, tractogram.properties);
+    for (original, (streamline, scalars, _)) in tractogram.streamlines.iter().zip(&resampled) {
+        assert_eq!(streamline.len(), 4);
+        assert_eq!(scalars.len(), 4 * nb_scalars);
+        assert_points_eq(
+            &[streamline[0], streamline[3]],
+            &[original[0], original[original.len() - 1]],
+        );
+    }
+}
+
+#[test]
+fn test_resample_empty_streamline() {
+    let streamlines =
+        Streamlines::new(vec![2, 0, 2], [x_line(&[0.0, 4.0]), x_line(&[1.0, 3.0])].concat());
+    assert_eq!(resample_streamlines(&streamlines, 3).offsets, vec![0, 3, 3, 6]);
+    assert_eq!(resample_streamlines_step(&streamlines, 2.0).offsets, vec![0, 3, 3, 5]);
+
+    let scalars = ArraySequence::new(vec![2, 0, 2], vec![0.0, 4.0, 1.0, 3.0]);
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![1.0, 2.0, 3.0]);
+    let tractogram = Tractogram::new(streamlines, scalars, properties.clone());
+    for resampled in
+        [resample_tractogram(&tractogram, 3), resample_tractogram_step(&tractogram, 2.0)]
+    {
+        assert_eq!(resampled.streamlines.len(), 3);
+        assert!(resampled.streamlines[1].is_empty());
+        assert_eq!(resampled.scalars.len(), 3);
+        assert!(resampled.scalars[1].is_empty());
+        assert_eq!(resampled.properties, properties);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_resample_streamlines_no_points() {
+    resample_streamlines(&Streamlines::new(vec![2], x_line(&[0.0, 4.0])), 0);
+}
+
+#[test]
+#[should_panic]
+fn test_resample_tractogram_no_points() {
+    let streamlines = Streamlines::new(vec![2], x_line(&[0.0, 4.0]));
+    let tractogram = Tractogram::new(streamlines, ArraySequence::empty(), ArraySequence::empty());
+    resample_tractogram(&tractogram, 0);
+}
+
+#[test]
+fn test_resample_read_empty_streamline() {
+    // One scalar per point, and an empty streamline in the middle
+    let mut header = Header::default();
+    header.add_scalar("x").unwrap();
+    let write_to = get_random_trk_path();
+    let mut writer = Writer::new(&write_to, Some(&header)).unwrap();
+    for xs in [&[1.0, 3.0][..], &[], &[5.0, 7.0]] {
+        let scalars = ArraySequence::new(vec![xs.len()], xs.to_vec());
+        writer.write((x_line(xs), scalars, vec![])).unwrap();
+    }
+    writer.finish().unwrap();
+
+    let (_, tractogram) = load_trk(&write_to);
+    assert_eq!(tractogram.streamlines.len(), 3);
+    assert_eq!(tractogram.scalars.offsets, vec![0, 2, 2, 4]);
+    let scalars = tractogram.into_iter().map(|(_, scalars, _)| scalars).collect::<Vec<_>>();
+    assert_eq!(scalars, [&[1.0, 3.0][..], &[], &[5.0, 7.0]]);
+
+    let resampled = resample_tractogram(&tractogram, 3);
+    assert_eq!(resampled.streamlines.offsets, vec![0, 3, 3, 6]);
+    assert_eq!(resampled.scalars.offsets, vec![0, 3, 3, 6]);
+    assert_eq!(resampled.scalars[2], [5.0, 6.0, 7.0]);
+}
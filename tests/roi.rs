@@ -0,0 +1,158 @@
+#![cfg(feature = "nifti_images")]
+
+mod test;
+
+use ndarray::Array3;
+use nifti::writer::WriterOptions;
+use tempfile::TempDir;
+use test::{get_random_trk_path, load_trk, x_line};
+use trk_io::{
+    roi::{Mask, RoiLogic, RoiMode, RoiSelection},
+    ArraySequence, Error, Point, Reader, Spacing, Streamlines, Tractogram,
+};
+
+/// A 4x4x4 mask holding only `voxels`.
+fn mask(voxels: &[[usize; 3]]) -> Mask {
+    let mut data = Array3::from_elem([4, 4, 4], false);
+    for &voxel in voxels {
+        data[voxel] = true;
+    }
+    Mask::new(data)
+}
+
+#[test]
+fn test_mask_contains() {
+    let mask = mask(&[[1, 0, 0]]);
+    assert!(mask.contains(&Point::new(1.0, 0.0, 0.0)));
+    assert!(mask.contains(&Point::new(1.99, 0.99, 0.99)));
+    assert!(!mask.contains(&Point::new(2.0, 0.5, 0.5)));
+    assert!(!mask.contains(&Point::new(-0.5, 0.5, 0.5)));
+    assert!(!mask.contains(&Point::new(10.5, 0.5, 0.5)));
+}
+
+#[test]
+fn test_mask_touches() {
+    let mask = mask(&[[1, 0, 0]]);
+    // No point in voxel [1, 0, 0], but the segment crosses it
+    assert!(mask.touches(&x_line(&[0.5, 3.5])));
+    assert!(mask.touches(&x_line(&[1.5])));
+    assert!(!mask.touches(&x_line(&[2.5, 3.5])));
+    assert!(!mask.touches(&[]));
+}
+
+#[test]
+fn test_roi_modes() {
+    let a = mask(&[[0, 0, 0]]);
+    let b = mask(&[[3, 0, 0]]);
+    let c = mask(&[[2, 0, 0]]);
+    let full = x_line(&[0.5, 3.5]);
+    let half = x_line(&[0.5, 1.5]);
+
+    let include = RoiSelection::new(RoiLogic::All).add(c.clone(), RoiMode::Include);
+    assert!(include.selects(&full));
+    assert!(!include.selects(&half));
+
+    let exclude = RoiSelection::new(RoiLogic::All).add(c, RoiMode::Exclude);
+    assert!(!exclude.selects(&full));
+    assert!(exclude.selects(&half));
+
+    let any = RoiSelection::new(RoiLogic::All).add(b.clone(), RoiMode::AnyEndpoint);
+    assert!(any.selects(&full));
+    assert!(any.selects(&x_line(&[3.5, 0.5])));
+    assert!(!any.selects(&half));
+
+    let both = RoiSelection::new(RoiLogic::All).add(a.clone(), RoiMode::BothEndpoints);
+    assert!(!both.selects(&full));
+    assert!(both.selects(&x_line(&[0.2, 2.5, 0.8])));
+    assert!(both.selects(&x_line(&[0.5])));
+    assert!(!both.selects(&[]));
+
+    // From A to B
+    let all = RoiSelection::new(RoiLogic::All)
+        .add(a.clone(), RoiMode::AnyEndpoint)
+        .add(b.clone(), RoiMode::AnyEndpoint);
+    assert!(all.selects(&full));
+    assert!(!all.selects(&half));
+
+    // From A or B
+    let any =
+        RoiSelection::new(RoiLogic::Any).add(a, RoiMode::AnyEndpoint).add(b, RoiMode::Include);
+    assert!(any.selects(&half));
+    assert!(any.selects(&x_line(&[2.5, 3.5])));
+    assert!(!any.selects(&x_line(&[1.5, 2.5])));
+
+    assert!(RoiSelection::new(RoiLogic::All).selects(&full));
+    assert!(!RoiSelection::new(RoiLogic::Any).selects(&full));
+}
+
+#[test]
+fn test_roi_filter_tractogram() {
+    let streamlines = Streamlines::new(
+        vec![2, 2, 2],
+        [x_line(&[0.5, 3.5]), x_line(&[0.5, 1.5]), x_line(&[2.5, 3.5])].concat(),
+    );
+    let properties = ArraySequence::new(vec![1, 1, 1], vec![1.0, 2.0, 3.0]);
+    let tractogram = Tractogram::new(streamlines, ArraySequence::empty(), properties);
+
+    let selection = RoiSelection::new(RoiLogic::All).add(mask(&[[3, 0, 0]]), RoiMode::Include);
+    let selected = selection.filter_tractogram(&tractogram);
+    assert_eq!(selected.streamlines.len(), 2);
+    assert_eq!(selected.properties.data, vec![1.0, 3.0]);
+}
+
+#[test]
+fn test_roi_from_nifti() {
+    let mut data = Array3::<f32>::zeros([4, 4, 4]);
+    data[[1, 2, 3]] = 2.0;
+    let path = TempDir::new().unwrap().keep().join("mask.nii.gz");
+    WriterOptions::new(&path).write_nifti(&data).unwrap();
+
+    let mask = Mask::from_nifti(&path).unwrap();
+    assert_eq!(mask.shape(), [4, 4, 4]);
+    assert!(mask.contains(&Point::new(1.5, 2.5, 3.5)));
+    assert!(!mask.contains(&Point::new(0.5, 0.5, 0.5)));
+}
+
+#[test]
+fn test_roi_filter_trk() {
+    let full = Mask::new(Array3::from_elem([4, 5, 7], true));
+    let write_to = get_random_trk_path();
+
+    let selection = RoiSelection::new(RoiLogic::All).add(full.clone(), RoiMode::Include);
+    let stats = selection.filter_trk("data/standard.trk", &write_to).unwrap();
+    assert_eq!(stats.nb_streamlines, 120);
+    // The streamlines are written back in world space
+    assert_eq!(load_trk(&write_to).1, load_trk("data/standard.trk").1);
+
+    let selection = RoiSelection::new(RoiLogic::All).add(full, RoiMode::Exclude);
+    let stats = selection.filter_trk("data/standard.trk", &write_to).unwrap();
+    assert_eq!(stats.nb_streamlines, 0);
+}
+
+#[test]
+fn test_roi_filter_trk_same_as_in_memory() {
+    // Only voxel [1, 1, 1]
+    let mut data = Array3::from_elem([4, 5, 7], false);
+    data[[1, 1, 1]] = true;
+    let selection = RoiSelection::new(RoiLogic::All).add(Mask::new(data), RoiMode::Include);
+
+    let write_to = get_random_trk_path();
+    let stats = selection.filter_trk("data/standard.trk", &write_to).unwrap();
+    let tractogram = Reader::new("data/standard.trk")
+        .unwrap()
+        .to_voxel_space(Spacing::new(1.0, 3.0, 2.0))
+        .tractogram()
+        .unwrap();
+    let expected = selection.filter_tractogram(&tractogram);
+    assert!(0 < stats.nb_streamlines && stats.nb_streamlines < 120);
+    assert_eq!(stats.nb_streamlines, expected.streamlines.len());
+}
+
+#[test]
+fn test_roi_filter_trk_wrong_grid() {
+    let selection = RoiSelection::new(RoiLogic::All).add(mask(&[]), RoiMode::Include);
+    match selection.filter_trk("data/standard.trk", get_random_trk_path()) {
+        Err(Error::InvalidHeader(_)) => {}
+        _ => panic!("A mask on another grid should be refused"),
+    }
+}
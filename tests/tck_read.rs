@@ -0,0 +1,126 @@
+mod test;
+
+use anyhow::Result;
+
+use test::get_random_path;
+use trk_io::{Error, Point, TckDataType, TckHeader, TckReader, Tractogram};
+
+fn check_simple(path: &str, datatype: TckDataType) -> Result<()> {
+    let first = [Point::new(0.0, 1.0, 2.0)];
+    let second = [Point::new(0.0, 1.0, 2.0), Point::new(3.0, 4.0, 5.0)];
+    let third = [
+        Point::new(0.0, 1.0, 2.0),
+        Point::new(3.0, 4.0, 5.0),
+        Point::new(6.0, 7.0, 8.0),
+        Point::new(9.0, 10.0, 11.0),
+        Point::new(12.0, 13.0, 14.0),
+    ];
+
+    // Test the complete tractogram reading
+    let mut reader = TckReader::new(path)?;
+    assert_eq!(reader.header.nb_streamlines, 3);
+    assert_eq!(reader.header.datatype, datatype);
+    let Tractogram { streamlines, scalars, properties } = reader.tractogram()?;
+    assert_eq!(streamlines.len(), 3);
+    assert_eq!(streamlines[0], first);
+    assert_eq!(streamlines[1], second);
+    assert_eq!(streamlines[2], third);
+    assert!(scalars.is_empty());
+    assert!(properties.is_empty());
+
+    // Test the tractogram items generator
+    for (i, item) in TckReader::new(path)?.enumerate() {
+        let (streamline, scalars, properties) = item?;
+        assert!(scalars.is_empty());
+        assert!(properties.is_empty());
+        match i {
+            0 => assert_eq!(streamline, first),
+            1 => assert_eq!(streamline, second),
+            2 => assert_eq!(streamline, third),
+            _ => panic!("Failed test."),
+        }
+    }
+
+    // Test the streamlines generator
+    let reader = TckReader::new(path)?;
+    assert_eq!(reader.into_streamlines_iter().count(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_load_empty() -> Result<()> {
+    let Tractogram { streamlines, scalars, properties } =
+        TckReader::new("data/empty.tck")?.tractogram()?;
+
+    assert_eq!(streamlines.len(), 0);
+    assert!(scalars.is_empty());
+    assert!(properties.is_empty());
+
+    // Test generator
+    let reader = TckReader::new("data/empty.tck")?;
+    assert_eq!(reader.into_iter().count(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_load_simple() -> Result<()> {
+    check_simple("data/simple.tck", TckDataType::Float32LE)
+}
+
+#[test]
+fn test_load_simple_big_endian() -> Result<()> {
+    check_simple("data/simple_big_endian.tck", TckDataType::Float32BE)
+}
+
+#[test]
+fn test_load_simple_float64() -> Result<()> {
+    check_simple("data/simple_float64.tck", TckDataType::Float64LE)
+}
+
+#[test]
+fn test_load_not_tck() {
+    assert!(matches!(TckHeader::from_tck("data/simple.trk"), Err(Error::BadMagic)));
+    assert!(matches!(TckReader::new("data/simple.trk"), Err(Error::BadMagic)));
+}
+
+#[test]
+fn test_load_truncated() -> Result<()> {
+    // Remove the Inf terminator, the NaN delimiter and a float of the last point
+    let bytes = std::fs::read("data/simple.tck")?;
+    let write_to = get_random_path("tck");
+    std::fs::write(&write_to, &bytes[..bytes.len() - 28])?;
+
+    // 67 (header) + 24 (first streamline) + 36 (second streamline)
+    let is_truncated =
+        |e: &Error| matches!(e, Error::Truncated { streamline_index: 2, byte_offset: 127 });
+    assert!(is_truncated(&TckReader::new(&write_to)?.tractogram().unwrap_err()));
+
+    let mut reader = TckReader::new(&write_to)?;
+    assert!(reader.next().unwrap().is_ok());
+    assert!(reader.next().unwrap().is_ok());
+    assert!(is_truncated(&reader.next().unwrap().unwrap_err()));
+    assert!(reader.next().is_none());
+
+    // Without the Inf terminator, the file is still valid
+    std::fs::write(&write_to, &bytes[..bytes.len() - 12])?;
+    assert_eq!(TckReader::new(&write_to)?.tractogram()?.streamlines.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_load_invalid_header() -> Result<()> {
+    let write_to = get_random_path("tck");
+    std::fs::write(&write_to, b"mrtrix tracks\ncount: 0\nfile: . 40\nEND\n")?;
+    assert!(matches!(TckReader::new(&write_to), Err(Error::InvalidHeader(_))));
+
+    std::fs::write(&write_to, b"mrtrix tracks\ndatatype: Int8\nfile: . 40\nEND\n")?;
+    assert!(matches!(TckReader::new(&write_to), Err(Error::InvalidHeader(_))));
+
+    std::fs::write(&write_to, b"mrtrix tracks\ndatatype: Float32LE\nfile: . 40\n")?;
+    assert!(matches!(TckReader::new(&write_to), Err(Error::InvalidHeader(_))));
+
+    Ok(())
+}
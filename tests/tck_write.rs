@@ -0,0 +1,130 @@
+mod test;
+
+use anyhow::Result;
+
+use test::{get_random_path, load_tck, load_trk};
+use trk_io::{Point, Stats, Streamlines, TckHeader, TckReader, TckWriter};
+
+#[test]
+fn test_write_empty() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let (original_header, original_tractogram) = load_tck("data/empty.tck");
+
+    {
+        let mut writer = TckWriter::new(&write_to, Some(&original_header))?;
+        writer.write(original_tractogram.clone())?;
+    }
+
+    assert!((original_header, original_tractogram) == load_tck(&write_to));
+    Ok(())
+}
+
+#[test]
+fn test_write_simple() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let (original_header, original_tractogram) = load_tck("data/simple.tck");
+
+    {
+        let mut writer = TckWriter::new(&write_to, Some(&original_header))?;
+        writer.write(original_tractogram.clone())?;
+    }
+
+    assert!((original_header, original_tractogram) == load_tck(&write_to));
+    Ok(())
+}
+
+#[test]
+fn test_write_items_and_points() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let reader = TckReader::new("data/simple_float64.tck")?;
+
+    {
+        let mut writer = TckWriter::new(&write_to, None)?;
+        for (i, item) in reader.enumerate() {
+            let item = item?;
+            match i {
+                0 => writer.write(item)?,
+                1 => writer.write(&item.0[..])?,
+                _ => writer.write_from_iter(item.0)?,
+            }
+        }
+    }
+
+    let (_, original_tractogram) = load_tck("data/simple.tck");
+    let (header, tractogram) = load_tck(&write_to);
+    assert_eq!(header.nb_streamlines, 3);
+    assert!(original_tractogram == tractogram);
+    Ok(())
+}
+
+#[test]
+fn test_write_from_trk() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let (_, trk_tractogram) = load_trk("data/complex.trk");
+
+    {
+        let mut writer = TckWriter::new(&write_to, None)?;
+        writer.write(trk_tractogram.clone())?;
+    }
+
+    // Scalars and properties can't be saved in a tck file
+    let (_, tck_tractogram) = load_tck(&write_to);
+    assert!(trk_tractogram.streamlines == tck_tractogram.streamlines);
+    assert!(tck_tractogram.scalars.is_empty());
+    assert!(tck_tractogram.properties.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_write_fields() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let mut header = TckHeader::default();
+    header.fields.push(("step_size".to_string(), "0.5".to_string()));
+    header.fields.push(("method".to_string(), "iFOD2".to_string()));
+
+    {
+        let mut writer = TckWriter::new(&write_to, Some(&header))?;
+        writer.write(&[Point::new(0.0, 1.0, 2.0), Point::new(3.0, 4.0, 5.0)][..])?;
+    }
+
+    let (read_header, _) = load_tck(&write_to);
+    assert_eq!(read_header.nb_streamlines, 1);
+    assert_eq!(read_header.fields, header.fields);
+    Ok(())
+}
+
+#[test]
+fn test_write_finish() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let (original_header, original_tractogram) = load_tck("data/simple.tck");
+
+    let mut writer = TckWriter::new(&write_to, Some(&original_header))?;
+    writer.write(original_tractogram.clone())?;
+    let stats = writer.finish()?;
+
+    assert_eq!(stats, Stats { nb_streamlines: 3, nb_points: 8 });
+    assert!((original_header, original_tractogram) == load_tck(&write_to));
+    Ok(())
+}
+
+#[test]
+fn test_write_empty_streamline() -> Result<()> {
+    let write_to = get_random_path("tck");
+    let p = Point::new(0.0, 1.0, 2.0);
+    let streamlines = Streamlines::new(vec![1, 0, 2], vec![p, p, p]);
+
+    {
+        let mut writer = TckWriter::new(&write_to, None)?;
+        for streamline in &streamlines {
+            writer.write(streamline)?;
+        }
+    }
+
+    // The empty streamline is kept, so the tractogram matches the header count
+    let (header, tractogram) = load_tck(&write_to);
+    assert_eq!(header.nb_streamlines, 3);
+    assert_eq!(tractogram.streamlines, streamlines);
+    let lengths = TckReader::new(&write_to)?.map(|item| item.unwrap().0.len()).collect::<Vec<_>>();
+    assert_eq!(lengths, [1, 0, 2]);
+    Ok(())
+}
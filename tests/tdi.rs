@@ -0,0 +1,107 @@
+#![cfg(feature = "nifti_images")]
+
+mod test;
+
+use nalgebra::{Matrix4, Vector3};
+use nifti::{IntoNdArray, NiftiHeader, NiftiObject, ReaderOptions};
+use tempfile::TempDir;
+use test::streamlines;
+use trk_io::{
+    tdi::{track_density_image, write_track_density_image, TdiMode},
+    Point,
+};
+
+/// A 4x4x4 grid of `voxel_size` mm voxels, with the center of the first voxel at the origin.
+fn reference(voxel_size: f32) -> NiftiHeader {
+    let mut header = NiftiHeader {
+        dim: [3, 4, 4, 4, 1, 1, 1, 1],
+        pixdim: [1.0, voxel_size, voxel_size, voxel_size, 1.0, 1.0, 1.0, 1.0],
+        ..Default::default()
+    };
+    header.set_affine(&Matrix4::new_nonuniform_scaling(&Vector3::repeat(voxel_size)));
+    header
+}
+
+#[test]
+fn test_tdi_count() {
+    let reference = reference(1.0);
+    let line = [Point::new(0.0, 0.0, 0.0), Point::new(3.0, 0.0, 0.0)];
+    // Goes back and forth in voxel [1, 1, 1], which is counted only once
+    let loop_ = [Point::new(1.0, 1.0, 1.0), Point::new(1.2, 1.0, 1.0), Point::new(1.0, 1.0, 1.0)];
+    let image =
+        track_density_image(&streamlines(&[&line[..], &line, &loop_]), &reference, TdiMode::Count)
+            .unwrap();
+    assert_eq!(image.shape(), &[4, 4, 4]);
+    for x in 0..4 {
+        assert_eq!(image[[x, 0, 0]], 2.0);
+    }
+    assert_eq!(image[[1, 1, 1]], 1.0);
+    assert_eq!(image.sum(), 9.0);
+}
+
+#[test]
+fn test_tdi_exact_traversal() {
+    // No point lies in voxels [1, 0, 0] and [1, 1, 0], but the segment crosses them
+    let reference = reference(1.0);
+    let line = [Point::new(0.0, 0.0, 0.0), Point::new(2.0, 1.0, 0.0)];
+    let image = track_density_image(&streamlines(&[&line]), &reference, TdiMode::Count).unwrap();
+    assert_eq!(image[[0, 0, 0]], 1.0);
+    assert_eq!(image[[1, 0, 0]], 1.0);
+    assert_eq!(image[[1, 1, 0]], 1.0);
+    assert_eq!(image[[2, 1, 0]], 1.0);
+    assert_eq!(image.sum(), 4.0);
+}
+
+#[test]
+fn test_tdi_length() {
+    let reference = reference(2.0);
+    // From the center of voxel 0 to the center of voxel 3, in mm
+    let line = [Point::new(0.0, 0.0, 0.0), Point::new(6.0, 0.0, 0.0)];
+    let image = track_density_image(&streamlines(&[&line]), &reference, TdiMode::Length).unwrap();
+    let expected = [1.0, 2.0, 2.0, 1.0];
+    for (x, expected) in expected.iter().enumerate() {
+        assert!((image[[x, 0, 0]] - expected).abs() < 1e-5);
+    }
+    assert!((image.sum() - 6.0).abs() < 1e-5);
+}
+
+#[test]
+fn test_tdi_endpoints() {
+    let reference = reference(1.0);
+    let line = [Point::new(0.0, 0.0, 0.0), Point::new(1.0, 0.0, 0.0), Point::new(3.0, 2.0, 1.0)];
+    let single = [Point::new(2.0, 2.0, 2.0)];
+    let image =
+        track_density_image(&streamlines(&[&line[..], &single]), &reference, TdiMode::Endpoints)
+            .unwrap();
+    assert_eq!(image[[0, 0, 0]], 1.0);
+    assert_eq!(image[[3, 2, 1]], 1.0);
+    assert_eq!(image[[2, 2, 2]], 1.0);
+    assert_eq!(image.sum(), 3.0);
+}
+
+#[test]
+fn test_tdi_outside_grid() {
+    let reference = reference(1.0);
+    let line = [Point::new(-5.0, 0.0, 0.0), Point::new(10.0, 0.0, 0.0)];
+    let image = track_density_image(&streamlines(&[&line]), &reference, TdiMode::Count).unwrap();
+    assert_eq!(image.sum(), 4.0);
+    let image =
+        track_density_image(&streamlines(&[&line]), &reference, TdiMode::Endpoints).unwrap();
+    assert_eq!(image.sum(), 0.0);
+}
+
+#[test]
+fn test_tdi_write() {
+    let reference = reference(2.0);
+    let line = [Point::new(0.0, 0.0, 0.0), Point::new(6.0, 2.0, 2.0)];
+    let image = track_density_image(&streamlines(&[&line]), &reference, TdiMode::Count).unwrap();
+
+    let path = TempDir::new().unwrap().keep().join("tdi.nii.gz");
+    write_track_density_image(&path, &image, &reference).unwrap();
+
+    let object = ReaderOptions::new().read_file(&path).unwrap();
+    assert_eq!(object.header().affine::<f32>(), reference.affine::<f32>());
+    let read = object.into_volume().into_ndarray::<f32>().unwrap();
+    assert_eq!(read.shape(), &[4, 4, 4]);
+    assert_eq!(read.iter().cloned().collect::<Vec<_>>(), image.iter().cloned().collect::<Vec<_>>());
+}
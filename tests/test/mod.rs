@@ -3,15 +3,48 @@
 #![allow(unused)]
 
 use tempfile::TempDir;
-use trk_io::{Header, Reader, Streamlines, Tractogram};
+use trk_io::{Header, Point, Reader, Streamlines, TckHeader, TckReader, Tractogram};
 
 pub fn get_random_trk_path() -> String {
+    get_random_path("trk")
+}
+
+/// Path of a new file with `extension` in a new temporary directory, which is never removed.
+pub fn get_random_path(extension: &str) -> String {
     let dir = TempDir::new().unwrap();
-    let path = dir.into_path().join("out.trk");
+    let path = dir.keep().join(format!("out.{}", extension));
     path.to_str().unwrap().to_string()
 }
 
+/// Path of the file `name` in `dir`.
+pub fn temp_path(dir: &TempDir, name: &str) -> String {
+    dir.path().join(name).to_str().unwrap().to_string()
+}
+
 pub fn load_trk(path: &str) -> (Header, Tractogram) {
     let mut reader = Reader::new(path).unwrap();
-    (reader.header.clone(), reader.tractogram())
+    (reader.header.clone(), reader.tractogram().unwrap())
+}
+
+pub fn load_tck(path: &str) -> (TckHeader, Tractogram) {
+    let mut reader = TckReader::new(path).unwrap();
+    (reader.header.clone(), reader.tractogram().unwrap())
+}
+
+/// A streamline parallel to the x axis, at height `y`, with one point per `xs`.
+pub fn line(y: f32, xs: &[f32]) -> Vec<Point> {
+    xs.iter().map(|&x| Point::new(x, y, 0.0)).collect()
+}
+
+/// A streamline along the x axis, with one point per `xs`.
+pub fn x_line(xs: &[f32]) -> Vec<Point> {
+    line(0.0, xs)
+}
+
+pub fn streamlines<L: AsRef<[Point]>>(lines: &[L]) -> Streamlines {
+    let mut streamlines = Streamlines::empty();
+    for line in lines {
+        streamlines.extend_from_slice(line.as_ref());
+    }
+    streamlines
 }
@@ -0,0 +1,115 @@
+mod test;
+
+use std::{fs, thread, time::Duration};
+
+use anyhow::Result;
+
+use test::{get_random_trk_path, load_trk};
+use trk_io::{Error, Reader, Tractogram, TrkIndex};
+
+fn check_same_items(tractogram: &Tractogram, full: &Tractogram, indices: &[usize]) {
+    assert_eq!(tractogram.streamlines.len(), indices.len());
+    for (k, &i) in indices.iter().enumerate() {
+        assert!(tractogram.item(k) == full.item(i));
+    }
+}
+
+#[test]
+fn test_index_build() -> Result<()> {
+    let index = TrkIndex::build("data/complex.trk")?;
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.position(0), 1000);
+    assert_eq!(index.position(2), 1132);
+    assert_eq!(index.position(3), fs::metadata("data/complex.trk")?.len());
+
+    let index = TrkIndex::build("data/empty.trk")?;
+    assert!(index.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_index_item_at() -> Result<()> {
+    for path in ["data/complex.trk", "data/complex_big_endian.trk"] {
+        let index = TrkIndex::build(path)?;
+        let items = Reader::new(path)?.collect::<trk_io::Result<Vec<_>>>()?;
+
+        // In reverse, to force seeking backward
+        let mut reader = Reader::new(path)?;
+        for i in (0..3).rev() {
+            assert!(reader.item_at(&index, i)? == items[i]);
+        }
+
+        // The iterator continues after a seek
+        reader.seek_streamline(&index, 1)?;
+        assert!(reader.next().unwrap()? == items[1]);
+        assert!(reader.next().unwrap()? == items[2]);
+        assert!(reader.next().is_none());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_index_range_and_indices() -> Result<()> {
+    let (_, full) = load_trk("data/complex.trk");
+    let index = TrkIndex::build("data/complex.trk")?;
+    let mut reader = Reader::new("data/complex.trk")?;
+
+    check_same_items(&reader.tractogram_range(&index, 1..3)?, &full, &[1, 2]);
+    check_same_items(&reader.tractogram_range(&index, 0..3)?, &full, &[0, 1, 2]);
+    check_same_items(&reader.tractogram_range(&index, 2..2)?, &full, &[]);
+    check_same_items(&reader.tractogram_indices(&index, &[0, 2])?, &full, &[0, 2]);
+    check_same_items(&reader.tractogram_indices(&index, &[2, 0, 0])?, &full, &[2, 0, 0]);
+    Ok(())
+}
+
+#[test]
+#[should_panic]
+fn test_index_out_of_bounds() {
+    let index = TrkIndex::build("data/complex.trk").unwrap();
+    let mut reader = Reader::new("data/complex.trk").unwrap();
+    let _ = reader.tractogram_indices(&index, &[1, 3]);
+}
+
+#[test]
+fn test_index_sidecar() -> Result<()> {
+    let trk_path = get_random_trk_path();
+    fs::copy("data/complex.trk", &trk_path)?;
+    let sidecar = TrkIndex::sidecar_path(&trk_path);
+    assert_eq!(sidecar.to_str().unwrap(), format!("{}.idx", trk_path));
+
+    let index = TrkIndex::load_or_build(&trk_path)?;
+    assert!(sidecar.exists());
+    assert_eq!(TrkIndex::load(&sidecar, &trk_path)?, index);
+    assert_eq!(TrkIndex::load_or_build(&trk_path)?, index);
+
+    // Modifying the trk file invalidates the sidecar
+    thread::sleep(Duration::from_millis(10));
+    fs::copy("data/simple.trk", &trk_path)?;
+    assert!(matches!(TrkIndex::load(&sidecar, &trk_path), Err(Error::InvalidIndex(_))));
+    let index = TrkIndex::load_or_build(&trk_path)?;
+    assert_eq!(index.position(index.len()), fs::metadata("data/simple.trk")?.len());
+    assert_eq!(TrkIndex::load(&sidecar, &trk_path)?, index);
+
+    // Not an index
+    assert!(matches!(TrkIndex::load(&trk_path, &trk_path), Err(Error::InvalidIndex(_))));
+
+    // A corrupted number of positions is rejected before allocating them
+    let mut bytes = fs::read(&sidecar)?;
+    bytes[32..40].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    fs::write(&sidecar, &bytes)?;
+    assert!(matches!(TrkIndex::load(&sidecar, &trk_path), Err(Error::InvalidIndex(_))));
+    assert_eq!(TrkIndex::load_or_build(&trk_path)?, index);
+    Ok(())
+}
+
+#[test]
+fn test_index_truncated() -> Result<()> {
+    let path = get_random_trk_path();
+    let bytes = fs::read("data/complex.trk")?;
+    fs::write(&path, &bytes[..bytes.len() - 4])?;
+
+    match TrkIndex::build(&path) {
+        Err(Error::Truncated { streamline_index: 2, byte_offset: 1132 }) => Ok(()),
+        r => panic!("Expected a Truncated error, got {:?}", r),
+    }
+}
@@ -1,12 +1,19 @@
+mod test;
+
+use std::io::Cursor;
+
 use anyhow::Result;
 use nalgebra::Vector3;
 
-use trk_io::{Affine, ArraySequence, Header, Point, Reader, Tractogram, Translation};
+use test::get_random_trk_path;
+use trk_io::{
+    Affine, ArraySequence, Endianness, Error, Header, Point, Reader, Tractogram, Translation,
+};
 
 #[test]
 fn test_load_empty() -> Result<()> {
     let Tractogram { streamlines, scalars, properties } =
-        Reader::new("data/empty.trk")?.tractogram();
+        Reader::new("data/empty.trk")?.tractogram()?;
 
     assert_eq!(streamlines.len(), 0);
     assert!(scalars.is_empty());
@@ -33,7 +40,7 @@ fn test_load_simple() -> Result<()> {
 
     // Test the complete tractogram reading
     let Tractogram { streamlines, scalars, properties } =
-        Reader::new("data/simple.trk")?.tractogram();
+        Reader::new("data/simple.trk")?.tractogram()?;
     assert_eq!(streamlines.len(), 3);
     assert_eq!(streamlines[0], first);
     assert_eq!(streamlines[1], second);
@@ -42,7 +49,7 @@ fn test_load_simple() -> Result<()> {
     assert!(properties.is_empty());
 
     // Test the complete points reading
-    let streamlines = Reader::new("data/simple.trk")?.streamlines();
+    let streamlines = Reader::new("data/simple.trk")?.streamlines()?;
     assert_eq!(streamlines.len(), 3);
     assert_eq!(streamlines[0], first);
     assert_eq!(streamlines[1], second);
@@ -50,7 +57,8 @@ fn test_load_simple() -> Result<()> {
 
     // Test the tractogram items generator
     let reader = Reader::new("data/simple.trk")?;
-    for (i, (streamline, _, _)) in reader.into_iter().enumerate() {
+    for (i, item) in reader.into_iter().enumerate() {
+        let (streamline, _, _) = item?;
         match i {
             0 => assert_eq!(streamline, first),
             1 => assert_eq!(streamline, second),
@@ -62,6 +70,7 @@ fn test_load_simple() -> Result<()> {
     // Test the streamlines generator
     let reader = Reader::new("data/simple.trk")?;
     for (i, streamline) in reader.into_streamlines_iter().enumerate() {
+        let streamline = streamline?;
         match i {
             0 => assert_eq!(streamline, first),
             1 => assert_eq!(streamline, second),
@@ -76,7 +85,7 @@ fn test_load_simple() -> Result<()> {
 #[test]
 fn test_load_standard() -> Result<()> {
     let mut reader = Reader::new("data/standard.trk")?;
-    let Tractogram { streamlines, scalars, properties } = reader.tractogram();
+    let Tractogram { streamlines, scalars, properties } = reader.tractogram()?;
 
     assert_eq!(reader.header.affine_to_rasmm, Affine::identity());
     assert_eq!(reader.header.translation, Translation::new(-0.5, -1.5, -1.0));
@@ -94,7 +103,8 @@ fn test_load_standard() -> Result<()> {
     assert!(properties.is_empty());
 
     // Test generator
-    for (streamline, _, _) in Reader::new("data/standard.trk")? {
+    for item in Reader::new("data/standard.trk")? {
+        let (streamline, _, _) = item?;
         assert_eq!(streamline.len(), 3);
     }
 
@@ -104,7 +114,7 @@ fn test_load_standard() -> Result<()> {
 #[test]
 fn test_load_standard_lps() -> Result<()> {
     let mut reader = Reader::new("data/standard.LPS.trk")?;
-    let Tractogram { streamlines, scalars, properties } = reader.tractogram();
+    let Tractogram { streamlines, scalars, properties } = reader.tractogram()?;
     assert_eq!(
         reader.header.affine_to_rasmm,
         Affine::from_diagonal(&Vector3::new(-1.0, -1.0, 1.0))
@@ -129,7 +139,7 @@ fn test_load_standard_lps() -> Result<()> {
 #[test]
 fn test_load_complex() -> Result<()> {
     let mut reader = Reader::new("data/complex.trk")?;
-    let Tractogram { streamlines, scalars, properties } = reader.tractogram();
+    let Tractogram { streamlines, scalars, properties } = reader.tractogram()?;
     assert_eq!(reader.header.affine_to_rasmm, Affine::identity());
     assert_eq!(reader.header.translation, Translation::new(-0.5, -0.5, -0.5));
 
@@ -165,7 +175,7 @@ fn test_load_complex_big_endian() -> Result<()> {
     ];
 
     let mut reader = Reader::new("data/complex_big_endian.trk")?;
-    let Tractogram { streamlines, scalars, properties } = reader.tractogram();
+    let Tractogram { streamlines, scalars, properties } = reader.tractogram()?;
     assert_eq!(streamlines.len(), 3);
     assert_eq!(streamlines[0], first);
     assert_eq!(streamlines[1], second);
@@ -175,6 +185,7 @@ fn test_load_complex_big_endian() -> Result<()> {
     // Test generator
     let reader = Reader::new("data/complex_big_endian.trk")?;
     for (i, streamline) in reader.into_streamlines_iter().enumerate() {
+        let streamline = streamline?;
         match i {
             0 => assert_eq!(streamline, first),
             1 => assert_eq!(streamline, second),
@@ -186,6 +197,119 @@ fn test_load_complex_big_endian() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_load_from_reader() -> Result<()> {
+    let bytes = std::fs::read("data/complex_big_endian.trk")?;
+    let mut reader = Reader::new("data/complex_big_endian.trk")?;
+    let tractogram = reader.tractogram()?;
+
+    // Seekable, in-memory source. It doesn't need to be at the start of the data.
+    let mut cursor = Cursor::new(bytes.clone());
+    cursor.set_position(500);
+    let mut cursor_reader = Reader::from_reader(cursor)?;
+    assert!(cursor_reader.header == reader.header);
+    assert_eq!(cursor_reader.tractogram()?, tractogram);
+
+    // Non-seekable source
+    let mut stream_reader = Reader::from_stream(&bytes[..])?;
+    assert!(stream_reader.header == reader.header);
+    assert_eq!(stream_reader.tractogram()?, tractogram);
+
+    let stream_reader = Reader::from_stream(&bytes[..])?.into_streamlines_iter();
+    assert_eq!(stream_reader.count(), 3);
+
+    let (header, endianness) = Header::read_stream(&mut &bytes[..])?;
+    assert!(header == reader.header);
+    assert!(matches!(endianness, Endianness::Big));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_truncated() -> Result<()> {
+    // Remove the last property of the last streamline
+    let bytes = std::fs::read("data/complex.trk")?;
+    let write_to = get_random_trk_path();
+    std::fs::write(&write_to, &bytes[..bytes.len() - 4])?;
+
+    // 1000 (header) + 52 (first streamline) + 80 (second streamline)
+    let is_truncated =
+        |e: &Error| matches!(e, Error::Truncated { streamline_index: 2, byte_offset: 1132 });
+    assert!(is_truncated(&Reader::new(&write_to)?.tractogram().unwrap_err()));
+    assert!(is_truncated(&Reader::new(&write_to)?.streamlines().unwrap_err()));
+
+    // The iterators should return the valid streamlines, then the error, then nothing
+    let mut reader = Reader::new(&write_to)?;
+    assert!(reader.next().unwrap().is_ok());
+    assert!(reader.next().unwrap().is_ok());
+    assert!(is_truncated(&reader.next().unwrap().unwrap_err()));
+    assert!(reader.next().is_none());
+
+    let mut reader = Reader::new(&write_to)?.into_streamlines_iter();
+    assert_eq!(reader.by_ref().filter(|s| s.is_ok()).count(), 2);
+    assert!(reader.next().is_none());
+
+    // Only the first byte of the `nb_points` of the last streamline
+    std::fs::write(&write_to, &bytes[..1133])?;
+    assert!(is_truncated(&Reader::new(&write_to)?.tractogram().unwrap_err()));
+
+    // A huge `nb_points` must not be allocated before reading the points
+    let mut corrupted = bytes.clone();
+    corrupted[1132..1136].copy_from_slice(&i32::MAX.to_le_bytes());
+    std::fs::write(&write_to, &corrupted)?;
+    assert!(is_truncated(&Reader::new(&write_to)?.tractogram().unwrap_err()));
+    assert!(is_truncated(&Reader::new(&write_to)?.streamlines().unwrap_err()));
+    assert!(is_truncated(&Reader::new(&write_to)?.nth(2).unwrap().unwrap_err()));
+    let mut reader = Reader::new(&write_to)?.into_streamlines_iter();
+    assert!(is_truncated(&reader.nth(2).unwrap().unwrap_err()));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_invalid() -> Result<()> {
+    assert!(matches!(Reader::new("data/simple.tck"), Err(Error::BadMagic)));
+    assert!(matches!(Reader::new("data/missing.trk"), Err(Error::Io(_))));
+
+    let write_to = get_random_trk_path();
+    std::fs::write(&write_to, b"TRACK\0")?;
+    assert!(matches!(Reader::new(&write_to), Err(Error::InvalidHeader(_))));
+
+    // A negative number of points
+    let mut bytes = std::fs::read("data/simple.trk")?;
+    bytes[1000..1004].copy_from_slice(&(-1i32).to_le_bytes());
+    std::fs::write(&write_to, &bytes)?;
+    assert!(matches!(
+        Reader::new(&write_to)?.tractogram(),
+        Err(Error::InvalidNbPoints { streamline_index: 0, nb_points: -1 })
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_load_many_repeated_names() -> Result<()> {
+    // nibabel saves a name once, with a repeat count that can be larger than 9
+    let write_to = get_random_trk_path();
+    let mut bytes = std::fs::read("data/empty.trk")?;
+    bytes[36..38].copy_from_slice(&13i16.to_le_bytes());
+    bytes[38..48].copy_from_slice(b"tensor\x0012\x00");
+    bytes[58..60].copy_from_slice(b"fa");
+    std::fs::write(&write_to, &bytes)?;
+    let header = Reader::new(&write_to)?.header;
+    assert_eq!(header.scalars_name.len(), 13);
+    assert!(header.scalars_name[..12].iter().all(|name| name == "tensor"));
+    assert_eq!(header.scalars_name[12], "fa");
+
+    // 11 unnamed scalars need more than 10 slots
+    bytes[36..38].copy_from_slice(&11i16.to_le_bytes());
+    bytes[38..238].fill(0);
+    std::fs::write(&write_to, &bytes)?;
+    assert!(matches!(Reader::new(&write_to), Err(Error::InvalidHeader(_))));
+    Ok(())
+}
+
+#[allow(clippy::excessive_precision)]
 fn check_complex_scalars_and_properties(
     header: Header,
     scalars: ArraySequence<f32>,
@@ -230,3 +354,80 @@ fn check_complex_scalars_and_properties(
     assert_eq!(&properties[1], &[0.0, 1.0, 0.0, 2.11000001, 2.22000003]);
     assert_eq!(&properties[2], &[0.0, 0.0, 1.0, 3.11000001, 3.22000003]);
 }
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_load_parallel() -> Result<()> {
+    let paths = [
+        "data/empty.trk",
+        "data/simple.trk",
+        "data/complex.trk",
+        "data/complex_big_endian.trk",
+        "data/standard.LPS.trk",
+    ];
+    for path in paths {
+        assert_eq!(Reader::new(path)?.tractogram_parallel()?, Reader::new(path)?.tractogram()?);
+        assert_eq!(
+            Reader::new(path)?.raw().tractogram_parallel()?,
+            Reader::new(path)?.raw().tractogram()?
+        );
+        let spacing = Vector3::new(1.0, 2.0, 3.0);
+        assert_eq!(
+            Reader::new(path)?.to_voxel_space(spacing).tractogram_parallel()?,
+            Reader::new(path)?.to_voxel_space(spacing).tractogram()?
+        );
+    }
+
+    // After reading the first streamline, only the rest of the file should be read
+    let mut reader = Reader::new("data/complex.trk")?;
+    let first = reader.next().unwrap()?;
+    let rest = reader.tractogram_parallel()?;
+    let all = Reader::new("data/complex.trk")?.tractogram()?;
+    assert_eq!(rest.streamlines.len(), 2);
+    assert!(all.item(0) == (&first.0[..], &first.1.data[..], &first.2[..]));
+    assert!(rest.item(1) == all.item(2));
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_load_parallel_big() -> Result<()> {
+    // Bigger than a read chunk, with a streamline that doesn't fit in a single chunk, and an empty
+    // streamline
+    let header = Reader::new("data/complex.trk")?.header;
+    let (nb_scalars, nb_properties) = (header.scalars_name.len(), header.properties_name.len());
+    let lengths = [1000; 50].into_iter().chain([60_000, 0, 1000]).collect::<Vec<_>>();
+    let nb_points = lengths.iter().sum::<usize>();
+    let points = (0..nb_points).map(|i| Point::new(i as f32, 1.0, 2.0)).collect();
+    let scalars = (0..nb_points * nb_scalars).map(|i| i as f32).collect();
+    let properties = (0..lengths.len() * nb_properties).map(|i| i as f32).collect();
+    let tractogram = Tractogram::new(
+        ArraySequence::new(lengths.clone(), points),
+        ArraySequence::new(lengths.iter().map(|l| l * nb_scalars).collect(), scalars),
+        ArraySequence::new(vec![nb_properties; lengths.len()], properties),
+    );
+
+    let write_to = get_random_trk_path();
+    let mut writer = trk_io::Writer::new(&write_to, Some(&header))?;
+    writer.write(tractogram)?;
+    writer.finish()?;
+    let parallel = Reader::new(&write_to)?.tractogram_parallel()?;
+    assert_eq!(parallel, Reader::new(&write_to)?.tractogram()?);
+    assert_eq!(parallel.scalars.len(), lengths.len());
+    Ok(())
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_load_parallel_truncated() -> Result<()> {
+    let bytes = std::fs::read("data/complex.trk")?;
+    let write_to = get_random_trk_path();
+    for len in [bytes.len() - 4, 1133] {
+        std::fs::write(&write_to, &bytes[..len])?;
+        match Reader::new(&write_to)?.tractogram_parallel() {
+            Err(Error::Truncated { streamline_index: 2, byte_offset: 1132 }) => {}
+            r => panic!("Expected a Truncated error, got {:?}", r),
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,11 @@
 mod test;
 
-use std::iter::FromIterator;
+use std::{io::Cursor, iter::FromIterator};
 
 use anyhow::Result;
 
 use test::{get_random_trk_path, load_trk};
-use trk_io::{Affine4, Point, Reader, Writer};
+use trk_io::{Affine4, Error, Point, Reader, Stats, Writer};
 
 // write(Tractogram) is tested in write_empty and write_simple.
 // write(TractogramItem) is tested in test_write_tractogram_item_simple and write_complex.
@@ -21,15 +21,15 @@ fn test_write_dynamic() -> Result<()> {
     // This seemingly useless { scope } is *required* because Writer::drop must be called
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
-        writer.write_from_iter([Point::new(0.0, 1.0, 2.0)].iter().cloned(), 1);
+        writer.write_from_iter([Point::new(0.0, 1.0, 2.0)].iter().cloned(), 1)?;
 
         let v = vec![Point::new(0.0, 1.0, 2.0), Point::new(3.0, 4.0, 5.0)];
-        writer.write_from_iter(v, 2);
+        writer.write_from_iter(v, 2)?;
 
         let v = Vec::from_iter(0..15);
         let iter =
             v.chunks(3).map(|ints| Point::new(ints[0] as f32, ints[1] as f32, ints[2] as f32));
-        writer.write_from_iter(iter, 5);
+        writer.write_from_iter(iter, 5)?;
     }
 
     assert!((original_header, original_tractogram) == load_trk(&write_to));
@@ -43,7 +43,7 @@ fn test_write_empty() -> Result<()> {
 
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
-        writer.write(original_tractogram.clone());
+        writer.write(original_tractogram.clone())?;
     }
 
     assert!((original_header, original_tractogram) == load_trk(&write_to));
@@ -57,7 +57,7 @@ fn test_write_simple() -> Result<()> {
 
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
-        writer.write(original_tractogram.clone());
+        writer.write(original_tractogram.clone())?;
     }
 
     assert!((original_header, original_tractogram) == load_trk(&write_to));
@@ -72,7 +72,7 @@ fn test_write_points_simple() -> Result<()> {
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
         for streamline in original_tractogram.streamlines.into_iter() {
-            writer.write(streamline);
+            writer.write(streamline)?;
         }
     }
 
@@ -88,7 +88,7 @@ fn test_write_tractogram_item_simple() -> Result<()> {
     {
         let mut writer = Writer::new(&write_to, Some(&reader.header))?;
         for item in reader.into_iter() {
-            writer.write(item);
+            writer.write(item?)?;
         }
     }
 
@@ -105,7 +105,7 @@ fn test_write_ref_tractogram_item_simple() -> Result<()> {
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
         for ref_item in original_tractogram.into_iter() {
-            writer.write(ref_item);
+            writer.write(ref_item)?;
         }
     }
 
@@ -120,9 +120,9 @@ fn test_write_standard() -> Result<()> {
 
     {
         let mut writer = Writer::new(&write_to, Some(&original_header))?;
-        writer.write(&original_tractogram.streamlines[0]);
-        writer.write(&original_tractogram.streamlines[1]);
-        writer.write(&original_tractogram.streamlines[2]);
+        writer.write(&original_tractogram.streamlines[0])?;
+        writer.write(&original_tractogram.streamlines[1])?;
+        writer.write(&original_tractogram.streamlines[2])?;
     }
 
     let (header, tractogram) = load_trk(&write_to);
@@ -152,7 +152,7 @@ fn test_write_standard_lps() -> Result<()> {
             )
         );
         for i in 0..10 {
-            writer.write(&original_tractogram.streamlines[i]);
+            writer.write(&original_tractogram.streamlines[i])?;
         }
     }
 
@@ -183,7 +183,7 @@ fn test_write_complex() -> Result<()> {
     {
         let mut writer = Writer::new(&write_to, Some(&reader.header))?;
         for item in reader.into_iter() {
-            writer.write(item);
+            writer.write(item?)?;
         }
     }
 
@@ -191,3 +191,73 @@ fn test_write_complex() -> Result<()> {
     assert!((original_header, original_tractogram) == load_trk(&write_to));
     Ok(())
 }
+
+#[test]
+fn test_write_finish() -> Result<()> {
+    let write_to = get_random_trk_path();
+    let (original_header, original_tractogram) = load_trk("data/complex.trk");
+
+    // No scope required, the file is complete as soon as `finish` returns
+    let mut writer = Writer::new(&write_to, Some(&original_header))?.sync_on_finish();
+    writer.write(original_tractogram.clone())?;
+    let stats = writer.finish()?;
+
+    assert_eq!(stats, Stats { nb_streamlines: 3, nb_points: 8 });
+    assert!((original_header, original_tractogram) == load_trk(&write_to));
+    Ok(())
+}
+
+#[test]
+fn test_write_in_memory() -> Result<()> {
+    let (original_header, original_tractogram) = load_trk("data/complex.trk");
+
+    let mut cursor = Cursor::new(vec![]);
+    let mut writer = Writer::from_writer(&mut cursor, Some(&original_header))?;
+    writer.write(original_tractogram.clone())?;
+    assert_eq!(writer.finish()?, Stats { nb_streamlines: 3, nb_points: 8 });
+
+    cursor.set_position(0);
+    let mut reader = Reader::from_reader(cursor)?;
+    assert_eq!(reader.header.nb_streamlines, 3);
+    assert!(
+        (original_header, original_tractogram) == (reader.header.clone(), reader.tractogram()?)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_write_stream() -> Result<()> {
+    let (original_header, original_tractogram) = load_trk("data/complex.trk");
+
+    // Unknown number of streamlines
+    let mut bytes = vec![];
+    let mut writer = Writer::from_stream(&mut bytes, Some(&original_header), None)?;
+    writer.write(original_tractogram.clone())?;
+    writer.finish()?;
+
+    let mut reader = Reader::from_stream(&bytes[..])?;
+    assert_eq!(reader.header.nb_streamlines, 0);
+    assert!(original_tractogram == reader.tractogram()?);
+
+    // Known number of streamlines
+    let mut bytes = vec![];
+    let mut writer = Writer::from_stream(&mut bytes, Some(&original_header), Some(3))?;
+    writer.write(original_tractogram.clone())?;
+    writer.finish()?;
+
+    let mut reader = Reader::from_stream(&bytes[..])?;
+    assert_eq!(reader.header.nb_streamlines, 3);
+    assert!(original_tractogram == reader.tractogram()?);
+    Ok(())
+}
+
+#[test]
+fn test_write_stream_count_mismatch() -> Result<()> {
+    let mut bytes = vec![];
+    let mut writer = Writer::from_stream(&mut bytes, None, Some(2))?;
+    writer.write(&[Point::new(0.0, 1.0, 2.0)][..])?;
+    match writer.finish() {
+        Err(Error::CountMismatch { declared: 2, written: 1 }) => Ok(()),
+        r => panic!("Expected a CountMismatch error, got {:?}", r),
+    }
+}
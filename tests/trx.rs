@@ -0,0 +1,228 @@
+#![cfg(feature = "trx")]
+
+mod test;
+
+use std::fs;
+
+use anyhow::Result;
+use tempfile::TempDir;
+
+use test::{get_random_path, get_random_trk_path, load_trk};
+use trk_io::{
+    trx::{OffsetsType, PositionsType, Trx},
+    Affine4, ArraySequence, Error, Point, Tractogram, Writer,
+};
+
+fn write_directory(dir: &TempDir, files: &[(&str, Vec<u8>)]) {
+    for (name, bytes) in files {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, bytes).unwrap();
+    }
+}
+
+fn header_json(nb_streamlines: usize, nb_vertices: usize) -> Vec<u8> {
+    format!(
+        r#"{{"VOXEL_TO_RASMM": [[2, 0, 0, -10], [0, 2, 0, -20], [0, 0, 2, -30], [0, 0, 0, 1]],
+            "DIMENSIONS": [5, 6, 7], "NB_STREAMLINES": {}, "NB_VERTICES": {}}}"#,
+        nb_streamlines, nb_vertices
+    )
+    .into_bytes()
+}
+
+fn bytes_f32(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn test_zip_round_trip() -> Result<()> {
+    let (header, tractogram) = load_trk("data/complex.trk");
+    let trx = Trx::from_trk(&header, tractogram.clone());
+    let path = get_random_path("trx");
+    trx.save_zip(&path)?;
+
+    // The colors are saved as a single 3D dpv
+    let loaded = Trx::load(&path)?;
+    assert_eq!(loaded, trx);
+    assert_eq!(loaded.tractogram, tractogram);
+    assert_eq!(loaded.scalars_name, header.scalars_name);
+    assert_eq!(loaded.properties_name, header.properties_name);
+
+    let trk_header = loaded.trk_header();
+    assert_eq!(trk_header.raw_header().dim, header.raw_header().dim);
+    assert_eq!(trk_header.raw_header().vox_to_ras, header.raw_header().vox_to_ras);
+    assert_eq!(trk_header.scalars_name, header.scalars_name);
+    assert_eq!(trk_header.properties_name, header.properties_name);
+    Ok(())
+}
+
+#[test]
+fn test_directory_round_trip() -> Result<()> {
+    let (header, tractogram) = load_trk("data/simple.trk");
+    let mut trx = Trx::from_trk(&header, tractogram);
+    trx.groups = vec![("first".to_string(), vec![0]), ("others".to_string(), vec![1, 2])];
+    let dir = TempDir::new()?;
+    trx.save_directory(dir.path())?;
+
+    assert!(dir.path().join("header.json").exists());
+    assert!(dir.path().join("positions.3.float32").exists());
+    assert!(dir.path().join("offsets.uint32").exists());
+    assert!(dir.path().join("groups/others.uint32").exists());
+    assert!(!dir.path().join("dpv").exists());
+    assert_eq!(Trx::load(dir.path())?, trx);
+    Ok(())
+}
+
+#[test]
+fn test_positions_types() -> Result<()> {
+    let (header, tractogram) = load_trk("data/simple.trk");
+    for positions_type in [PositionsType::Float16, PositionsType::Float64] {
+        let mut trx = Trx::from_trk(&header, tractogram.clone());
+        trx.positions_type = positions_type;
+        let path = get_random_path("trx");
+        trx.save_zip(&path)?;
+
+        // All coordinates of simple.trk are small integers, so they are exact in float16
+        let loaded = Trx::load(&path)?;
+        assert_eq!(loaded.positions_type, positions_type);
+        assert_eq!(loaded.tractogram, tractogram);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_uint64_offsets() -> Result<()> {
+    let (header, tractogram) = load_trk("data/simple.trk");
+    let mut trx = Trx::from_trk(&header, tractogram.clone());
+    trx.offsets_type = OffsetsType::UInt64;
+    let dir = TempDir::new()?;
+    trx.save_directory(dir.path())?;
+    assert!(dir.path().join("offsets.uint64").exists());
+
+    let loaded = Trx::load(dir.path())?;
+    assert_eq!(loaded.offsets_type, OffsetsType::UInt64);
+    assert_eq!(loaded.tractogram, tractogram);
+    Ok(())
+}
+
+#[test]
+fn test_names_without_trk_limits() -> Result<()> {
+    let streamlines = ArraySequence::new(vec![2], vec![Point::origin(), Point::new(1.0, 1.0, 1.0)]);
+    let names = (0..12).map(|i| format!("a_very_long_scalar_name_number_{:02}", i)).collect();
+    let scalars = ArraySequence::new(vec![24], (0..24).map(|v| v as f32).collect());
+    let properties = ArraySequence::new(vec![1], vec![42.0]);
+    let mut trx = Trx::new(streamlines, Affine4::identity(), [10, 10, 10]);
+    trx.tractogram = Tractogram { scalars, properties, ..trx.tractogram };
+    trx.scalars_name = names;
+    trx.properties_name = vec!["a_property_name_longer_than_20_chars".to_string()];
+
+    let path = get_random_path("trx");
+    trx.save_zip(&path)?;
+    assert_eq!(Trx::load(&path)?, trx);
+
+    // They are kept in the trk header, but can't be saved in a trk file
+    let header = trx.trk_header();
+    assert!(header.scalars_name == trx.scalars_name);
+    assert!(header.properties_name == trx.properties_name);
+    let trk_path = get_random_trk_path();
+    let result = Writer::new(&trk_path, Some(&header));
+    assert!(matches!(result, Err(Error::InvalidName(_))));
+    Ok(())
+}
+
+#[test]
+fn test_load_foreign_directory() -> Result<()> {
+    // 2 streamlines, offsets with a trailing nb_vertices, a 3D dpv saved as uint8, a float64 dps
+    // and some data per group, which is ignored.
+    let dir = TempDir::new()?;
+    let offsets = [0u32, 1, 3].iter().flat_map(|o| o.to_le_bytes()).collect();
+    let dps = [0.5f64, 1.5].iter().flat_map(|v| v.to_le_bytes()).collect();
+    write_directory(
+        &dir,
+        &[
+            ("header.json", header_json(2, 3)),
+            ("positions.3.float32", bytes_f32(&[0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0])),
+            ("offsets.uint32", offsets),
+            ("dpv/color.3.uint8", vec![255, 0, 0, 0, 255, 0, 0, 0, 255]),
+            ("dpv/fa.float32", bytes_f32(&[0.1, 0.2, 0.3])),
+            ("dps/weight.float64", dps),
+            ("groups/all.uint32", [0u32, 1].iter().flat_map(|i| i.to_le_bytes()).collect()),
+            ("dpg/all/mean.float32", bytes_f32(&[1.0])),
+        ],
+    );
+
+    let trx = Trx::load(dir.path())?;
+    assert_eq!(trx.dimensions, [5, 6, 7]);
+    assert_eq!(trx.voxel_to_rasmm[(0, 0)], 2.0);
+    assert_eq!(trx.voxel_to_rasmm[(2, 3)], -30.0);
+    assert_eq!(trx.positions_type, PositionsType::Float32);
+    assert_eq!(trx.tractogram.streamlines.len(), 2);
+    assert_eq!(trx.tractogram.streamlines[0], [Point::origin()]);
+    assert_eq!(trx.tractogram.streamlines[1].len(), 2);
+    assert_eq!(trx.scalars_name, vec!["color", "color", "color", "fa"]);
+    assert_eq!(trx.tractogram.scalars[0], [255.0, 0.0, 0.0, 0.1]);
+    assert_eq!(trx.tractogram.scalars[1], [0.0, 255.0, 0.0, 0.2, 0.0, 0.0, 255.0, 0.3]);
+    assert_eq!(trx.properties_name, vec!["weight"]);
+    assert_eq!(trx.tractogram.properties[1], [1.5]);
+    assert_eq!(trx.groups, vec![("all".to_string(), vec![0, 1])]);
+    Ok(())
+}
+
+#[test]
+fn test_load_invalid() {
+    // Missing offsets
+    let dir = TempDir::new().unwrap();
+    write_directory(
+        &dir,
+        &[("header.json", header_json(1, 1)), ("positions.3.float32", bytes_f32(&[0.0; 3]))],
+    );
+    assert!(matches!(Trx::load(dir.path()), Err(Error::InvalidHeader(_))));
+
+    // Wrong number of vertices
+    let dir = TempDir::new().unwrap();
+    write_directory(
+        &dir,
+        &[
+            ("header.json", header_json(1, 2)),
+            ("positions.3.float32", bytes_f32(&[0.0; 3])),
+            ("offsets.uint64", 0u64.to_le_bytes().to_vec()),
+        ],
+    );
+    assert!(matches!(Trx::load(dir.path()), Err(Error::InvalidHeader(_))));
+
+    // Huge counts must not overflow nor allocate
+    let dir = TempDir::new().unwrap();
+    write_directory(
+        &dir,
+        &[
+            ("header.json", header_json(usize::MAX, 1)),
+            ("positions.3.float32", bytes_f32(&[0.0; 3])),
+            ("offsets.uint64", vec![]),
+        ],
+    );
+    assert!(matches!(Trx::load(dir.path()), Err(Error::InvalidHeader(_))));
+    let dir = TempDir::new().unwrap();
+    write_directory(
+        &dir,
+        &[
+            ("header.json", header_json(1, usize::MAX / 2)),
+            ("positions.3.float32", bytes_f32(&[0.0; 3])),
+            ("offsets.uint64", 0u64.to_le_bytes().to_vec()),
+        ],
+    );
+    assert!(matches!(Trx::load(dir.path()), Err(Error::InvalidHeader(_))));
+    let dir = TempDir::new().unwrap();
+    write_directory(
+        &dir,
+        &[
+            ("header.json", header_json(1, 1)),
+            ("positions.3.float32", bytes_f32(&[0.0; 3])),
+            ("offsets.uint64", 0u64.to_le_bytes().to_vec()),
+            (&format!("dpv/fa.{}.float32", usize::MAX / 2), bytes_f32(&[0.0; 2])),
+        ],
+    );
+    assert!(matches!(Trx::load(dir.path()), Err(Error::InvalidHeader(_))));
+
+    // Not a zip file
+    assert!(Trx::load("data/simple.trk").is_err());
+}
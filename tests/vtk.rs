@@ -0,0 +1,250 @@
+mod test;
+
+use std::fs;
+
+use anyhow::Result;
+use tempfile::TempDir;
+
+use test::{load_trk, temp_path};
+use trk_io::{
+    vtk::{read_vtk, write_vtk, VtkFormat},
+    vtp::{read_vtp, write_vtp, VtpEncoding},
+    ArraySequence, Error, Header, Point,
+};
+
+#[test]
+fn test_vtk_round_trip() -> Result<()> {
+    let dir = TempDir::new()?;
+    for path in ["data/empty.trk", "data/simple.trk", "data/complex.trk"] {
+        let (header, tractogram) = load_trk(path);
+        for format in [VtkFormat::Ascii, VtkFormat::Binary] {
+            let vtk_path = temp_path(&dir, "out.vtk");
+            write_vtk(&vtk_path, &tractogram, &header, format)?;
+            let (vtk_header, vtk_tractogram) = read_vtk(&vtk_path)?;
+            assert_eq!(vtk_tractogram, tractogram);
+            assert_eq!(vtk_header.nb_streamlines, tractogram.streamlines.len());
+            assert_eq!(vtk_header.scalars_name, header.scalars_name);
+            assert_eq!(vtk_header.properties_name, header.properties_name);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_vtp_round_trip() -> Result<()> {
+    let dir = TempDir::new()?;
+    let encodings = [
+        VtpEncoding::Ascii,
+        VtpEncoding::Binary,
+        VtpEncoding::AppendedRaw,
+        VtpEncoding::AppendedBase64,
+    ];
+    for path in ["data/empty.trk", "data/simple.trk", "data/complex.trk"] {
+        let (header, tractogram) = load_trk(path);
+        for encoding in encodings {
+            let vtp_path = temp_path(&dir, "out.vtp");
+            write_vtp(&vtp_path, &tractogram, &header, encoding)?;
+            let (vtp_header, vtp_tractogram) = read_vtp(&vtp_path)?;
+            assert_eq!(vtp_tractogram, tractogram);
+            assert_eq!(vtp_header.scalars_name, header.scalars_name);
+            assert_eq!(vtp_header.properties_name, header.properties_name);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_read_vtk_shared_points() -> Result<()> {
+    // Both lines use the middle point, and there's a vertex before the lines in the cell data
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "shared.vtk");
+    fs::write(
+        &path,
+        "# vtk DataFile Version 4.2\n\
+         Some title\n\
+         ASCII\n\
+         DATASET POLYDATA\n\
+         POINTS 3 double\n\
+         0 0 0 1 1 1\n\
+         2 2 2\n\
+         VERTICES 1 2\n\
+         1 0\n\
+         LINES 2 7\n\
+         2 0 1\n\
+         3 2 1 0\n\
+         POINT_DATA 3\n\
+         SCALARS fa float\n\
+         LOOKUP_TABLE default\n\
+         0.5 1.5 2.5\n\
+         CELL_DATA 3\n\
+         FIELD FieldData 1\n\
+         weight 1 3 int\n\
+         7 8 9\n",
+    )?;
+    let (header, tractogram) = read_vtk(&path)?;
+    assert_eq!(header.scalars_name, vec!["fa"]);
+    assert_eq!(header.properties_name, vec!["weight"]);
+    assert_eq!(tractogram.streamlines.len(), 2);
+    assert_eq!(tractogram.streamlines[0], [Point::origin(), Point::new(1.0, 1.0, 1.0)]);
+    assert_eq!(tractogram.streamlines[1][0], Point::new(2.0, 2.0, 2.0));
+    assert_eq!(tractogram.scalars[1], [2.5, 1.5, 0.5]);
+    assert_eq!(tractogram.properties[0], [8.0]);
+    assert_eq!(tractogram.properties[1], [9.0]);
+    Ok(())
+}
+
+#[test]
+fn test_read_vtk_offsets_connectivity() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "vtk51.vtk");
+    fs::write(
+        &path,
+        "# vtk DataFile Version 5.1\n\
+         vtk output\n\
+         ASCII\n\
+         DATASET POLYDATA\n\
+         POINTS 3 float\n\
+         0 0 0 1 1 1 2 2 2\n\
+         METADATA\n\
+         INFORMATION 0\n\
+         \n\
+         LINES 3 3\n\
+         OFFSETS vtktypeint64\n\
+         0 1 3\n\
+         CONNECTIVITY vtktypeint64\n\
+         0 1 2\n",
+    )?;
+    let (header, tractogram) = read_vtk(&path)?;
+    assert_eq!(header.nb_streamlines, 2);
+    assert_eq!(tractogram.streamlines[0], [Point::origin()]);
+    assert_eq!(tractogram.streamlines[1].len(), 2);
+    assert!(tractogram.scalars.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_read_vtp_uint32_headers() -> Result<()> {
+    // The points are encoded with a separate header, the scalars with a joint header
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "header32.vtp");
+    fs::write(
+        &path,
+        r#"<?xml version="1.0"?>
+<VTKFile type="PolyData" version="0.1" byte_order="LittleEndian">
+  <!-- Written by hand -->
+  <PolyData>
+    <Piece NumberOfPoints="3" NumberOfLines="1">
+      <PointData Scalars="fa">
+        <DataArray type="Float32" Name="f&amp;a" format="binary">
+          DAAAAAAAAD8AAMA/AAAgQA==
+        </DataArray>
+      </PointData>
+      <Points>
+        <DataArray type="Float32" NumberOfComponents="3" format="binary">
+          JAAAAA==AAAAAAAAAAAAAAAAAACAPwAAgD8AAIA/AAAAQAAAAEAAAABA
+        </DataArray>
+      </Points>
+      <Lines>
+        <DataArray type="Int32" Name="connectivity" format="ascii">0 1 2</DataArray>
+        <DataArray type="Int32" Name="offsets" format="ascii">3</DataArray>
+      </Lines>
+    </Piece>
+  </PolyData>
+</VTKFile>
+"#,
+    )?;
+    let (header, tractogram) = read_vtp(&path)?;
+    assert_eq!(header.scalars_name, vec!["f&a"]);
+    assert_eq!(tractogram.streamlines[0][2], Point::new(2.0, 2.0, 2.0));
+    assert_eq!(tractogram.scalars[0], [0.5, 1.5, 2.5]);
+    Ok(())
+}
+
+#[test]
+fn test_write_invalid() {
+    let dir = TempDir::new().unwrap();
+    let path = temp_path(&dir, "out.vtk");
+    let (mut header, tractogram) = load_trk("data/complex.trk");
+
+    // The header doesn't describe the tractogram
+    let empty_header = Header::default();
+    assert!(matches!(
+        write_vtk(&path, &tractogram, &empty_header, VtkFormat::Ascii),
+        Err(Error::InvalidHeader(_))
+    ));
+
+    // Legacy VTK names can't hold spaces
+    header.clear_properties();
+    for name in ["mean fa", "a", "b", "c", "d", "e"] {
+        header.add_property(name).unwrap();
+    }
+    let mut tractogram = tractogram;
+    tractogram.properties = ArraySequence::new(
+        vec![6; tractogram.streamlines.len()],
+        vec![0.0; 6 * tractogram.streamlines.len()],
+    );
+    assert!(matches!(
+        write_vtk(&path, &tractogram, &header, VtkFormat::Binary),
+        Err(Error::InvalidName(_))
+    ));
+    assert!(write_vtp(temp_path(&dir, "out.vtp"), &tractogram, &header, VtpEncoding::Ascii).is_ok());
+}
+
+#[test]
+fn test_read_vtk_invalid_text() -> Result<()> {
+    // A keyword that isn't valid text must not end the file silently
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "invalid.vtk");
+    let mut bytes = b"# vtk DataFile Version 4.2\ninvalid\nASCII\nDATASET POLYDATA\n".to_vec();
+    bytes.extend_from_slice(b"POINTS 2 float\n0 0 0 1 0 0\n\xff\xfe\nLINES 1 3\n2 0 1\n");
+    fs::write(&path, bytes)?;
+    assert!(matches!(read_vtk(&path), Err(Error::InvalidHeader(_))));
+    Ok(())
+}
+
+#[test]
+fn test_read_vtk_huge_counts() -> Result<()> {
+    // Counts read in the file must be checked before allocating anything
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "huge.vtk");
+    let start = "# vtk DataFile Version 4.2\nhuge\nASCII\nDATASET POLYDATA\n";
+    for body in [
+        "LINES 99999999999999 0\n",
+        "LINES 2 99999999999999\n2 0 1\n",
+        "POINTS 99999999999999 float\n0 0 0\n",
+        "POINTS 9999999999999999999 float\n0 0 0\n",
+        "POINTS 1 float\n0 0 0\nPOINT_DATA 1\nFIELD f 1\nfa 99999999999 99999999999 float\n0\n",
+    ] {
+        fs::write(&path, format!("{}{}", start, body))?;
+        assert!(matches!(read_vtk(&path), Err(Error::InvalidHeader(_))), "{}", body);
+    }
+
+    let binary = "# vtk DataFile Version 4.2\nhuge\nBINARY\nDATASET POLYDATA\n";
+    fs::write(&path, format!("{}POINTS 9999999999999999999 double\n\0\0\0\0", binary))?;
+    assert!(matches!(read_vtk(&path), Err(Error::InvalidHeader(_))));
+    Ok(())
+}
+
+#[test]
+fn test_read_vtp_huge_block_size() -> Result<()> {
+    let dir = TempDir::new()?;
+    let path = temp_path(&dir, "huge.vtp");
+    fs::write(
+        &path,
+        r#"<?xml version="1.0"?>
+<VTKFile type="PolyData" version="1.0" byte_order="LittleEndian" header_type="UInt64">
+  <PolyData>
+    <Piece NumberOfPoints="1" NumberOfLines="0">
+      <Points>
+        <DataArray type="Float32" NumberOfComponents="3" format="binary">
+          //////////8=AAAAAAAAAAAAAAAA
+        </DataArray>
+      </Points>
+    </Piece>
+  </PolyData>
+</VTKFile>
+"#,
+    )?;
+    assert!(read_vtp(&path).is_err());
+    Ok(())
+}